@@ -19,6 +19,10 @@ pub struct HttpCodec {
     span: Span,
     // The next kind of HTTP message expected.
     expecting: HttpMsg,
+    // Headers of the last complete HTTP message.
+    headers: Vec<(String, String)>,
+    // Path of the last complete HTTP request.
+    path: Option<String>,
 }
 
 impl HttpCodec {
@@ -27,8 +31,20 @@ impl HttpCodec {
             codec: Default::default(),
             span,
             expecting,
+            headers: Vec::new(),
+            path: None,
         }
     }
+
+    /// Takes the headers of the last decoded HTTP message.
+    pub fn take_headers(&mut self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.headers)
+    }
+
+    /// The path of the last decoded HTTP request.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
 }
 
 impl Decoder for HttpCodec {
@@ -49,7 +65,9 @@ impl Decoder for HttpCodec {
         let res = match self.expecting {
             HttpMsg::Request => {
                 let mut req = httparse::Request::new(&mut headers);
-                req.parse(&raw_bytes)
+                let res = req.parse(&raw_bytes);
+                self.path = req.path.map(str::to_owned);
+                res
             }
             HttpMsg::Response => {
                 let mut resp = httparse::Response::new(&mut headers);
@@ -68,6 +86,16 @@ impl Decoder for HttpCodec {
                 Ok(None)
             }
             httparse::Status::Complete(header_length) => {
+                self.headers = headers
+                    .iter()
+                    .take_while(|header| !header.name.is_empty())
+                    .map(|header| {
+                        (
+                            header.name.to_owned(),
+                            String::from_utf8_lossy(header.value).into_owned(),
+                        )
+                    })
+                    .collect();
                 raw_bytes.advance(header_length);
 
                 Ok(Some(raw_bytes))
@@ -29,7 +29,6 @@ enum Compression {
 
 #[derive(Debug)]
 pub struct Header {
-    #[allow(dead_code)]
     total_wire_size: u32,
     #[allow(dead_code)]
     header_size: u32,
@@ -41,6 +40,18 @@ pub struct Header {
     compression: Compression,
 }
 
+impl Header {
+    /// The size of the whole message on the wire, including the header.
+    pub fn total_wire_size(&self) -> u32 {
+        self.total_wire_size
+    }
+
+    /// The message type, as [MessageType] if it's a known one.
+    pub fn message_type(&self) -> Option<MessageType> {
+        MessageType::from_i32(self.message_type.into())
+    }
+}
+
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum Payload {
@@ -69,6 +80,37 @@ pub enum Payload {
     TmTransactions(TmTransactions),
 }
 
+impl Payload {
+    /// The message type used in the header for this payload.
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Payload::TmManifests(_) => MessageType::MtManifests,
+            Payload::TmPing(_) => MessageType::MtPing,
+            Payload::TmCluster(_) => MessageType::MtCluster,
+            Payload::TmEndpoints(_) => MessageType::MtEndpoints,
+            Payload::TmTransaction(_) => MessageType::MtTransaction,
+            Payload::TmGetLedger(_) => MessageType::MtGetLedger,
+            Payload::TmLedgerData(_) => MessageType::MtLedgerData,
+            Payload::TmProposeLedger(_) => MessageType::MtProposeLedger,
+            Payload::TmStatusChange(_) => MessageType::MtStatusChange,
+            Payload::TmHaveTransactions(_) => MessageType::MtHaveTransactions,
+            Payload::TmHaveSet(_) => MessageType::MtHaveSet,
+            Payload::TmValidation(_) => MessageType::MtValidation,
+            Payload::TmGetObjectByHash(_) => MessageType::MtGetObjects,
+            Payload::TmValidatorList(_) => MessageType::MtValidatorlist,
+            Payload::TmSquelch(_) => MessageType::MtSquelch,
+            Payload::TmValidatorListCollection(_) => MessageType::MtValidatorlistcollection,
+            Payload::TmProofPathRequest(_) => MessageType::MtProofPathReq,
+            Payload::TmProofPathResponse(_) => MessageType::MtProofPathResponse,
+            Payload::TmReplayDeltaRequest(_) => MessageType::MtReplayDeltaReq,
+            Payload::TmReplayDeltaResponse(_) => MessageType::MtReplayDeltaResponse,
+            Payload::TmGetPeerShardInfoV2(_) => MessageType::MtGetPeerShardInfoV2,
+            Payload::TmPeerShardInfoV2(_) => MessageType::MtPeerShardInfoV2,
+            Payload::TmTransactions(_) => MessageType::MtTransactions,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct BinaryMessage {
     pub header: Header,
@@ -77,6 +119,8 @@ pub struct BinaryMessage {
 
 pub struct MessageCodec {
     current_msg_header: Option<Header>,
+    // A copy of the consumed bytes, if they're kept.
+    consumed: Option<BytesMut>,
     // The associated node's span.
     span: Span,
 }
@@ -85,9 +129,33 @@ impl MessageCodec {
     pub fn new(span: Span) -> Self {
         Self {
             current_msg_header: None,
+            consumed: None,
             span,
         }
     }
+
+    /// Keeps a copy of the bytes consumed by the decoder, e.g. to record them.
+    pub fn keep_consumed(mut self) -> Self {
+        self.consumed = Some(BytesMut::new());
+        self
+    }
+
+    /// Returns the bytes consumed since the last call, if they're kept.
+    pub fn take_consumed(&mut self) -> BytesMut {
+        self.consumed
+            .as_mut()
+            .map(|consumed| consumed.split())
+            .unwrap_or_default()
+    }
+
+    // Splits the given number of bytes off the front of the buffer, keeping a copy if needed.
+    fn consume(&mut self, src: &mut BytesMut, len: usize) -> BytesMut {
+        let bytes = src.split_to(len);
+        if let Some(consumed) = &mut self.consumed {
+            consumed.extend_from_slice(&bytes);
+        }
+        bytes
+    }
 }
 
 impl Decoder for MessageCodec {
@@ -114,7 +182,7 @@ impl Decoder for MessageCodec {
                     unimplemented!();
                 }
 
-                let header_bytes = src.split_to(header_size as usize);
+                let header_bytes = self.consume(src, header_size as usize);
                 let mut iter = header_bytes.into_iter();
 
                 let compression = src[0] & COMPRESSION_ALGO;
@@ -161,7 +229,7 @@ impl Decoder for MessageCodec {
                     return Ok(None);
                 }
 
-                let header_bytes = src.split_to(header_size as usize);
+                let header_bytes = self.consume(src, header_size as usize);
                 let mut iter = header_bytes.into_iter();
 
                 let mut payload_wire_size = 0;
@@ -203,7 +271,7 @@ impl Decoder for MessageCodec {
             }
 
             let header = self.current_msg_header.take().unwrap();
-            let mut payload = src.split_to(payload_wire_size as usize);
+            let mut payload = self.consume(src, payload_wire_size as usize);
 
             let payload = match header.message_type {
                 2 => Payload::TmManifests(Message::decode(&mut payload)?),
@@ -0,0 +1,18 @@
+use std::net::SocketAddr;
+
+use pea2pea::{protocols::Disconnect, Pea2Pea};
+use tracing::*;
+
+use crate::tools::{events::ConnectionEventKind, inner_node::InnerNode};
+
+#[async_trait::async_trait]
+impl Disconnect for InnerNode {
+    async fn handle_disconnect(&self, addr: SocketAddr) {
+        let reason = self.events.take_reason(addr);
+        self.shaping.detach(addr);
+        debug!(parent: self.node().span(), "disconnected from {addr}: {reason:?}");
+
+        self.events
+            .emit(addr, ConnectionEventKind::Disconnected(reason));
+    }
+}
@@ -34,7 +34,7 @@
 //! > \r\n"
 //! ---------------------
 
-use std::{io, pin::Pin};
+use std::{io, net::SocketAddr, pin::Pin};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::Bytes;
@@ -43,13 +43,18 @@ use openssl::ssl::Ssl;
 use pea2pea::{protocols::Handshake, Connection, ConnectionSide, Pea2Pea};
 use rand::{thread_rng, Rng};
 use sha2::{Digest, Sha512};
+use tokio::net::TcpStream;
 use tokio_openssl::SslStream;
 use tokio_util::codec::Framed;
 use tracing::*;
 
 use crate::{
     protocol::codecs::http::{HttpCodec, HttpMsg},
-    tools::inner_node::{Crypto, InnerNode},
+    tools::{
+        events::{ConnectionEventKind, DisconnectReason, PeerInfo},
+        inner_node::{Crypto, InnerNode},
+        stream::PeerStream,
+    },
 };
 
 // Default handshake header values.
@@ -68,6 +73,37 @@ enum NodeType {
     Private = 32,
 }
 
+/// The layers established on a connection before it's handed over for reading and writing.
+///
+/// Any bytes can be written in every mode with
+/// [unicast_bytes](crate::tools::synth_node::SyntheticNode::unicast_bytes), so each layer
+/// can be targeted deliberately.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionMode {
+    /// Plain TCP without TLS, the messages are framed as usual.
+    Tcp,
+    /// TLS without the HTTP upgrade, the messages are framed as usual.
+    Tls,
+    /// TLS and the HTTP upgrade, without any message framing afterwards.
+    ///
+    /// Inbound bytes are recorded and counted as raw bytes, but never decoded: whatever the
+    /// peer answers to malformed frames would likely fail the decoding and close the
+    /// connection, hiding the peer's own reaction. The answers can be examined in the
+    /// [traffic statistics](crate::tools::synth_node::SyntheticNode::traffic_stats) or the
+    /// [capture](crate::tools::config::SynthNodeCfg::capture_path) instead.
+    TlsHttp,
+    /// TLS, the HTTP upgrade and message framing, i.e. a regular peer connection.
+    #[default]
+    Full,
+}
+
+impl ConnectionMode {
+    /// Whether the messages are framed once the connection is established.
+    pub fn is_framed(&self) -> bool {
+        !matches!(self, Self::TlsHttp)
+    }
+}
+
 /// Handshake configuration allows some customization of the handshake procedure.
 #[derive(Clone)]
 pub struct HandshakeCfg {
@@ -195,33 +231,116 @@ fn get_shared_value<S>(tls_stream: &SslStream<S>) -> io::Result<Vec<u8>> {
 impl Handshake for InnerNode {
     async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
         let own_conn_side = !conn.side();
-        let stream = self.take_stream(&mut conn);
         let addr = conn.addr();
+        let stream = PeerStream::new(
+            self.take_stream(&mut conn),
+            addr,
+            self.events.clone(),
+            self.shaping.attach(addr),
+        );
+
+        self.events
+            .emit(addr, ConnectionEventKind::Connected(own_conn_side));
+
+        // Without TLS the connection continues over the plain stream.
+        if self.mode == ConnectionMode::Tcp {
+            self.events.emit(
+                addr,
+                ConnectionEventKind::HandshakeCompleted(PeerInfo::default()),
+            );
+            self.return_stream(&mut conn, stream);
+            return Ok(conn);
+        }
+
+        match self.secure_stream(stream, own_conn_side, addr).await {
+            Ok((tls_stream, headers)) => {
+                self.events.emit(
+                    addr,
+                    ConnectionEventKind::HandshakeCompleted(PeerInfo::from_headers(headers)),
+                );
+                self.return_stream(&mut conn, tls_stream);
+
+                Ok(conn)
+            }
+            Err(e) => {
+                // The handshake failure is more telling than the transport-level reason.
+                let _ = self.events.take_reason(addr);
+                self.shaping.detach(addr);
+                self.events.emit(
+                    addr,
+                    ConnectionEventKind::Disconnected(DisconnectReason::Handshake(e.to_string())),
+                );
+
+                Err(e)
+            }
+        }
+    }
+}
+
+impl InnerNode {
+    // Performs the TLS handshake followed by the HTTP upgrade, unless the mode skips it.
+    // Returns the TLS stream together with the headers received from the peer.
+    async fn secure_stream(
+        &self,
+        stream: PeerStream<TcpStream>,
+        side: ConnectionSide,
+        addr: SocketAddr,
+    ) -> io::Result<(SslStream<PeerStream<TcpStream>>, Vec<(String, String)>)> {
+        let mut tls_stream = self.tls_handshake(stream, side).await?;
+
+        let headers = if self.mode == ConnectionMode::Tls {
+            Vec::new()
+        } else {
+            self.http_upgrade(&mut tls_stream, side, addr).await?
+        };
+
+        Ok((tls_stream, headers))
+    }
 
-        // The function shouldn't be called in case the handshake config is not set.
-        let hs_cfg = self
-            .handshake_cfg
-            .as_ref()
-            .expect("a handshake config is not set");
+    /// Performs the TLS handshake.
+    pub(crate) async fn tls_handshake(
+        &self,
+        stream: PeerStream<TcpStream>,
+        side: ConnectionSide,
+    ) -> io::Result<SslStream<PeerStream<TcpStream>>> {
+        let ssl = match side {
+            ConnectionSide::Initiator => self
+                .tls
+                .connector
+                .configure()
+                .unwrap()
+                .into_ssl("domain") // is SNI and hostname verification enabled?
+                .unwrap(),
+            ConnectionSide::Responder => Ssl::new(self.tls.acceptor.context()).unwrap(),
+        };
+        let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+
+        match side {
+            ConnectionSide::Initiator => Pin::new(&mut tls_stream).connect().await,
+            ConnectionSide::Responder => Pin::new(&mut tls_stream).accept().await,
+        }
+        .map_err(|e| {
+            error!(parent: self.node().span(), "TLS handshake error: {e}");
+            io::ErrorKind::InvalidData
+        })?;
+
+        Ok(tls_stream)
+    }
 
-        let tls_stream = match own_conn_side {
+    // Performs the HTTP upgrade over an established TLS stream.
+    // Returns the headers received from the peer.
+    async fn http_upgrade(
+        &self,
+        tls_stream: &mut SslStream<PeerStream<TcpStream>>,
+        side: ConnectionSide,
+        addr: SocketAddr,
+    ) -> io::Result<Vec<(String, String)>> {
+        let headers = match side {
             ConnectionSide::Initiator => {
-                let ssl = self
-                    .tls
-                    .connector
-                    .configure()
-                    .unwrap()
-                    .into_ssl("domain") // is SNI and hostname verification enabled?
-                    .unwrap();
-                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
-
-                Pin::new(&mut tls_stream).connect().await.map_err(|e| {
-                    error!(parent: self.node().span(), "TLS handshake error: {e}");
-                    io::ErrorKind::InvalidData
-                })?;
+                let hs_cfg = &self.handshake_cfg;
 
                 // get the shared value based on the TLS handshake
-                let mut shared_value = get_shared_value(&tls_stream)?;
+                let mut shared_value = get_shared_value(tls_stream)?;
 
                 let public_key = &mut self.crypto.public_key.serialize().clone();
                 // introduce intentional errors into handshake if needed
@@ -272,7 +391,7 @@ impl Handshake for InnerNode {
                 // use the HTTP codec to read/write the (post-TLS) handshake messages
                 let req = Bytes::from(req);
                 let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Response);
-                let mut framed = Framed::new(&mut tls_stream, codec);
+                let mut framed = Framed::new(tls_stream, codec);
 
                 // send the handshake HTTP request message
                 trace!(parent: self.node().span(), "sending a request to {addr}: {req:?}");
@@ -280,91 +399,209 @@ impl Handshake for InnerNode {
 
                 // read the HTTP request message (there should only be headers)
                 let _ = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
-
-                tls_stream
+                framed.codec_mut().take_headers()
             }
             ConnectionSide::Responder => {
-                let ssl = Ssl::new(self.tls.acceptor.context()).unwrap();
-                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+                let (_, headers) = self.read_upgrade_request(tls_stream, addr).await?;
+                self.accept_upgrade(tls_stream, addr).await?;
 
-                Pin::new(&mut tls_stream).accept().await.map_err(|e| {
-                    error!(parent: self.node().span(), "TLS handshake error: {e}");
-                    io::ErrorKind::InvalidData
-                })?;
+                headers
+            }
+        };
 
-                // get the shared value based on the TLS handshake
-                let mut shared_value = get_shared_value(&tls_stream)?;
+        Ok(headers)
+    }
 
-                // use the HTTP codec to read/write the (post-TLS) handshake messages
-                let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Request);
-                let mut framed = Framed::new(&mut tls_stream, codec);
+    /// Reads the HTTP request of an inbound handshake without answering it, so a peer serving
+    /// other requests on the peer port can tell them apart, see [accept_upgrade](Self::accept_upgrade).
+    ///
+    /// Returns the requested path along with the headers.
+    pub(crate) async fn read_upgrade_request(
+        &self,
+        tls_stream: &mut SslStream<PeerStream<TcpStream>>,
+        addr: SocketAddr,
+    ) -> io::Result<(Option<String>, Vec<(String, String)>)> {
+        // use the HTTP codec to read the (post-TLS) handshake request
+        let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Request);
+        let mut framed = Framed::new(tls_stream, codec);
+
+        // read the HTTP request message (there should only be headers)
+        let request_body = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
+        if !request_body.is_empty() {
+            warn!(parent: self.node().span(), "trailing bytes in the handshake request from {addr}: {request_body:?}");
+        }
 
-                // read the HTTP request message (there should only be headers)
-                let request_body = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
-                if !request_body.is_empty() {
-                    warn!(parent: self.node().span(), "trailing bytes in the handshake request from {addr}: {request_body:?}");
-                }
+        let path = framed.codec().path().map(str::to_owned);
+        Ok((path, framed.codec_mut().take_headers()))
+    }
 
-                let public_key = &mut self.crypto.public_key.serialize().clone();
-                // introduce intentional errors into handshake if needed
-                if hs_cfg.bitflip_shared_val {
-                    randomly_flip_bit(&mut shared_value);
-                }
-                if hs_cfg.bitflip_pub_key {
-                    randomly_flip_bit(public_key.as_mut_slice());
-                }
-                // base58-encode the public key and create the session signature
-                let base58_pk = encode_base58(NodeType::Public, public_key);
-                let sig = create_session_signature(&self.crypto, &shared_value);
+    /// Answers the HTTP request read with [read_upgrade_request](Self::read_upgrade_request),
+    /// which completes an inbound handshake.
+    pub(crate) async fn accept_upgrade(
+        &self,
+        tls_stream: &mut SslStream<PeerStream<TcpStream>>,
+        addr: SocketAddr,
+    ) -> io::Result<()> {
+        let hs_cfg = &self.handshake_cfg;
+
+        // get the shared value based on the TLS handshake
+        let mut shared_value = get_shared_value(tls_stream)?;
+
+        let public_key = &mut self.crypto.public_key.serialize().clone();
+        // introduce intentional errors into handshake if needed
+        if hs_cfg.bitflip_shared_val {
+            randomly_flip_bit(&mut shared_value);
+        }
+        if hs_cfg.bitflip_pub_key {
+            randomly_flip_bit(public_key.as_mut_slice());
+        }
+        // base58-encode the public key and create the session signature
+        let base58_pk = encode_base58(NodeType::Public, public_key);
+        let sig = create_session_signature(&self.crypto, &shared_value);
+
+        // prepare the response
+        let mut rsp = Vec::new();
+        let mut rsp_header = |mut header: String| {
+            header.push_str("\r\n");
+            rsp.extend_from_slice(header.as_bytes());
+        };
 
-                // prepare the response
-                let mut rsp = Vec::new();
-                let mut rsp_header = |mut header: String| {
-                    header.push_str("\r\n");
-                    rsp.extend_from_slice(header.as_bytes());
-                };
+        rsp_header("HTTP/1.1 101 Switching Protocols".into());
+        rsp_header(format!("Connection: {}", hs_cfg.http_connection));
+        rsp_header(format!("Upgrade: {}", hs_cfg.http_upgrade_rsp));
+        rsp_header(format!("Connect-As: {}", hs_cfg.http_connect_as));
+        rsp_header(format!("Server: {}", hs_cfg.http_ident));
+        if let Some(ref crawl) = hs_cfg.http_crawl {
+            rsp_header(format!("Crawl: {crawl}"))
+        };
+        rsp_header(format!("X-Protocol-Ctl: {}", hs_cfg.http_x_protocol_ctl));
+        if let Some(ref time) = hs_cfg.http_network_time {
+            rsp_header(format!("Network-Time: {time}"))
+        };
+        rsp_header(format!("Public-Key: {base58_pk}"));
+        rsp_header(format!("Session-Signature: {sig}"));
+        if let Some(ref ledger) = hs_cfg.http_closed_ledger {
+            rsp_header(format!("Closed-Ledger: {ledger}"))
+        };
+        if let Some(ref ledger) = hs_cfg.http_prev_ledger {
+            rsp_header(format!("Previous-Ledger: {ledger}"))
+        };
+        if let Some(ref header) = hs_cfg.http_unexpected_extra_field_and_value {
+            rsp_header(header.clone())
+        };
+        rsp_header("".into()); // An HTTP header ends with '\r\n'
 
-                rsp_header("HTTP/1.1 101 Switching Protocols".into());
-                rsp_header(format!("Connection: {}", hs_cfg.http_connection));
-                rsp_header(format!("Upgrade: {}", hs_cfg.http_upgrade_rsp));
-                rsp_header(format!("Connect-As: {}", hs_cfg.http_connect_as));
-                rsp_header(format!("Server: {}", hs_cfg.http_ident));
-                if let Some(ref crawl) = hs_cfg.http_crawl {
-                    rsp_header(format!("Crawl: {crawl}"))
-                };
-                rsp_header(format!("X-Protocol-Ctl: {}", hs_cfg.http_x_protocol_ctl));
-                if let Some(ref time) = hs_cfg.http_network_time {
-                    rsp_header(format!("Network-Time: {time}"))
-                };
-                rsp_header(format!("Public-Key: {base58_pk}"));
-                rsp_header(format!("Session-Signature: {sig}"));
-                if let Some(ref ledger) = hs_cfg.http_closed_ledger {
-                    rsp_header(format!("Closed-Ledger: {ledger}"))
-                };
-                if let Some(ref ledger) = hs_cfg.http_prev_ledger {
-                    rsp_header(format!("Previous-Ledger: {ledger}"))
-                };
-                if let Some(ref header) = hs_cfg.http_unexpected_extra_field_and_value {
-                    rsp_header(header.clone())
-                };
-                rsp_header("".into()); // An HTTP header ends with '\r\n'
+        // send the handshake HTTP response message
+        let rsp = Bytes::from(rsp);
+        trace!(parent: self.node().span(), "responding to {addr} with {rsp:?}");
+        let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Request);
+        Framed::new(tls_stream, codec).send(rsp).await
+    }
+}
 
-                // send the handshake HTTP response message
-                let rsp = Bytes::from(rsp);
-                trace!(parent: self.node().span(), "responding to {addr} with {rsp:?}");
-                framed.send(rsp).await?;
+fn randomly_flip_bit(arr: &mut [u8]) {
+    let idx = thread_rng().gen_range(0..arr.len());
+    arr[idx] ^= 1 << thread_rng().gen_range(0..8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        tools::{
+            config::SynthNodeCfg,
+            mock_rippled::{MockRippled, MockRippledCfg},
+            synth_node::SyntheticNode,
+            testing::{is_pong, ping, ping_frame, TIMEOUT},
+            traffic::Direction,
+        },
+        wait_until,
+    };
+
+    // Connects a synthetic node to a mock, both using the given modes, and returns the
+    // peer information from the synthetic node's handshake.
+    async fn connect(
+        mock_mode: ConnectionMode,
+        synth_mode: ConnectionMode,
+    ) -> (MockRippled, SyntheticNode, PeerInfo) {
+        let mut mock_cfg = MockRippledCfg::default();
+        mock_cfg.node.mode = mock_mode;
+        let mock = MockRippled::new(mock_cfg).await.unwrap();
+
+        let synth_cfg = SynthNodeCfg {
+            mode: synth_mode,
+            ..Default::default()
+        };
+        let mut synth_node = SyntheticNode::new(&synth_cfg).await;
+        synth_node.connect(mock.addr()).await.unwrap();
 
-                tls_stream
+        let info = loop {
+            let event = synth_node.recv_event_timeout(TIMEOUT).await.unwrap();
+            if let ConnectionEventKind::HandshakeCompleted(info) = event.kind {
+                break info;
             }
         };
 
-        self.return_stream(&mut conn, tls_stream);
+        (mock, synth_node, info)
+    }
 
-        Ok(conn)
+    // Pings the mock and waits for the pong.
+    async fn ping_pong(mock: &MockRippled, synth_node: &mut SyntheticNode) {
+        synth_node.unicast(mock.addr(), ping(1)).unwrap();
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert!(is_pong(&message.payload, 1));
     }
-}
 
-fn randomly_flip_bit(arr: &mut [u8]) {
-    let idx = thread_rng().gen_range(0..arr.len());
-    arr[idx] ^= 1 << thread_rng().gen_range(0..8);
+    #[tokio::test]
+    async fn tcp_mode() {
+        let (mock, mut synth_node, info) = connect(ConnectionMode::Tcp, ConnectionMode::Tcp).await;
+
+        // There are no headers without the HTTP upgrade.
+        assert!(info.headers.is_empty());
+        ping_pong(&mock, &mut synth_node).await;
+    }
+
+    #[tokio::test]
+    async fn tls_mode() {
+        let (mock, mut synth_node, info) = connect(ConnectionMode::Tls, ConnectionMode::Tls).await;
+
+        assert!(info.headers.is_empty());
+        ping_pong(&mock, &mut synth_node).await;
+    }
+
+    #[tokio::test]
+    async fn tls_http_mode() {
+        let (mock, mut synth_node, info) =
+            connect(ConnectionMode::Full, ConnectionMode::TlsHttp).await;
+        assert!(info.public_key.is_some());
+
+        // The frame has to be encoded by hand, and the pong is only counted as raw bytes.
+        synth_node
+            .unicast_bytes(mock.addr(), ping_frame(1).to_vec())
+            .unwrap();
+
+        wait_until!(TIMEOUT, {
+            let stats = synth_node.traffic_stats();
+            stats
+                .by_type(Direction::Inbound)
+                .get(&None)
+                .is_some_and(|totals| totals.bytes > 0)
+        });
+        assert!(synth_node
+            .recv_message_timeout(Duration::from_millis(100))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn full_mode() {
+        let (mock, mut synth_node, info) =
+            connect(ConnectionMode::Full, ConnectionMode::Full).await;
+
+        assert!(info.public_key.is_some());
+        assert_eq!(info.ident.as_deref(), Some("rippled-1.9.4"));
+        ping_pong(&mock, &mut synth_node).await;
+    }
 }
@@ -1,6 +1,7 @@
 //! An implementation of the Ripple network protocol types and messages.
 
 pub mod codecs;
+pub mod disconnect;
 pub mod handshake;
 pub mod proto;
 pub mod reading;
@@ -1,20 +1,146 @@
 use std::{io, net::SocketAddr};
 
+use bytes::BytesMut;
 use pea2pea::{protocols::Reading, ConnectionSide, Pea2Pea};
+use tokio_util::codec::Decoder;
 use tracing::*;
 
 use crate::{
     protocol::codecs::message::{BinaryMessage, MessageCodec},
-    tools::inner_node::InnerNode,
+    tools::{
+        capture::{RecordKind, Recorder},
+        events::{ConnectionEvents, DisconnectReason},
+        inner_node::InnerNode,
+        traffic::{Direction, TrafficStats},
+    },
 };
 
+/// Wraps the [MessageCodec] for inbound connections to keep the traffic statistics, record
+/// the traffic and note why the inbound stream has ended.
+pub struct InboundCodec {
+    codec: MessageCodec,
+    addr: SocketAddr,
+    events: ConnectionEvents,
+    stats: TrafficStats,
+    recorder: Option<Recorder>,
+    // Bytes of the frame being decoded, only kept while recording.
+    frame: BytesMut,
+    // Whether the inbound bytes are decoded as messages.
+    framed: bool,
+}
+
+impl InboundCodec {
+    // Consumes the unframed inbound bytes, keeping them only in the statistics and the capture.
+    fn discard(&mut self, src: &mut BytesMut) {
+        if src.is_empty() {
+            return;
+        }
+
+        let bytes = src.split();
+        self.stats
+            .record(Direction::Inbound, None, self.addr, bytes.len());
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record(Direction::Inbound, RecordKind::Raw, self.addr, &bytes)
+            {
+                warn!("couldn't record inbound bytes from {}: {}", self.addr, e);
+            }
+        }
+    }
+
+    // Records the bytes consumed by the last decoding attempt.
+    fn record(&mut self, src: &BytesMut, result: &io::Result<Option<BinaryMessage>>) {
+        let recorder = match &self.recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        let consumed = self.codec.take_consumed();
+        self.frame.unsplit(consumed);
+
+        let kind = match result {
+            Ok(Some(_)) => RecordKind::Frame,
+            Ok(None) => return,
+            // Keep whatever couldn't be decoded for the analysis.
+            Err(_) => {
+                self.frame.extend_from_slice(src);
+                RecordKind::Raw
+            }
+        };
+
+        let frame = self.frame.split();
+        if let Err(e) = recorder.record(Direction::Inbound, kind, self.addr, &frame) {
+            warn!("couldn't record an inbound frame from {}: {}", self.addr, e);
+        }
+    }
+}
+
+impl Decoder for InboundCodec {
+    type Item = BinaryMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.framed {
+            self.discard(src);
+            return Ok(None);
+        }
+
+        let result = self.codec.decode(src);
+        self.record(src, &result);
+
+        let message = result.inspect_err(|e| {
+            self.events
+                .set_reason(self.addr, DisconnectReason::Codec(e.to_string()));
+        })?;
+
+        if let Some(message) = &message {
+            self.stats.record(
+                Direction::Inbound,
+                message.header.message_type(),
+                self.addr,
+                message.header.total_wire_size() as usize,
+            );
+        }
+
+        Ok(message)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(message) => Ok(Some(message)),
+            None if src.is_empty() => {
+                self.events.set_reason(self.addr, DisconnectReason::Eof);
+                Ok(None)
+            }
+            None => {
+                let reason = format!("{} trailing bytes in an incomplete frame", src.len());
+                self.events
+                    .set_reason(self.addr, DisconnectReason::Codec(reason.clone()));
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, reason))
+            }
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl Reading for InnerNode {
     type Message = BinaryMessage;
-    type Codec = MessageCodec;
+    type Codec = InboundCodec;
+
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        let mut codec = MessageCodec::new(self.node().span().clone());
+        if self.recorder.is_some() {
+            codec = codec.keep_consumed();
+        }
 
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Self::Codec::new(self.node().span().clone())
+        InboundCodec {
+            codec,
+            addr,
+            events: self.events.clone(),
+            stats: self.stats.clone(),
+            recorder: self.recorder.clone(),
+            frame: BytesMut::new(),
+            framed: self.mode.is_framed(),
+        }
     }
 
     async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
@@ -30,3 +156,59 @@ impl Reading for InnerNode {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tracing::Span;
+
+    use super::*;
+    use crate::tools::{capture::read_capture, testing::ping_frame};
+
+    #[test]
+    fn record_consumed_frames() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("traffic.zgcap");
+        let addr: SocketAddr = "127.0.0.1:51235".parse().unwrap();
+        let mut codec = InboundCodec {
+            codec: MessageCodec::new(Span::none()).keep_consumed(),
+            addr,
+            events: Default::default(),
+            stats: Default::default(),
+            recorder: Some(Recorder::create(&path).unwrap()),
+            frame: BytesMut::new(),
+            framed: true,
+        };
+
+        // Two frames and a half arrive at once, the rest of the third one later.
+        let third = ping_frame(3);
+        let mut src = ping_frame(1);
+        src.extend_from_slice(&ping_frame(2));
+        src.extend_from_slice(&third[..4]);
+        assert!(codec.decode(&mut src).unwrap().is_some());
+        assert!(codec.decode(&mut src).unwrap().is_some());
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&third[4..]);
+        assert!(codec.decode(&mut src).unwrap().is_some());
+        assert!(src.is_empty());
+
+        // An invalid compression indicator.
+        src.extend_from_slice(&[0x7f, 0xff]);
+        assert!(codec.decode(&mut src).is_err());
+
+        let records = read_capture(&path).unwrap();
+        let frames = records
+            .iter()
+            .map(|record| (record.kind, record.bytes.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames,
+            [
+                (RecordKind::Frame, ping_frame(1).to_vec()),
+                (RecordKind::Frame, ping_frame(2).to_vec()),
+                (RecordKind::Frame, third.to_vec()),
+                (RecordKind::Raw, vec![0x7f, 0xff]),
+            ]
+        );
+    }
+}
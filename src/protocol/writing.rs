@@ -1,12 +1,18 @@
 use std::{io, net::SocketAddr};
 
 use bytes::{BufMut, BytesMut};
+use futures_util::future::join_all;
 use pea2pea::{protocols::Writing, ConnectionSide, Pea2Pea};
 use tokio_util::codec::Encoder;
+use tracing::*;
 
 use crate::{
     protocol::codecs::message::{MessageCodec, Payload},
-    tools::inner_node::InnerNode,
+    tools::{
+        capture::{RecordKind, Recorder},
+        inner_node::InnerNode,
+        traffic::{Direction, TrafficStats},
+    },
 };
 
 impl Encoder<Vec<u8>> for MessageCodec {
@@ -30,16 +36,94 @@ impl Encoder<MessageOrBytes> for MessageCodec {
     }
 }
 
+#[derive(Clone)]
 pub enum MessageOrBytes {
     Payload(Payload),
     Bytes(Vec<u8>),
 }
 
+/// Wraps the [MessageCodec] for outbound connections to keep the traffic statistics and
+/// record the traffic.
+pub struct OutboundCodec {
+    codec: MessageCodec,
+    addr: SocketAddr,
+    stats: TrafficStats,
+    recorder: Option<Recorder>,
+}
+
+impl Encoder<MessageOrBytes> for OutboundCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, message: MessageOrBytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        // Raw bytes aren't guaranteed to form a valid message.
+        let message_type = match &message {
+            MessageOrBytes::Payload(payload) => Some(payload.message_type()),
+            MessageOrBytes::Bytes(_) => None,
+        };
+
+        let len_before = dst.len();
+        Encoder::<MessageOrBytes>::encode(&mut self.codec, message, dst)?;
+        self.stats.record(
+            Direction::Outbound,
+            message_type,
+            self.addr,
+            dst.len() - len_before,
+        );
+
+        if let Some(recorder) = &self.recorder {
+            let kind = match message_type {
+                Some(_) => RecordKind::Frame,
+                None => RecordKind::Raw,
+            };
+            if let Err(e) =
+                recorder.record(Direction::Outbound, kind, self.addr, &dst[len_before..])
+            {
+                warn!("couldn't record an outbound frame to {}: {}", self.addr, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Writing for InnerNode {
     type Message = MessageOrBytes;
-    type Codec = MessageCodec;
+    type Codec = OutboundCodec;
+
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        OutboundCodec {
+            codec: MessageCodec::new(self.node().span().clone()),
+            addr,
+            stats: self.stats.clone(),
+            recorder: self.recorder.clone(),
+        }
+    }
+}
+
+impl InnerNode {
+    /// Sends the message to each of the given addresses and waits until it's delivered.
+    ///
+    /// Returns the delivery result for every address.
+    pub async fn multicast(
+        &self,
+        addrs: &[SocketAddr],
+        message: MessageOrBytes,
+    ) -> Vec<(SocketAddr, io::Result<()>)> {
+        let deliveries = addrs.iter().map(|&addr| {
+            let queued = self.unicast(addr, message.clone());
+
+            async move {
+                let result = match queued {
+                    Ok(delivery) => delivery
+                        .await
+                        .unwrap_or_else(|_| Err(io::ErrorKind::ConnectionAborted.into())),
+                    Err(e) => Err(e),
+                };
+
+                (addr, result)
+            }
+        });
 
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Self::Codec::new(self.node().span().clone())
+        join_all(deliveries).await
     }
 }
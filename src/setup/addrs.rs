@@ -0,0 +1,121 @@
+//! Allocation of unique addresses for [Node](crate::setup::node::Node)s, so nodes started by
+//! tests running in parallel don't clash.
+
+use std::{
+    collections::BTreeSet,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+/// The first loopback address handed out to nodes.
+const FIRST_IP: Ipv4Addr = Ipv4Addr::new(127, 0, 1, 1);
+
+/// The number of loopback addresses handed out before starting over from [FIRST_IP].
+const IP_COUNT: u32 = 254;
+
+/// Index of the next address to hand out, relative to [FIRST_IP].
+static NEXT_IP: AtomicU32 = AtomicU32::new(0);
+
+/// Every address handed out so far; a port is never handed out twice on the same IP.
+static ALLOCATED: Mutex<BTreeSet<SocketAddr>> = Mutex::new(BTreeSet::new());
+
+/// The addresses a single node listens on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeAddrs {
+    pub ip: IpAddr,
+    /// The port of the peer protocol.
+    pub peer_port: u16,
+    /// The port of the JSON-RPC admin API.
+    pub rpc_port: u16,
+}
+
+impl NodeAddrs {
+    pub fn new(ip: IpAddr, peer_port: u16, rpc_port: u16) -> Self {
+        Self {
+            ip,
+            peer_port,
+            rpc_port,
+        }
+    }
+
+    /// Allocates free ports on the next loopback address.
+    ///
+    /// Addresses other than `127.0.0.1` aren't available on every system (e.g. on MacOS they need
+    /// to be aliased first), in which case `127.0.0.1` is used instead.
+    pub fn allocate() -> io::Result<Self> {
+        let offset = NEXT_IP.fetch_add(1, Ordering::Relaxed) % IP_COUNT;
+        let ip = IpAddr::V4(Ipv4Addr::from(u32::from(FIRST_IP) + offset));
+
+        match Self::allocate_on(ip) {
+            Err(e) if e.kind() == io::ErrorKind::AddrNotAvailable => {
+                Self::allocate_on(Ipv4Addr::LOCALHOST.into())
+            }
+            result => result,
+        }
+    }
+
+    /// Allocates free ports on the given address.
+    pub fn allocate_on(ip: IpAddr) -> io::Result<Self> {
+        Ok(Self::new(ip, free_port(ip)?, free_port(ip)?))
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.peer_port)
+    }
+
+    pub fn rpc_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.rpc_port)
+    }
+
+    /// The URL to be passed to the [rpc](crate::tools::rpc) functions.
+    pub fn rpc_url(&self) -> String {
+        format!("http://{}", self.rpc_addr())
+    }
+}
+
+/// Allocates a single free port on the given address, e.g. for a proxy in front of a node.
+pub fn allocate_port(ip: IpAddr) -> io::Result<u16> {
+    free_port(ip)
+}
+
+// Finds a port which is free at the moment and wasn't handed out before.
+fn free_port(ip: IpAddr) -> io::Result<u16> {
+    loop {
+        // The OS picks a free port; the listener is closed right away, so the node can bind to it.
+        let port = TcpListener::bind((ip, 0))?.local_addr()?.port();
+
+        if ALLOCATED.lock().unwrap().insert(SocketAddr::new(ip, port)) {
+            return Ok(port);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn allocated_addrs_are_unique() {
+        let addrs = (0..50)
+            .map(|_| NodeAddrs::allocate().unwrap())
+            .collect::<Vec<_>>();
+
+        let sockets = addrs
+            .iter()
+            .flat_map(|addrs| [addrs.peer_addr(), addrs.rpc_addr()])
+            .collect::<HashSet<_>>();
+        assert_eq!(sockets.len(), addrs.len() * 2);
+
+        // The ports are free to be bound to.
+        for addrs in addrs {
+            TcpListener::bind(addrs.peer_addr()).unwrap();
+            TcpListener::bind(addrs.rpc_addr()).unwrap();
+        }
+    }
+}
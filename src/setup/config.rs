@@ -1,32 +1,74 @@
 //! Utilities for node configuration.
 
 use std::{
+    collections::BTreeMap,
+    env,
     ffi::OsString,
-    fmt::Write,
-    fs,
+    fmt, fs,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
+    process::Command,
 };
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::setup::{
     constants::{
-        JSON_RPC_PORT, RIPPLED_DIR, RIPPLED_NODE_SEED, SYNTHETIC_NODE_PUBLIC_KEY,
+        RIPPLED_BINARY_VAR, RIPPLED_DEBUG_LOG, RIPPLED_DIR, RIPPLED_NODE_SEED,
         VALIDATORS_FILE_NAME, ZIGGURAT_CONFIG,
     },
     node::NodeConfig,
+    version::RippledVersion,
 };
 
-/// Convenience struct for reading Ziggurat's configuration file.
-#[derive(Deserialize)]
+/// Convenience struct for reading and writing Ziggurat's configuration file.
+///
+/// The top-level binary is the default one, further binaries can be added by name, e.g.:
+///
+/// ```toml
+/// path = "/home/user/rippled/build"
+/// start_command = "./rippled"
+///
+/// [binaries."1.9.3"]
+/// path = "/opt/rippled-1.9.3"
+/// start_command = "./rippled"
+/// ```
+#[derive(Deserialize, Serialize)]
 struct ConfigFile {
+    #[serde(flatten)]
+    default: BinaryConfig,
+    /// The other binaries, by their names.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    binaries: BTreeMap<String, BinaryConfig>,
+}
+
+/// How to start a rippled binary.
+#[derive(Deserialize, Serialize)]
+struct BinaryConfig {
     /// The absolute path of where to run the start command.
     path: PathBuf,
     /// The command to start the node.
     start_command: String,
 }
 
+impl BinaryConfig {
+    // Runs the `rippled` binary found in the directory.
+    fn new(rippled_path: PathBuf) -> Self {
+        Self {
+            path: rippled_path,
+            start_command: "./rippled".into(),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn read(setup_path: &Path) -> Result<Self> {
+        let config_string = fs::read_to_string(setup_path.join(ZIGGURAT_CONFIG))?;
+        Ok(toml::from_str(&config_string)?)
+    }
+}
+
 /// The node metadata read from Ziggurat's configuration file.
 #[derive(Debug, Clone)]
 pub struct NodeMetaData {
@@ -39,169 +81,662 @@ pub struct NodeMetaData {
 }
 
 impl NodeMetaData {
+    /// Reads the default binary's metadata, or that of the binary named by the
+    /// [RIPPLED_BINARY_VAR] environment variable if it's set, so a whole test run can target
+    /// another binary.
     pub fn new(setup_path: PathBuf) -> Result<NodeMetaData> {
-        // Read Ziggurat's configuration file.
-        let path = setup_path.join(ZIGGURAT_CONFIG);
-        let config_string = fs::read_to_string(path)?;
-        let config_file: ConfigFile = toml::from_str(&config_string)?;
+        match env::var(RIPPLED_BINARY_VAR) {
+            Ok(name) if !name.is_empty() => Self::named(setup_path, &name),
+            _ => Self::from_binary(ConfigFile::read(&setup_path)?.default),
+        }
+    }
 
+    /// Reads the metadata of the binary with the given name in Ziggurat's configuration file.
+    pub fn named(setup_path: PathBuf, name: &str) -> Result<NodeMetaData> {
+        let binary = ConfigFile::read(&setup_path)?
+            .binaries
+            .remove(name)
+            .ok_or_else(|| anyhow!("no rippled binary named {name:?} in {ZIGGURAT_CONFIG}"))?;
+        Self::from_binary(binary)
+    }
+
+    /// The names of the binaries in Ziggurat's configuration file, besides the default one.
+    pub fn binary_names(setup_path: &Path) -> Result<Vec<String>> {
+        Ok(ConfigFile::read(setup_path)?.binaries.into_keys().collect())
+    }
+
+    /// The path of the binary, which can be passed to
+    /// [Node::restart_with_binary](crate::setup::node::Node::restart_with_binary).
+    pub fn rippled_path(&self) -> PathBuf {
+        self.path.join(&self.start_command)
+    }
+
+    /// Runs `rippled --version` and parses its output.
+    pub fn version(&self) -> Result<RippledVersion> {
+        let output = Command::new(&self.start_command)
+            .current_dir(&self.path)
+            .arg("--version")
+            .output()?;
+        if !output.status.success() {
+            bail!("`rippled --version` failed with {}", output.status);
+        }
+
+        RippledVersion::from_version_output(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn from_binary(binary: BinaryConfig) -> Result<NodeMetaData> {
         // Read the args (which includes the start command at index 0).
         let args_from = |command: &str| -> Vec<OsString> {
             command.split_whitespace().map(OsString::from).collect()
         };
 
         // Separate the start command from the args list.
-        let mut start_args = args_from(&config_file.start_command);
+        let mut start_args = args_from(&binary.start_command);
+        if start_args.is_empty() {
+            bail!("the start command is empty");
+        }
         let start_command = start_args.remove(0);
 
         Ok(Self {
-            path: config_file.path,
+            path: binary.path,
             start_command,
             start_args,
         })
     }
+
+    /// Writes Ziggurat's configuration file, to start the `rippled` binary found in `rippled_path`
+    /// by default, while the named `binaries` are found in their respective directories.
+    pub fn write(
+        setup_path: &Path,
+        rippled_path: PathBuf,
+        binaries: BTreeMap<String, PathBuf>,
+    ) -> Result<()> {
+        let config_file = ConfigFile {
+            default: BinaryConfig::new(rippled_path),
+            binaries: binaries
+                .into_iter()
+                .map(|(name, path)| (name, BinaryConfig::new(path)))
+                .collect(),
+        };
+
+        fs::create_dir_all(setup_path)?;
+        fs::write(
+            setup_path.join(ZIGGURAT_CONFIG),
+            toml::to_string(&config_file)?,
+        )?;
+        Ok(())
+    }
 }
 
-pub struct RippledConfigFile;
+/// The contents of a `rippled.cfg` file: a list of `[section]`s, each followed by its lines.
+///
+/// The sections keep their order, so a generated file can be parsed back and compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RippledConfig {
+    sections: Vec<ConfigSection>,
+}
 
-impl RippledConfigFile {
-    pub fn generate(config: &NodeConfig, path: &Path) -> Result<String> {
-        let mut config_str = String::new();
+/// A single `[section]` of the `rippled.cfg` file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigSection {
+    pub name: String,
+    /// The lines of the section, either single values or `key = value` pairs.
+    pub lines: Vec<String>,
+}
 
-        // 1. Server
+impl RippledConfig {
+    /// Parses the contents of a `rippled.cfg` file, skipping comments and empty lines.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                config.sections.push(ConfigSection {
+                    name: name.trim().into(),
+                    lines: vec![],
+                });
+            } else if let Some(section) = config.sections.last_mut() {
+                section.lines.push(line.into());
+            } else {
+                bail!("line {} is outside of any section: {line}", i + 1);
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn sections(&self) -> &[ConfigSection] {
+        &self.sections
+    }
+
+    /// The lines of the section, if present.
+    pub fn section(&self, name: &str) -> Option<&[String]> {
+        self.sections
+            .iter()
+            .find(|section| section.name == name)
+            .map(|section| section.lines.as_slice())
+    }
 
-        writeln!(&mut config_str, "[server]")?;
-        writeln!(&mut config_str, "port_rpc_admin_local")?;
-        writeln!(&mut config_str, "port_peer")?;
-        writeln!(&mut config_str)?;
+    /// The value of a `key = value` line in the section.
+    pub fn value(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?.iter().find_map(|line| {
+            let (k, v) = line.split_once('=')?;
+            (k.trim() == key).then(|| v.trim())
+        })
+    }
 
-        writeln!(&mut config_str, "[port_rpc_admin_local]")?;
-        writeln!(&mut config_str, "port = {JSON_RPC_PORT}")?;
-        writeln!(&mut config_str, "ip = {}", config.local_addr.ip())?;
-        writeln!(&mut config_str, "admin = {}", config.local_addr.ip())?;
-        writeln!(&mut config_str, "protocol = http")?;
-        writeln!(&mut config_str)?;
+    /// Sets the lines of the section, replacing the section if it's already present.
+    pub fn set_section<I, L>(&mut self, name: &str, lines: I)
+    where
+        I: IntoIterator<Item = L>,
+        L: ToString,
+    {
+        let lines = lines.into_iter().map(|line| line.to_string()).collect();
+
+        match self
+            .sections
+            .iter_mut()
+            .find(|section| section.name == name)
+        {
+            Some(section) => section.lines = lines,
+            None => self.sections.push(ConfigSection {
+                name: name.into(),
+                lines,
+            }),
+        }
+    }
+
+    // Sets a section with a single value, if there is one.
+    fn set_value(&mut self, name: &str, value: Option<impl fmt::Display>) {
+        if let Some(value) = value {
+            self.set_section(name, [value]);
+        }
+    }
 
-        writeln!(&mut config_str, "[port_peer]")?;
-        writeln!(&mut config_str, "port = {}", config.local_addr.port())?;
-        writeln!(&mut config_str, "ip = {}", config.local_addr.ip())?;
-        writeln!(&mut config_str, "protocol = peer")?;
-        writeln!(&mut config_str)?;
+    // Sets a section consisting of `key = value` pairs; the pairs without a value are skipped,
+    // as well as the whole section if none of them has one.
+    fn set_pairs(&mut self, name: &str, pairs: &[(&str, Option<String>)]) {
+        let lines = pairs
+            .iter()
+            .filter_map(|(key, value)| Some(format!("{key} = {}", value.as_ref()?)))
+            .collect::<Vec<_>>();
 
-        if let Some(token) = &config.validator_token {
-            writeln!(&mut config_str, "[validator_token]")?;
-            writeln!(&mut config_str, "{token}")?;
-            writeln!(&mut config_str)?;
+        if !lines.is_empty() {
+            self.set_section(name, lines);
         }
+    }
+}
 
-        if let Some(network_id) = &config.network_id {
-            writeln!(&mut config_str, "[network_id]")?;
-            writeln!(&mut config_str, "{network_id}")?;
-            writeln!(&mut config_str)?;
+impl fmt::Display for RippledConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in &self.sections {
+            writeln!(f, "[{}]", section.name)?;
+            for line in &section.lines {
+                writeln!(f, "{line}")?;
+            }
+            writeln!(f)?;
         }
+        Ok(())
+    }
+}
 
-        // 2. Peer protocol
-        writeln!(&mut config_str, "[reduce_relay]")?;
-        writeln!(&mut config_str, "tx_enable = 1")?;
-        writeln!(&mut config_str)?;
+/// The `[node_size]` tuning rippled's caches and memory usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeSize {
+    Tiny,
+    Small,
+    Medium,
+    Large,
+    Huge,
+}
 
-        writeln!(&mut config_str, "[ledger_replay]")?;
-        writeln!(&mut config_str, "1")?;
-        writeln!(&mut config_str)?;
+impl fmt::Display for NodeSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = match self {
+            Self::Tiny => "tiny",
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+            Self::Huge => "huge",
+        };
+        f.write_str(size)
+    }
+}
 
-        writeln!(&mut config_str, "[ips_fixed]")?;
-        for addr in &config.initial_peers {
-            writeln!(&mut config_str, "{} {}", addr.ip(), addr.port())?;
+/// The `[ledger_history]` to acquire and keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerHistory {
+    None,
+    Full,
+    /// The number of past ledgers.
+    Ledgers(u32),
+}
+
+impl fmt::Display for LedgerHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Full => f.write_str("full"),
+            Self::Ledgers(count) => write!(f, "{count}"),
         }
-        writeln!(&mut config_str)?;
+    }
+}
 
-        writeln!(&mut config_str, "[peers_max]")?;
-        writeln!(&mut config_str, "{}", config.max_peers)?;
-        writeln!(&mut config_str)?;
+/// The `[overlay]` options of the peer network.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverlayConfig {
+    /// The public IP address advertised to the peers.
+    pub public_ip: Option<IpAddr>,
+    /// Seconds after which a server with unknown consensus status is considered unhealthy.
+    pub max_unknown_time: Option<u64>,
+    /// Seconds after which a server diverging from consensus is considered unhealthy.
+    pub max_diverged_time: Option<u64>,
+}
+
+/// The fee `[voting]` preferences of a validator, in drops.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VotingConfig {
+    pub reference_fee: Option<u64>,
+    pub account_reserve: Option<u64>,
+    pub owner_reserve: Option<u64>,
+}
+
+/// The `[transaction_queue]` tuning.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionQueueConfig {
+    /// The size of the queue, in multiples of the expected ledger size.
+    pub ledgers_in_queue: Option<u32>,
+    pub minimum_queue_size: Option<u32>,
+    pub minimum_txn_in_ledger: Option<u32>,
+    pub target_txn_in_ledger: Option<u32>,
+    pub maximum_txn_in_ledger: Option<u32>,
+    pub maximum_txn_per_account: Option<u32>,
+}
+
+/// The `[insight]` options, making rippled report its metrics to a StatsD server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsightConfig {
+    /// The address of the StatsD server.
+    pub address: SocketAddr,
+    /// The prefix of the reported metric names.
+    pub prefix: String,
+}
 
-        writeln!(&mut config_str, "[sntp_servers]")?;
-        writeln!(&mut config_str, "time.windows.com")?;
-        writeln!(&mut config_str, "time.apple.com")?;
-        writeln!(&mut config_str, "time.nist.gov")?;
-        writeln!(&mut config_str, "pool.ntp.org")?;
-        writeln!(&mut config_str)?;
+pub struct RippledConfigFile;
+
+impl RippledConfigFile {
+    pub fn generate(config: &NodeConfig, path: &Path) -> Result<String> {
+        Ok(Self::build(config, path)?.to_string())
+    }
+
+    /// Builds the configuration model, which [generate](Self::generate) writes out.
+    pub fn build(config: &NodeConfig, path: &Path) -> Result<RippledConfig> {
+        let mut cfg = RippledConfig::default();
+        let path_str = |path: PathBuf| -> Result<String> {
+            path.to_str()
+                .map(String::from)
+                .ok_or_else(|| anyhow!("path is not valid UTF-8: {}", path.display()))
+        };
+        let some = |value: &dyn fmt::Display| Some(value.to_string());
+        let opt = |value: Option<u64>| value.map(|value| value.to_string());
+
+        // 1. Server
+
+        cfg.set_section("server", ["port_rpc_admin_local", "port_peer"]);
+
+        let ip = config.local_addr.ip();
+        cfg.set_pairs(
+            "port_rpc_admin_local",
+            &[
+                ("port", some(&config.rpc_port)),
+                ("ip", some(&ip)),
+                ("admin", some(&ip)),
+                ("protocol", some(&"http")),
+            ],
+        );
+        cfg.set_pairs(
+            "port_peer",
+            &[
+                ("port", some(&config.local_addr.port())),
+                ("ip", some(&ip)),
+                ("protocol", some(&"peer")),
+            ],
+        );
+
+        cfg.set_value("validator_token", config.validator_token.as_ref());
+        cfg.set_value("network_id", config.network_id);
+
+        if !config.rpc_startup.is_empty() {
+            cfg.set_section("rpc_startup", &config.rpc_startup);
+        }
+
+        // 2. Peer protocol
+
+        cfg.set_section("reduce_relay", ["tx_enable = 1"]);
+        cfg.set_section("ledger_replay", ["1"]);
+        cfg.set_value("compression", config.compression.map(u8::from));
+
+        cfg.set_section(
+            "ips_fixed",
+            config
+                .initial_peers
+                .iter()
+                .map(|addr| format!("{} {}", addr.ip(), addr.port())),
+        );
+        if !config.ips.is_empty() {
+            cfg.set_section(
+                "ips",
+                config
+                    .ips
+                    .iter()
+                    .map(|addr| format!("{} {}", addr.ip(), addr.port())),
+            );
+        }
+
+        cfg.set_value("peer_private", config.peer_private.map(u8::from));
+        // Rippled refuses `[peers_max]` along with the separate limits.
+        if config.peers_in_max.is_none() && config.peers_out_max.is_none() {
+            cfg.set_value("peers_max", Some(config.max_peers));
+        }
+        cfg.set_value("peers_in_max", config.peers_in_max);
+        cfg.set_value("peers_out_max", config.peers_out_max);
+
+        let overlay = &config.overlay;
+        cfg.set_pairs(
+            "overlay",
+            &[
+                ("public_ip", overlay.public_ip.map(|ip| ip.to_string())),
+                ("max_unknown_time", opt(overlay.max_unknown_time)),
+                ("max_diverged_time", opt(overlay.max_diverged_time)),
+            ],
+        );
+
+        // Without any servers, the node simply follows the system clock.
+        cfg.set_section("sntp_servers", &config.sntp_servers);
 
         // 3. Ripple protocol
-        if config.enable_cluster {
-            writeln!(&mut config_str, "[node_seed]")?;
-            writeln!(&mut config_str, "{RIPPLED_NODE_SEED}")?;
-            writeln!(&mut config_str)?;
 
-            writeln!(&mut config_str, "[cluster_nodes]")?;
-            writeln!(&mut config_str, "{SYNTHETIC_NODE_PUBLIC_KEY}")?;
-            writeln!(&mut config_str)?;
+        if config.enable_cluster {
+            cfg.set_section("node_seed", [RIPPLED_NODE_SEED]);
+            cfg.set_section("cluster_nodes", &config.cluster_nodes);
         }
 
-        writeln!(&mut config_str, "[validators_file]")?;
-        writeln!(&mut config_str, "{VALIDATORS_FILE_NAME}")?;
-        writeln!(&mut config_str)?;
+        cfg.set_section("validators_file", [VALIDATORS_FILE_NAME]);
+
+        let queue = &config.transaction_queue;
+        let opt32 = |value: Option<u32>| value.map(|value| value.to_string());
+        cfg.set_pairs(
+            "transaction_queue",
+            &[
+                ("ledgers_in_queue", opt32(queue.ledgers_in_queue)),
+                ("minimum_queue_size", opt32(queue.minimum_queue_size)),
+                ("minimum_txn_in_ledger", opt32(queue.minimum_txn_in_ledger)),
+                ("target_txn_in_ledger", opt32(queue.target_txn_in_ledger)),
+                ("maximum_txn_in_ledger", opt32(queue.maximum_txn_in_ledger)),
+                (
+                    "maximum_txn_per_account",
+                    opt32(queue.maximum_txn_per_account),
+                ),
+            ],
+        );
 
         if config.enable_sharding {
-            writeln!(&mut config_str, "[shard_db]")?;
-            writeln!(
-                &mut config_str,
-                "path={}",
-                path.join(RIPPLED_DIR)
-                    .join("db/shard/nudb")
-                    .to_str()
-                    .unwrap()
-            )?;
             // For our test it's sufficient to hold the smallest possible number of shards.
             // More information about sharding config: https://xrpl.org/configure-history-sharding.html
-            writeln!(&mut config_str, "max_historical_shards=1")?;
-            writeln!(&mut config_str)?;
+            cfg.set_section(
+                "shard_db",
+                [
+                    format!(
+                        "path={}",
+                        path_str(path.join(RIPPLED_DIR).join("db/shard/nudb"))?
+                    ),
+                    "max_historical_shards=1".into(),
+                ],
+            );
         }
 
         // 4. HTTPS client
 
-        writeln!(&mut config_str, "[ssl_verify]")?;
-        writeln!(&mut config_str, "0")?;
-        writeln!(&mut config_str)?;
+        cfg.set_section("ssl_verify", ["0"]);
 
         // 5. Reporting mode
 
         // 6. Database
 
-        writeln!(&mut config_str, "[node_db]")?;
-        writeln!(&mut config_str, "type=NuDB")?;
-        writeln!(
-            &mut config_str,
-            "path={}",
-            path.join(RIPPLED_DIR).join("db/nudb").to_str().unwrap()
-        )?;
-        writeln!(&mut config_str, "online_delete=512")?;
-        writeln!(&mut config_str, "advisory_delete=0")?;
-        writeln!(&mut config_str)?;
-
-        writeln!(&mut config_str, "[database_path]")?;
-        writeln!(
-            &mut config_str,
-            "{}",
-            path.join(RIPPLED_DIR).join("db").to_str().unwrap()
-        )?;
-        writeln!(&mut config_str)?;
+        cfg.set_section(
+            "node_db",
+            [
+                "type=NuDB".into(),
+                format!("path={}", path_str(path.join(RIPPLED_DIR).join("db/nudb"))?),
+                "online_delete=512".into(),
+                "advisory_delete=0".into(),
+            ],
+        );
+        cfg.set_section(
+            "database_path",
+            [path_str(path.join(RIPPLED_DIR).join("db"))?],
+        );
+        cfg.set_value("node_size", config.node_size);
+        cfg.set_value("ledger_history", config.ledger_history);
 
         // 7. Diagnostics
 
-        writeln!(&mut config_str, "[debug_logfile]")?;
-        writeln!(
-            &mut config_str,
-            "{}",
-            path.join(RIPPLED_DIR).join("debug.log").to_str().unwrap()
-        )?;
-        writeln!(&mut config_str)?;
+        cfg.set_section(
+            "debug_logfile",
+            [path_str(path.join(RIPPLED_DIR).join(RIPPLED_DEBUG_LOG))?],
+        );
+
+        if let Some(insight) = &config.insight {
+            cfg.set_pairs(
+                "insight",
+                &[
+                    ("server", some(&"statsd")),
+                    ("address", some(&insight.address)),
+                    ("prefix", some(&insight.prefix)),
+                ],
+            );
+        }
 
         // 8. Voting
 
+        let voting = &config.voting;
+        cfg.set_pairs(
+            "voting",
+            &[
+                ("reference_fee", opt(voting.reference_fee)),
+                ("account_reserve", opt(voting.account_reserve)),
+                ("owner_reserve", opt(voting.owner_reserve)),
+            ],
+        );
+
         // 9. Misc settings
 
         // 10. Example settings
 
-        Ok(config_str)
+        // Raw sections go last, so they can replace any of the above.
+        for section in &config.extra_sections {
+            cfg.set_section(&section.name, &section.lines);
+        }
+
+        Ok(cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn generated_config_round_trips() {
+        let config = NodeConfig {
+            local_addr: "127.0.1.1:51235".parse().unwrap(),
+            rpc_port: 5005,
+            initial_peers: ["127.0.1.2:51235".parse().unwrap()].into(),
+            ips: vec!["127.0.1.3:51235".parse().unwrap()],
+            sntp_servers: vec!["127.0.1.4".into()],
+            peers_in_max: Some(10),
+            peers_out_max: Some(5),
+            peer_private: Some(true),
+            compression: Some(false),
+            overlay: OverlayConfig {
+                public_ip: Some("10.0.0.1".parse().unwrap()),
+                max_unknown_time: Some(600),
+                max_diverged_time: None,
+            },
+            voting: VotingConfig {
+                reference_fee: Some(10),
+                ..Default::default()
+            },
+            transaction_queue: TransactionQueueConfig {
+                ledgers_in_queue: Some(20),
+                maximum_txn_per_account: Some(10),
+                ..Default::default()
+            },
+            node_size: Some(NodeSize::Tiny),
+            ledger_history: Some(LedgerHistory::Ledgers(256)),
+            rpc_startup: vec![json!({ "command": "log_level", "severity": "debug" })],
+            insight: Some(InsightConfig {
+                address: "127.0.0.1:8125".parse().unwrap(),
+                prefix: "rippled".into(),
+            }),
+            enable_cluster: true,
+            enable_sharding: true,
+            ..Default::default()
+        };
+
+        let built = RippledConfigFile::build(&config, Path::new("/tmp/node")).unwrap();
+        let generated = RippledConfigFile::generate(&config, Path::new("/tmp/node")).unwrap();
+        let parsed = RippledConfig::parse(&generated).unwrap();
+        assert_eq!(parsed, built);
+        assert_eq!(parsed.to_string(), generated);
+
+        assert_eq!(parsed.value("port_peer", "port"), Some("51235"));
+        assert_eq!(parsed.value("port_rpc_admin_local", "port"), Some("5005"));
+        assert_eq!(parsed.section("ips_fixed").unwrap(), ["127.0.1.2 51235"]);
+        assert_eq!(parsed.section("ips").unwrap(), ["127.0.1.3 51235"]);
+        assert_eq!(parsed.section("sntp_servers").unwrap(), ["127.0.1.4"]);
+        assert_eq!(parsed.section("peers_max"), None);
+        assert_eq!(parsed.section("peers_in_max").unwrap(), ["10"]);
+        assert_eq!(parsed.section("peer_private").unwrap(), ["1"]);
+        assert_eq!(parsed.section("compression").unwrap(), ["0"]);
+        assert_eq!(parsed.value("overlay", "public_ip"), Some("10.0.0.1"));
+        assert_eq!(parsed.value("overlay", "max_diverged_time"), None);
+        assert_eq!(parsed.section("voting").unwrap(), ["reference_fee = 10"]);
+        assert_eq!(
+            parsed.value("transaction_queue", "ledgers_in_queue"),
+            Some("20")
+        );
+        assert_eq!(parsed.section("node_size").unwrap(), ["tiny"]);
+        assert_eq!(parsed.section("ledger_history").unwrap(), ["256"]);
+        assert_eq!(
+            parsed.section("rpc_startup").unwrap(),
+            [r#"{"command":"log_level","severity":"debug"}"#]
+        );
+        assert_eq!(parsed.value("insight", "address"), Some("127.0.0.1:8125"));
+        assert_eq!(parsed.value("node_db", "type"), Some("NuDB"));
+        assert!(parsed.section("node_seed").is_some());
+        assert!(parsed.section("shard_db").is_some());
+
+        // The config is also saved as JSON, to reuse the node's directory.
+        let saved = serde_json::to_string(&config).unwrap();
+        let restored: NodeConfig = serde_json::from_str(&saved).unwrap();
+        assert_eq!(
+            RippledConfigFile::generate(&restored, Path::new("/tmp/node")).unwrap(),
+            generated
+        );
+    }
+
+    #[test]
+    fn extra_sections_replace_generated_ones() {
+        let config = NodeConfig {
+            extra_sections: vec![
+                ConfigSection {
+                    name: "ssl_verify".into(),
+                    lines: vec!["1".into()],
+                },
+                ConfigSection {
+                    name: "workers".into(),
+                    lines: vec!["4".into()],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cfg = RippledConfigFile::build(&config, Path::new("/tmp/node")).unwrap();
+        assert_eq!(cfg.section("ssl_verify").unwrap(), ["1"]);
+        assert_eq!(cfg.section("workers").unwrap(), ["4"]);
+        assert_eq!(
+            cfg.sections()
+                .iter()
+                .filter(|section| section.name == "ssl_verify")
+                .count(),
+            1
+        );
+        // Without separate limits, the total one is written.
+        assert_eq!(cfg.section("peers_max").unwrap(), ["0"]);
+        // No time servers are queried by default.
+        assert!(cfg.section("sntp_servers").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_handwritten_config() {
+        let cfg = RippledConfig::parse(
+            "# A comment\n\
+             [server]\n\
+             port_peer\n\
+             \n\
+             [port_peer]\n\
+             port=51235\n\
+             \x20 ip = 0.0.0.0\n\
+             [ips]\n\
+             r.ripple.com 51235\n",
+        )
+        .unwrap();
+
+        assert_eq!(cfg.sections().len(), 3);
+        assert_eq!(cfg.value("port_peer", "port"), Some("51235"));
+        assert_eq!(cfg.value("port_peer", "ip"), Some("0.0.0.0"));
+        assert_eq!(cfg.section("ips").unwrap(), ["r.ripple.com 51235"]);
+        assert_eq!(cfg.section("missing"), None);
+
+        assert!(RippledConfig::parse("port = 51235\n[server]\n").is_err());
+    }
+
+    #[test]
+    fn named_binaries_in_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let binaries = [("1.9.3".to_owned(), PathBuf::from("/opt/rippled-1.9.3"))].into();
+        NodeMetaData::write(dir.path(), "/opt/rippled".into(), binaries).unwrap();
+
+        let default = NodeMetaData::from_binary(ConfigFile::read(dir.path()).unwrap().default);
+        assert_eq!(default.unwrap().path, PathBuf::from("/opt/rippled"));
+        let named = NodeMetaData::named(dir.path().to_path_buf(), "1.9.3").unwrap();
+        assert_eq!(
+            named.rippled_path(),
+            PathBuf::from("/opt/rippled-1.9.3/./rippled")
+        );
+        assert_eq!(NodeMetaData::binary_names(dir.path()).unwrap(), ["1.9.3"]);
+        assert!(NodeMetaData::named(dir.path().to_path_buf(), "1.10.0").is_err());
+
+        // A configuration file without named binaries is still valid.
+        fs::write(
+            dir.path().join(ZIGGURAT_CONFIG),
+            "path = \"/opt/rippled\"\nstart_command = \"./rippled --quiet\"\n",
+        )
+        .unwrap();
+        let config = ConfigFile::read(dir.path()).unwrap();
+        assert!(config.binaries.is_empty());
+        let meta = NodeMetaData::from_binary(config.default).unwrap();
+        assert_eq!(meta.start_args, ["--quiet"]);
     }
 }
@@ -12,6 +12,10 @@ pub const RIPPLE_SETUP_DIR: &str = "setup";
 /// Configuration file with paths to start rippled.
 pub const ZIGGURAT_CONFIG: &str = "config.toml";
 
+/// The environment variable naming the binary in [ZIGGURAT_CONFIG] the nodes are started with,
+/// instead of the default one.
+pub const RIPPLED_BINARY_VAR: &str = "RIPPLED_BINARY";
+
 /// Validators file name.
 pub const VALIDATORS_FILE_NAME: &str = "validators.txt";
 
@@ -21,18 +25,30 @@ pub const STATEFUL_NODES_DIR: &str = "stateful";
 /// Number of available stateful nodes
 pub const STATEFUL_NODES_COUNT: usize = 3;
 
-/// Validator IP address list
-pub const VALIDATOR_IPS: [&str; STATEFUL_NODES_COUNT] = ["127.0.0.1", "127.0.0.2", "127.0.0.3"];
-
 /// Rippled's configuration file name.
 pub const RIPPLED_CONFIG: &str = "rippled.cfg";
+
+/// The file with the [NodeConfig](crate::setup::node::NodeConfig) a node was started with,
+/// placed next to [RIPPLED_CONFIG] so the node's directory can be reused.
+pub const NODE_CONFIG_FILE: &str = "node_config.json";
 pub const RIPPLED_DIR: &str = "rippled";
 
-/// Rippled's JSON RPC port
-pub const JSON_RPC_PORT: u32 = 5005;
+/// Rippled's log file name, placed in [RIPPLED_DIR].
+pub const RIPPLED_DEBUG_LOG: &str = "debug.log";
 
-/// The default port to start a Rippled node on.
-pub const DEFAULT_PORT: u16 = 8080;
+/// Directory where the logs of the nodes from failed tests are kept.
+pub const KEPT_LOGS_DIR: &str = "logs";
+
+/// The default number of the most recent log lines kept in memory for each node.
+pub const LOG_BUFFER_SIZE: usize = 10_000;
+
+/// Public SNTP servers, which nodes can be pointed at when the tests are run online.
+pub const PUBLIC_SNTP_SERVERS: [&str; 4] = [
+    "time.windows.com",
+    "time.apple.com",
+    "time.nist.gov",
+    "pool.ntp.org",
+];
 
 /// [TestNet](crate::setup::testnet::TestNet)'s network id. The number here doesn't have any significance, but cannot be 0 nor 255.
 pub const TESTNET_NETWORK_ID: u32 = 239048;
@@ -40,6 +56,9 @@ pub const TESTNET_NETWORK_ID: u32 = 239048;
 /// Timeout when waiting for [Node](crate::setup::node::Node)'s start.
 pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Timeout when waiting for [Node](crate::setup::node::Node) to exit after a graceful stop request.
+pub const NODE_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Timeout when waiting for [TestNet](crate::setup::testnet::TestNet) to start.
 pub const TESTNET_READY_TIMEOUT: Duration = Duration::from_secs(60);
 
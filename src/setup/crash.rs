@@ -0,0 +1,365 @@
+//! Detection and reporting of [Node](crate::setup::node::Node) crashes.
+
+use std::{
+    env, fmt, fs,
+    net::SocketAddr,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use crate::setup::logs::{LogLine, NodeLogs};
+
+/// The number of the most recent log lines included in a crash report.
+const CRASH_LOG_TAIL: usize = 50;
+
+/// The prefix of the sanitizer report files placed in the node's directory.
+const SANITIZER_LOG_PREFIX: &str = "sanitizer";
+
+/// Environment variables with the options of the sanitizers rippled may be built with.
+const SANITIZER_OPTIONS_VARS: [&str; 3] = ["ASAN_OPTIONS", "UBSAN_OPTIONS", "TSAN_OPTIONS"];
+
+/// Returns the sanitizer options which make the reports land in the given directory.
+///
+/// Options already set in the environment are kept, unless they set the report path themselves.
+pub(crate) fn sanitizer_env(dir: &Path) -> Vec<(&'static str, String)> {
+    let log_path = format!("log_path={}", dir.join(SANITIZER_LOG_PREFIX).display());
+
+    SANITIZER_OPTIONS_VARS
+        .iter()
+        .filter_map(|var| match env::var(var) {
+            Ok(options) if options.contains("log_path") => None,
+            Ok(options) if !options.is_empty() => Some((*var, format!("{options}:{log_path}"))),
+            _ => Some((*var, log_path.clone())),
+        })
+        .collect()
+}
+
+/// A sanitizer which can detect errors in rippled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    Leak,
+    Thread,
+    UndefinedBehavior,
+}
+
+impl fmt::Display for Sanitizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Address => "AddressSanitizer",
+            Self::Leak => "LeakSanitizer",
+            Self::Thread => "ThreadSanitizer",
+            Self::UndefinedBehavior => "UndefinedBehaviorSanitizer",
+        };
+        f.pad(name)
+    }
+}
+
+/// A report written by a sanitizer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizerReport {
+    pub sanitizer: Sanitizer,
+    /// The file containing the full report.
+    pub path: PathBuf,
+    /// The line summarizing the error.
+    pub summary: String,
+}
+
+impl SanitizerReport {
+    /// Parses the report; returns `None` if it doesn't come from a known sanitizer.
+    pub fn parse(path: PathBuf, report: &str) -> Option<Self> {
+        // The order matters, e.g. AddressSanitizer also runs the LeakSanitizer.
+        let sanitizer = if report.contains("LeakSanitizer") {
+            Sanitizer::Leak
+        } else if report.contains("AddressSanitizer") {
+            Sanitizer::Address
+        } else if report.contains("ThreadSanitizer") {
+            Sanitizer::Thread
+        } else if report.contains("UndefinedBehaviorSanitizer") || report.contains("runtime error:")
+        {
+            Sanitizer::UndefinedBehavior
+        } else {
+            return None;
+        };
+
+        let summary = report
+            .lines()
+            .find(|line| line.starts_with("SUMMARY:"))
+            .or_else(|| report.lines().find(|line| line.contains("ERROR:")))
+            .or_else(|| report.lines().find(|line| line.contains("runtime error:")))
+            .unwrap_or_default()
+            .trim()
+            .to_owned();
+
+        Some(Self {
+            sanitizer,
+            path,
+            summary,
+        })
+    }
+}
+
+/// The report of a node which exited while it wasn't asked to.
+#[derive(Clone)]
+pub struct NodeCrash {
+    /// The address of the node.
+    pub addr: SocketAddr,
+    /// The process id of the node.
+    pub pid: u32,
+    /// The exit code, if the node exited on its own.
+    pub exit_code: Option<i32>,
+    /// The signal which terminated the node.
+    pub signal: Option<i32>,
+    /// Whether the node dumped its core.
+    pub core_dumped: bool,
+    /// Core files found in the node's directories.
+    pub core_files: Vec<PathBuf>,
+    /// Sanitizer reports found in the node's directory.
+    pub sanitizer_reports: Vec<SanitizerReport>,
+    /// The last lines logged by the node.
+    pub log_tail: Vec<LogLine>,
+}
+
+impl fmt::Display for NodeCrash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the rippled node {} (pid {}) ", self.addr, self.pid)?;
+        match (self.exit_code, self.signal) {
+            (_, Some(signal)) => write!(f, "was terminated by {}", signal_name(signal))?,
+            (Some(code), _) => write!(f, "exited with code {code}")?,
+            (None, None) => write!(f, "exited")?,
+        }
+        if self.core_dumped {
+            write!(f, " (core dumped)")?;
+        }
+        writeln!(f)?;
+
+        for path in &self.core_files {
+            writeln!(f, "core file: {}", path.display())?;
+        }
+        for report in &self.sanitizer_reports {
+            writeln!(
+                f,
+                "{}: {} (full report: {})",
+                report.sanitizer,
+                report.summary,
+                report.path.display()
+            )?;
+        }
+
+        if !self.log_tail.is_empty() {
+            writeln!(f, "the last {} log lines:", self.log_tail.len())?;
+            for line in &self.log_tail {
+                writeln!(f, "{line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The report is meant to be read when a test fails, e.g. on `unwrap`.
+impl fmt::Debug for NodeCrash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for NodeCrash {}
+
+fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGILL => "SIGILL",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGTERM => "SIGTERM",
+        _ => return format!("signal {signal}"),
+    };
+
+    format!("{name} ({signal})")
+}
+
+/// Collects what's needed to report a crash of a node.
+#[derive(Clone)]
+pub(crate) struct CrashMonitor {
+    addr: SocketAddr,
+    pid: u32,
+    /// The directories where the node could leave core files or sanitizer reports.
+    dirs: Vec<PathBuf>,
+    started: SystemTime,
+    logs: NodeLogs,
+    /// Set once the node is asked to stop, after which an exit isn't a crash.
+    stopping: Arc<AtomicBool>,
+    /// Set once a crash report was handed out, so the crash isn't reported again.
+    reported: Arc<AtomicBool>,
+}
+
+impl CrashMonitor {
+    /// The directories are searched for core files and sanitizer reports.
+    pub(crate) fn new(addr: SocketAddr, pid: u32, dirs: Vec<PathBuf>, logs: NodeLogs) -> Self {
+        Self {
+            addr,
+            pid,
+            dirs,
+            started: SystemTime::now(),
+            logs,
+            stopping: Default::default(),
+            reported: Default::default(),
+        }
+    }
+
+    pub(crate) fn set_stopping(&self) {
+        self.stopping.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_reported(&self) -> bool {
+        self.reported.load(Ordering::Relaxed)
+    }
+
+    /// Builds the crash report for the given exit status and marks the crash as reported.
+    pub(crate) fn report(&self, status: ExitStatus) -> NodeCrash {
+        self.reported.store(true, Ordering::Relaxed);
+        self.logs.sync_debug_log();
+        let lines = self.logs.lines();
+        let log_tail = lines[lines.len().saturating_sub(CRASH_LOG_TAIL)..].to_vec();
+
+        NodeCrash {
+            addr: self.addr,
+            pid: self.pid,
+            exit_code: status.code(),
+            signal: status.signal(),
+            core_dumped: status.core_dumped(),
+            core_files: self.core_files(),
+            sanitizer_reports: self.sanitizer_reports(),
+            log_tail,
+        }
+    }
+
+    // Core files named `core` or `core.<pid>`, created since the node started.
+    fn core_files(&self) -> Vec<PathBuf> {
+        let pid_core = format!("core.{}", self.pid);
+
+        self.files(|name| name == "core" || name == pid_core)
+            .into_iter()
+            .filter(|path| {
+                fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .map(|modified| modified >= self.started)
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    fn sanitizer_reports(&self) -> Vec<SanitizerReport> {
+        let report_name = format!("{SANITIZER_LOG_PREFIX}.{}", self.pid);
+
+        self.files(|name| name == report_name)
+            .into_iter()
+            .filter_map(|path| {
+                let report = fs::read_to_string(&path).ok()?;
+                SanitizerReport::parse(path, &report)
+            })
+            .collect()
+    }
+
+    // The files in the node's directories with names matching the predicate.
+    fn files(&self, matches: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+        self.dirs
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_str().is_some_and(&matches))
+            .map(|entry| entry.path())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        process::Command,
+    };
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::setup::logs::LogSource;
+
+    const ASAN_REPORT: &str = "\
+=================================================================
+==4242==ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010
+READ of size 4 at 0x602000000010 thread T0
+    #0 0x4f5c3a in ripple::PeerImp::onMessage
+SUMMARY: AddressSanitizer: heap-use-after-free PeerImp.cpp:42 in ripple::PeerImp::onMessage
+";
+
+    #[test]
+    fn parse_sanitizer_reports() {
+        let report = SanitizerReport::parse("sanitizer.1".into(), ASAN_REPORT).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Address);
+        assert!(report
+            .summary
+            .starts_with("SUMMARY: AddressSanitizer: heap-use-after-free"));
+
+        let ubsan = "Peer.cpp:10:5: runtime error: signed integer overflow\n";
+        let report = SanitizerReport::parse("sanitizer.2".into(), ubsan).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::UndefinedBehavior);
+        assert_eq!(report.summary, ubsan.trim());
+
+        assert!(SanitizerReport::parse("sanitizer.3".into(), "nothing to see").is_none());
+    }
+
+    #[test]
+    fn crash_report() {
+        let dir = TempDir::new().unwrap();
+        let logs = NodeLogs::new(100, None, false).unwrap();
+        logs.push(LogSource::Stderr, "terminate called after throwing".into());
+
+        // A process killed by SIGSEGV provides the exit status.
+        let mut child = Command::new("sh")
+            .args(["-c", "kill -SEGV $$"])
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let status = child.wait().unwrap();
+
+        fs::write(dir.path().join(format!("sanitizer.{pid}")), ASAN_REPORT).unwrap();
+        fs::write(dir.path().join(format!("core.{pid}")), []).unwrap();
+        fs::write(dir.path().join("core.1"), []).unwrap();
+
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8080).into();
+        let monitor = CrashMonitor {
+            started: SystemTime::UNIX_EPOCH,
+            ..CrashMonitor::new(addr, pid, vec![dir.path().into()], logs)
+        };
+
+        assert!(!monitor.is_reported());
+        let crash = monitor.report(status);
+        // The clones share the state, like the ones used by the crash futures.
+        assert!(monitor.clone().is_reported());
+        assert_eq!(crash.signal, Some(libc::SIGSEGV));
+        assert_eq!(crash.exit_code, None);
+        assert_eq!(crash.core_files.len(), 1);
+        assert_eq!(crash.sanitizer_reports.len(), 1);
+        assert_eq!(crash.log_tail.len(), 1);
+
+        let report = crash.to_string();
+        assert!(report.contains("was terminated by SIGSEGV (11)"));
+        assert!(report.contains("heap-use-after-free"));
+        assert!(report.contains("terminate called after throwing"));
+    }
+}
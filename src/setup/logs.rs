@@ -0,0 +1,395 @@
+//! Capturing of the log output of a [Node](crate::setup::node::Node).
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, LineWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use regex::Regex;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::Notify,
+    task::JoinHandle,
+    time::{error::Elapsed, sleep, timeout},
+};
+
+/// How often the `debug.log` file is checked for new lines.
+const DEBUG_LOG_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The origin of a log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    Stdout,
+    Stderr,
+    DebugLog,
+}
+
+impl fmt::Display for LogSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+            Self::DebugLog => "debug.log",
+        };
+        f.pad(source)
+    }
+}
+
+/// A single line of the node's log output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub source: LogSource,
+    pub text: String,
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:<9}] {}", self.source, self.text)
+    }
+}
+
+// Follows a file which is appended to, e.g. `debug.log`.
+struct FileTail {
+    path: PathBuf,
+    offset: u64,
+    // The end of the file not terminated by a newline yet.
+    partial: Vec<u8>,
+}
+
+impl FileTail {
+    // Returns the lines appended since the last call.
+    fn read_lines(&mut self) -> io::Result<Vec<String>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            // The node creates the file only after it starts.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        // The file was truncated or replaced.
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        self.offset += file.read_to_end(&mut self.partial)? as u64;
+
+        let complete = match self.partial.iter().rposition(|byte| *byte == b'\n') {
+            Some(pos) => self.partial.drain(..=pos).collect::<Vec<_>>(),
+            None => return Ok(vec![]),
+        };
+
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+}
+
+struct Buffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+    // The total number of lines pushed, including the ones already dropped from the buffer.
+    pushed: u64,
+    file: Option<LineWriter<File>>,
+    tail: Option<FileTail>,
+}
+
+impl Buffer {
+    // Searches the lines pushed since `from`, and moves `from` past the searched lines.
+    fn find_since(&self, from: &mut u64, regex: &Regex) -> Option<LogLine> {
+        let first = self.pushed - self.lines.len() as u64;
+        let skip = from.saturating_sub(first) as usize;
+        *from = self.pushed;
+
+        self.lines
+            .iter()
+            .skip(skip)
+            .find(|line| regex.is_match(&line.text))
+            .cloned()
+    }
+}
+
+/// The log output of a node, kept in a ring buffer and optionally written to a file.
+///
+/// Handles are cheap to clone and all of them refer to the same logs.
+#[derive(Clone)]
+pub struct NodeLogs {
+    buffer: Arc<Mutex<Buffer>>,
+    new_lines: Arc<Notify>,
+    // Whether the standard output streams are also printed by Ziggurat.
+    echo: bool,
+}
+
+impl NodeLogs {
+    /// Keeps the last `capacity` lines in memory; if a file is given, all the lines are
+    /// also written to it.
+    pub fn new(capacity: usize, file: Option<&Path>, echo: bool) -> io::Result<Self> {
+        let file = file.map(File::create).transpose()?.map(LineWriter::new);
+
+        Ok(Self {
+            buffer: Arc::new(Mutex::new(Buffer {
+                lines: VecDeque::with_capacity(capacity.min(1024)),
+                capacity,
+                pushed: 0,
+                file,
+                tail: None,
+            })),
+            new_lines: Default::default(),
+            echo,
+        })
+    }
+
+    /// Adds a line to the logs.
+    pub fn push(&self, source: LogSource, text: String) {
+        if self.echo {
+            match source {
+                LogSource::Stdout => println!("{text}"),
+                LogSource::Stderr => eprintln!("{text}"),
+                LogSource::DebugLog => (),
+            }
+        }
+
+        let line = LogLine { source, text };
+        let mut buffer = self.buffer.lock().unwrap();
+
+        if let Some(file) = &mut buffer.file {
+            if let Err(e) = writeln!(file, "{line}") {
+                eprintln!("failed to write the node's logs: {e}");
+            }
+        }
+
+        if buffer.capacity != 0 {
+            if buffer.lines.len() == buffer.capacity {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back(line);
+        }
+        buffer.pushed += 1;
+        drop(buffer);
+
+        self.new_lines.notify_waiters();
+    }
+
+    /// The lines currently held in the buffer, oldest first.
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.buffer.lock().unwrap().lines.iter().cloned().collect()
+    }
+
+    /// Returns the first buffered line matching the regex.
+    pub fn find(&self, regex: &Regex) -> Option<LogLine> {
+        self.buffer.lock().unwrap().find_since(&mut 0, regex)
+    }
+
+    /// Waits until a line matching the regex is logged.
+    ///
+    /// The lines already in the buffer are searched as well, so the line can be logged
+    /// before this is called.
+    pub async fn wait_for(&self, regex: &Regex, duration: Duration) -> Result<LogLine, Elapsed> {
+        self.wait_for_since(0, regex, duration).await
+    }
+
+    /// Like [wait_for](Self::wait_for), but skips the lines logged before the
+    /// [position](Self::position).
+    pub async fn wait_for_since(
+        &self,
+        position: u64,
+        regex: &Regex,
+        duration: Duration,
+    ) -> Result<LogLine, Elapsed> {
+        timeout(duration, async {
+            let mut from = position;
+            loop {
+                // Registered before searching, so no line is missed in between.
+                let new_lines = self.new_lines.notified();
+
+                self.sync_debug_log();
+                let found = self.buffer.lock().unwrap().find_since(&mut from, regex);
+                if let Some(line) = found {
+                    return line;
+                }
+
+                new_lines.await;
+            }
+        })
+        .await
+    }
+
+    /// The number of lines logged so far, including the ones no longer buffered.
+    pub fn position(&self) -> u64 {
+        self.buffer.lock().unwrap().pushed
+    }
+
+    /// Writes the buffered lines to a file.
+    pub fn dump(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(File::create(path)?);
+        for line in self.buffer.lock().unwrap().lines.iter() {
+            writeln!(file, "{line}")?;
+        }
+        file.flush()
+    }
+
+    /// Reads the lines from the stream until it ends.
+    pub(crate) fn capture<R>(&self, source: LogSource, reader: R) -> JoinHandle<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let logs = self.clone();
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                logs.push(source, line);
+            }
+        })
+    }
+
+    /// Follows the node's `debug.log` file, until the returned task is aborted.
+    ///
+    /// Following the same file again, e.g. after the node is restarted, continues where the
+    /// previous task left off.
+    pub(crate) fn tail_debug_log(&self, path: PathBuf) -> JoinHandle<()> {
+        let mut buffer = self.buffer.lock().unwrap();
+        if !matches!(&buffer.tail, Some(tail) if tail.path == path) {
+            buffer.tail = Some(FileTail {
+                path,
+                offset: 0,
+                partial: vec![],
+            });
+        }
+        drop(buffer);
+
+        let logs = self.clone();
+        tokio::spawn(async move {
+            loop {
+                logs.sync_debug_log();
+                sleep(DEBUG_LOG_POLL_INTERVAL).await;
+            }
+        })
+    }
+
+    /// Picks up the lines appended to the `debug.log` file since the last check.
+    pub(crate) fn sync_debug_log(&self) {
+        let lines = match self.buffer.lock().unwrap().tail.as_mut() {
+            Some(tail) => tail.read_lines(),
+            None => return,
+        };
+
+        match lines {
+            Ok(lines) => lines
+                .into_iter()
+                .for_each(|line| self.push(LogSource::DebugLog, line)),
+            Err(e) => eprintln!("failed to read the node's debug.log: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn ring_buffer_and_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("node.log");
+        let logs = NodeLogs::new(2, Some(&path), false).unwrap();
+
+        for i in 0..3 {
+            logs.push(LogSource::Stdout, format!("line {i}"));
+        }
+
+        let texts = logs
+            .lines()
+            .into_iter()
+            .map(|line| line.text)
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["line 1", "line 2"]);
+        assert!(logs.find(&Regex::new("line 0").unwrap()).is_none());
+
+        // The file isn't limited by the buffer's capacity.
+        let file = fs::read_to_string(&path).unwrap();
+        assert_eq!(file.lines().count(), 3);
+        assert!(file.starts_with("[stdout   ] line 0"));
+    }
+
+    #[tokio::test]
+    async fn wait_for_debug_log_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("debug.log");
+        let logs = NodeLogs::new(100, None, false).unwrap();
+        let tail = logs.tail_debug_log(path.clone());
+
+        let regex = Regex::new(r"Peer:\w+ charge: malformed").unwrap();
+        assert!(logs
+            .wait_for(&regex, Duration::from_millis(200))
+            .await
+            .is_err());
+
+        // The second line is written in two parts, the first of them without the newline.
+        fs::write(
+            &path,
+            "2023-Feb-01 Peer:info started\n2023-Feb-01 Peer:warn ",
+        )
+        .unwrap();
+        let waiter = tokio::spawn({
+            let logs = logs.clone();
+            async move { logs.wait_for(&regex, Duration::from_secs(5)).await }
+        });
+        sleep(DEBUG_LOG_POLL_INTERVAL * 2).await;
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "charge: malformed").unwrap();
+
+        let line = waiter.await.unwrap().unwrap();
+        assert_eq!(line.source, LogSource::DebugLog);
+        assert_eq!(line.text, "2023-Feb-01 Peer:warn charge: malformed");
+        assert_eq!(logs.lines().len(), 2);
+
+        tail.abort();
+    }
+
+    #[tokio::test]
+    async fn logs_across_restarts() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("debug.log");
+        let logs = NodeLogs::new(100, None, false).unwrap();
+
+        fs::write(&path, "first run: ledger loaded\n").unwrap();
+        logs.tail_debug_log(path.clone()).abort();
+        logs.sync_debug_log();
+        let restart = logs.position();
+        assert_eq!(restart, 1);
+
+        // The restarted node appends to the same file, which isn't read again from the start.
+        let tail = logs.tail_debug_log(path.clone());
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "second run: started").unwrap();
+
+        let regex = Regex::new("ledger loaded").unwrap();
+        assert!(logs
+            .wait_for_since(restart, &regex, Duration::from_millis(200))
+            .await
+            .is_err());
+        assert!(logs.wait_for(&regex, Duration::ZERO).await.is_ok());
+
+        writeln!(file, "second run: ledger loaded").unwrap();
+        let line = logs
+            .wait_for_since(restart, &regex, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(line.text, "second run: ledger loaded");
+        assert_eq!(logs.lines().len(), 3);
+
+        tail.abort();
+    }
+}
@@ -4,10 +4,16 @@ use std::{io, path::PathBuf};
 
 use crate::setup::constants::{RIPPLE_WORK_DIR, ZIGGURAT_DIR};
 
+pub mod addrs;
 pub mod config;
 pub mod constants;
+pub mod crash;
+pub mod logs;
 pub mod node;
+pub mod resources;
+pub mod snapshot;
 pub mod testnet;
+pub mod version;
 
 pub fn build_ripple_work_path() -> io::Result<PathBuf> {
     Ok(home::home_dir()
@@ -1,47 +1,95 @@
 use std::{
     collections::HashSet,
-    fs, io,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    ffi::OsString,
+    fs,
+    future::{pending, Future},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    os::unix::process::ExitStatusExt,
     path::{Path, PathBuf},
-    process::{Child, Command, ExitStatus, Stdio},
+    process::{ExitStatus, Stdio},
 };
 
 use anyhow::Result;
+use chrono::Utc;
 use fs_extra::{dir, file};
-use tokio::{io::AsyncWriteExt, net::TcpStream, time::Duration};
-
-use crate::setup::{
-    build_ripple_work_path,
-    config::{NodeMetaData, RippledConfigFile},
-    constants::{
-        CONNECTION_TIMEOUT, DEFAULT_PORT, JSON_RPC_PORT, RIPPLED_CONFIG, RIPPLE_SETUP_DIR,
-        STATEFUL_NODES_COUNT, STATEFUL_NODES_DIR, TESTNET_NETWORK_ID, VALIDATORS_FILE_NAME,
-        VALIDATOR_IPS,
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    process::Command,
+    sync::watch,
+    task::JoinHandle,
+    time::{error::Elapsed, Duration},
+};
+use tracing::error;
+
+use crate::{
+    setup::{
+        addrs::NodeAddrs,
+        build_ripple_work_path,
+        config::{
+            ConfigSection, InsightConfig, LedgerHistory, NodeMetaData, NodeSize, OverlayConfig,
+            RippledConfigFile, TransactionQueueConfig, VotingConfig,
+        },
+        constants::{
+            CONNECTION_TIMEOUT, KEPT_LOGS_DIR, LOG_BUFFER_SIZE, NODE_CONFIG_FILE,
+            NODE_STOP_TIMEOUT, RIPPLED_CONFIG, RIPPLED_DEBUG_LOG, RIPPLED_DIR, RIPPLE_SETUP_DIR,
+            STATEFUL_NODES_COUNT, STATEFUL_NODES_DIR, SYNTHETIC_NODE_PUBLIC_KEY,
+            TESTNET_NETWORK_ID, VALIDATORS_FILE_NAME,
+        },
+        crash::{sanitizer_env, CrashMonitor, NodeCrash},
+        logs::{LogLine, LogSource, NodeLogs},
+        resources::{ResourceSample, ResourceSampler},
+        testnet::get_validator_token,
+        version::RippledVersion,
     },
-    testnet::get_validator_token,
+    tools::sntp::SntpServer,
 };
 
-async fn wait_for_start(addr: SocketAddr) {
-    tokio::time::timeout(CONNECTION_TIMEOUT, async {
-        const SLEEP: Duration = Duration::from_millis(10);
+// Waits until the node accepts connections, unless it crashes or the timeout elapses first.
+async fn wait_for_start(node: &Node) -> Result<()> {
+    const SLEEP: Duration = Duration::from_millis(10);
 
+    let addr = node.addr();
+    let listening = async {
         loop {
             if let Ok(mut stream) = TcpStream::connect(addr).await {
-                stream.shutdown().await.unwrap();
+                let _ = stream.shutdown().await;
                 break;
             }
 
             tokio::time::sleep(SLEEP).await;
         }
-    })
-    .await
-    .unwrap();
+    };
+
+    match tokio::time::timeout(CONNECTION_TIMEOUT, node.until_crash(listening)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(crash)) => Err(crash.into()),
+        Err(_) => Err(anyhow::anyhow!(
+            "the node didn't start listening on {addr} within {CONNECTION_TIMEOUT:?}"
+        )),
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ChildExitCode {
     Success,
     ErrorCode(Option<i32>),
+    /// The node didn't stop before the timeout and was killed.
+    Killed,
+}
+
+impl From<ExitStatus> for ChildExitCode {
+    fn from(status: ExitStatus) -> Self {
+        match status.code() {
+            Some(0) => Self::Success,
+            // The node was terminated by the signal asking it to stop.
+            None if status.signal() == Some(libc::SIGTERM) => Self::Success,
+            code => Self::ErrorCode(code),
+        }
+    }
 }
 
 /// Node type is used to select different startup configurations.
@@ -61,6 +109,10 @@ pub struct NodeBuilder {
     meta: NodeMetaData,
     /// Counter for served stateful nodes.
     stateful_nodes_counter: usize,
+    /// The addresses to listen on; unique addresses are allocated for each node if not set.
+    addrs: Option<NodeAddrs>,
+    /// The directory of a previous run the node is started on.
+    reuse: Option<PathBuf>,
 }
 
 impl NodeBuilder {
@@ -75,6 +127,8 @@ impl NodeBuilder {
             conf,
             meta,
             stateful_nodes_counter: 0,
+            addrs: None,
+            reuse: None,
         })
     }
 
@@ -85,15 +139,75 @@ impl NodeBuilder {
             .network_id(TESTNET_NETWORK_ID))
     }
 
+    /// Makes the builder start the node on the directory of a previously started node, see
+    /// [start_reused](Self::start_reused).
+    ///
+    /// The node keeps its database, addresses and settings, which can still be changed with the
+    /// other builder methods.
+    pub fn reuse(mut self, path: &Path) -> Result<Self> {
+        let config = fs::read_to_string(path.join(NODE_CONFIG_FILE))?;
+        self.conf = serde_json::from_str(&config)?;
+        self.addrs = Some(NodeAddrs::new(
+            self.conf.local_addr.ip(),
+            self.conf.local_addr.port(),
+            self.conf.rpc_port,
+        ));
+        self.reuse = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Starts the node on the directory passed to [reuse](Self::reuse).
+    pub async fn start_reused(&mut self, node_type: NodeType) -> Result<Node> {
+        let target = self
+            .reuse
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("no directory to reuse was set"))?;
+        self.start(&target, node_type).await
+    }
+
+    /// Starts the nodes with the binary of the given name in Ziggurat's configuration file,
+    /// instead of the default one.
+    pub fn binary(mut self, name: &str) -> Result<Self> {
+        let setup_path = build_ripple_work_path()?.join(RIPPLE_SETUP_DIR);
+        self.meta = NodeMetaData::named(setup_path, name)?;
+        Ok(self)
+    }
+
+    /// The version of the binary the nodes are started with.
+    pub fn version(&self) -> Result<RippledVersion> {
+        self.meta.version()
+    }
+
     /// Creates [Node] according to configuration and starts its process.
+    ///
+    /// Panics if the target isn't the directory passed to [reuse](Self::reuse).
     pub async fn start(&mut self, target: &Path, node_type: NodeType) -> Result<Node> {
+        if let Some(path) = &self.reuse {
+            assert_eq!(
+                path, target,
+                "the node has to start on the reused directory"
+            );
+        }
         if !target.exists() {
             fs::create_dir_all(target)?;
         }
 
         let setup_path = build_ripple_work_path()?.join(RIPPLE_SETUP_DIR);
 
+        let addrs = match self.addrs {
+            Some(addrs) => addrs,
+            None => NodeAddrs::allocate()?,
+        };
+        self.conf.local_addr = addrs.peer_addr();
+        self.conf.rpc_port = addrs.rpc_port;
+
+        // The arguments only apply to this node, the builder's own are left as they are.
+        let mut meta = self.meta.clone();
         match node_type {
+            // The data is already in place, only the arguments need to be set.
+            NodeType::Stateful if self.reuse.is_some() => {
+                meta.start_args = stateful_start_args();
+            }
             NodeType::Stateful => {
                 let node_idx = self.stateful_nodes_counter;
                 self.stateful_nodes_counter += 1;
@@ -109,17 +223,10 @@ impl NodeBuilder {
                 copy_options.overwrite = true;
                 dir::copy(source, target, &copy_options)?;
 
-                self.conf.local_addr =
-                    SocketAddr::new(VALIDATOR_IPS[node_idx].parse().unwrap(), DEFAULT_PORT);
                 self.conf.validator_token = Some(get_validator_token(node_idx));
-                self.meta.start_args = vec![
-                    "--valid".into(),
-                    "--quorum".into(),
-                    "1".into(),
-                    "--load".into(),
-                ];
+                meta.start_args = stateful_start_args();
             }
-            NodeType::Stateless => {
+            NodeType::Stateless if self.reuse.is_none() => {
                 let validators_file_src = setup_path.join(VALIDATORS_FILE_NAME);
                 let validators_file_dst = target.join(VALIDATORS_FILE_NAME);
 
@@ -128,31 +235,26 @@ impl NodeBuilder {
 
                 self.conf.network_id = None;
                 self.conf.validator_token = None;
-                self.conf.local_addr =
-                    SocketAddr::new(VALIDATOR_IPS[0].parse().unwrap(), DEFAULT_PORT);
             }
-            NodeType::Testnet => (),
+            NodeType::Stateless | NodeType::Testnet => (),
         }
 
-        let rippled_cfg = RippledConfigFile::generate(&self.conf, target)?;
-        let rippled_cfg_path = target.join(RIPPLED_CONFIG);
-        fs::write(rippled_cfg_path.clone(), rippled_cfg)?;
+        let rippled_cfg_path = write_config(&self.conf, target)?;
 
         if self.conf.enable_sharding {
-            self.meta.start_args.push("--nodetoshard".into());
+            meta.start_args.push("--nodetoshard".into());
         }
 
         if self.conf.log_to_stdout {
-            self.meta.start_args.push("--debug".into());
+            meta.start_args.push("--debug".into());
         }
 
-        self.meta.start_args.push("--conf".into());
-        self.meta.start_args.push(rippled_cfg_path.into());
+        meta.start_args.push("--conf".into());
+        meta.start_args.push(rippled_cfg_path.into());
 
-        let node = self.start_node();
-        wait_for_start(node.config.local_addr).await;
+        let node = self.start_node(target, meta)?;
+        wait_for_start(&node).await?;
 
-        self.meta = NodeMetaData::new(setup_path)?; // Reset args
         Ok(node)
     }
 
@@ -168,9 +270,17 @@ impl NodeBuilder {
         self
     }
 
-    /// Sets address to bind to.
-    pub fn set_addr(mut self, addr: SocketAddr) -> Self {
-        self.conf.local_addr = addr;
+    /// Sets public keys of the nodes which are members of the node's cluster.
+    ///
+    /// Only used when clustering is enabled.
+    pub fn cluster_nodes(mut self, public_keys: Vec<String>) -> Self {
+        self.conf.cluster_nodes = public_keys;
+        self
+    }
+
+    /// Sets the addresses to listen on, instead of allocating unique ones for each node.
+    pub fn addrs(mut self, addrs: NodeAddrs) -> Self {
+        self.addrs = Some(addrs);
         self
     }
 
@@ -186,6 +296,106 @@ impl NodeBuilder {
         self
     }
 
+    /// Sets separate limits of inbound and outbound peer connections, replacing
+    /// [max_peers](Self::max_peers).
+    pub fn peers_in_out_max(mut self, peers_in_max: usize, peers_out_max: usize) -> Self {
+        self.conf.peers_in_max = Some(peers_in_max);
+        self.conf.peers_out_max = Some(peers_out_max);
+        self
+    }
+
+    /// Sets whether the node asks its peers not to share its address.
+    pub fn peer_private(mut self, private: bool) -> Self {
+        self.conf.peer_private = Some(private);
+        self
+    }
+
+    /// Sets whether the peer messages are compressed.
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.conf.compression = Some(enabled);
+        self
+    }
+
+    /// Sets the SNTP servers the node takes its time from; rippled always queries them on
+    /// the NTP port (123).
+    ///
+    /// No servers are set by default, so the node follows the system clock and doesn't depend
+    /// on the network, see [PUBLIC_SNTP_SERVERS](crate::setup::constants::PUBLIC_SNTP_SERVERS)
+    /// for the public ones.
+    pub fn sntp_servers(mut self, servers: Vec<String>) -> Self {
+        self.conf.sntp_servers = servers;
+        self
+    }
+
+    /// Makes the node take its time from the local [SntpServer], e.g. to skew its clock.
+    ///
+    /// The server listens on the privileged NTP port, see [SntpServer::start_local].
+    pub fn sntp_server(self, server: &SntpServer) -> Self {
+        self.sntp_servers(vec![server.ip().to_string()])
+    }
+
+    /// Sets the `[overlay]` options.
+    pub fn overlay(mut self, overlay: OverlayConfig) -> Self {
+        self.conf.overlay = overlay;
+        self
+    }
+
+    /// Sets the addresses the node connects to when it doesn't have enough peers.
+    ///
+    /// Unlike [initial_peers](Self::initial_peers), the node doesn't keep connections to them.
+    pub fn ips(mut self, addrs: Vec<SocketAddr>) -> Self {
+        self.conf.ips = addrs;
+        self
+    }
+
+    /// Sets the fees the node votes for as a validator.
+    pub fn voting(mut self, voting: VotingConfig) -> Self {
+        self.conf.voting = voting;
+        self
+    }
+
+    /// Sets the `[transaction_queue]` tuning.
+    pub fn transaction_queue(mut self, queue: TransactionQueueConfig) -> Self {
+        self.conf.transaction_queue = queue;
+        self
+    }
+
+    /// Sets the `[node_size]`.
+    pub fn node_size(mut self, size: NodeSize) -> Self {
+        self.conf.node_size = Some(size);
+        self
+    }
+
+    /// Sets the amount of `[ledger_history]` to keep.
+    pub fn ledger_history(mut self, history: LedgerHistory) -> Self {
+        self.conf.ledger_history = Some(history);
+        self
+    }
+
+    /// Sets admin commands the node runs on startup, e.g.
+    /// `json!({ "command": "log_level", "severity": "debug" })`.
+    pub fn rpc_startup(mut self, commands: Vec<serde_json::Value>) -> Self {
+        self.conf.rpc_startup = commands;
+        self
+    }
+
+    /// Makes the node report its metrics to a StatsD server.
+    pub fn insight(mut self, insight: InsightConfig) -> Self {
+        self.conf.insight = Some(insight);
+        self
+    }
+
+    /// Adds a raw section to rippled.cfg, replacing the generated one with the same name.
+    ///
+    /// Meant for the options which can't be set otherwise.
+    pub fn extra_section(mut self, name: &str, lines: Vec<String>) -> Self {
+        self.conf.extra_sections.push(ConfigSection {
+            name: name.into(),
+            lines,
+        });
+        self
+    }
+
     /// Sets validator token to be placed in rippled.cfg.
     /// This will configure the node to run as a validator.
     pub fn validator_token(mut self, token: String) -> Self {
@@ -205,70 +415,221 @@ impl NodeBuilder {
         self
     }
 
-    fn start_node(&self) -> Node {
-        let (stdout, stderr) = match self.conf.log_to_stdout {
-            true => (Stdio::inherit(), Stdio::inherit()),
-            false => (Stdio::null(), Stdio::null()),
-        };
+    /// Sets the number of the most recent log lines kept in memory.
+    pub fn log_buffer_size(mut self, lines: usize) -> Self {
+        self.conf.log_buffer_size = lines;
+        self
+    }
 
-        let child = Command::new(&self.meta.start_command)
-            .current_dir(&self.meta.path)
-            .args(&self.meta.start_args)
-            .stdin(Stdio::null())
-            .stdout(stdout)
-            .stderr(stderr)
-            .spawn()
-            .expect("node failed to start");
+    /// Sets a file to which all of the node's log lines are written.
+    pub fn log_file(mut self, path: PathBuf) -> Self {
+        self.conf.log_file = Some(path);
+        self
+    }
 
-        Node {
-            child,
-            meta: self.meta.clone(),
-            config: self.conf.clone(),
-        }
+    fn start_node(&self, target: &Path, meta: NodeMetaData) -> io::Result<Node> {
+        let logs = NodeLogs::new(
+            self.conf.log_buffer_size,
+            self.conf.log_file.as_deref(),
+            self.conf.log_to_stdout,
+        )?;
+
+        Node::spawn(self.conf.clone(), meta, target, logs)
+    }
+}
+
+// The arguments starting a validator on the preloaded ledger.
+fn stateful_start_args() -> Vec<OsString> {
+    vec![
+        "--valid".into(),
+        "--quorum".into(),
+        "1".into(),
+        "--load".into(),
+    ]
+}
+
+// Writes rippled.cfg along with the configuration it's generated from, which is read back
+// when the node's directory is reused. Returns the path of rippled.cfg.
+fn write_config(config: &NodeConfig, target: &Path) -> Result<PathBuf> {
+    let rippled_cfg = RippledConfigFile::generate(config, target)?;
+    let rippled_cfg_path = target.join(RIPPLED_CONFIG);
+    fs::write(&rippled_cfg_path, rippled_cfg)?;
+    fs::write(
+        target.join(NODE_CONFIG_FILE),
+        serde_json::to_string_pretty(config)?,
+    )?;
+
+    Ok(rippled_cfg_path)
+}
+
+impl Node {
+    // Starts the node's process on the directory, with the configuration already written there.
+    fn spawn(
+        config: NodeConfig,
+        meta: NodeMetaData,
+        target: &Path,
+        logs: NodeLogs,
+    ) -> io::Result<Node> {
+        let mut child = Command::new(&meta.start_command)
+            .current_dir(&meta.path)
+            .args(&meta.start_args)
+            .envs(sanitizer_env(target))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // In case the runtime shuts down before the node is stopped.
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let pid = child
+            .id()
+            .ok_or_else(|| io::Error::other("node exited before it was started"))?;
+        let crash_monitor = CrashMonitor::new(
+            config.local_addr,
+            pid,
+            vec![target.to_path_buf(), meta.path.clone()],
+            logs.clone(),
+        );
+        // The logs are kept across restarts, only the lines since the start belong to this run.
+        let log_start = logs.position();
+
+        logs.capture(LogSource::Stdout, child.stdout.take().unwrap());
+        logs.capture(LogSource::Stderr, child.stderr.take().unwrap());
+        let debug_log_tail = logs.tail_debug_log(target.join(RIPPLED_DIR).join(RIPPLED_DEBUG_LOG));
+
+        // The child is owned by a task waiting for its exit, so the exit can be awaited
+        // by any number of callers, while the node is still in use.
+        let (exit_tx, exit_rx) = watch::channel(None);
+        tokio::spawn(async move {
+            let exit = child.wait().await;
+            if let Err(e) = &exit {
+                error!("failed to wait for the node {pid}: {e}");
+            }
+            let _ = exit_tx.send(Some(exit));
+        });
+
+        Ok(Node {
+            pid,
+            path: target.to_path_buf(),
+            exit: exit_rx,
+            logs,
+            log_start,
+            debug_log_tail,
+            crash_monitor,
+            meta,
+            config,
+        })
     }
 }
 
 /// Startup configuration for the node.
 /// Some fields are written to the node's configuration file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeConfig {
     /// The socket address of the node.
     pub local_addr: SocketAddr,
+    /// The port of the node's JSON-RPC admin API.
+    pub rpc_port: u16,
     /// The initial peer set of the node.
     pub initial_peers: HashSet<SocketAddr>,
     /// The initial max number of peer connections to allow.
     pub max_peers: usize,
+    /// The max number of inbound peer connections, set along with `peers_out_max`.
+    pub peers_in_max: Option<usize>,
+    /// The max number of outbound peer connections, set along with `peers_in_max`.
+    pub peers_out_max: Option<usize>,
+    /// Whether the peers are asked not to share the node's address.
+    pub peer_private: Option<bool>,
+    /// Whether the peer messages are compressed.
+    pub compression: Option<bool>,
+    /// The `[overlay]` options.
+    pub overlay: OverlayConfig,
+    /// Addresses to connect to when the node doesn't have enough peers.
+    pub ips: Vec<SocketAddr>,
+    /// The SNTP servers to take the time from.
+    pub sntp_servers: Vec<String>,
     /// Token when run as a validator.
     pub validator_token: Option<String>,
     /// Network's id to form an isolated testnet.
     pub network_id: Option<u32>,
     /// Setting this option to true will enable node logging to stdout.
     pub log_to_stdout: bool,
+    /// The number of the most recent log lines kept in memory.
+    pub log_buffer_size: usize,
+    /// A file to which all of the log lines are written.
+    pub log_file: Option<PathBuf>,
     /// Setting this option to true will enable history sharding.
     pub enable_sharding: bool,
     /// Setting this option to true will enable clustering.
     pub enable_cluster: bool,
+    /// Public keys of the cluster members.
+    pub cluster_nodes: Vec<String>,
+    /// The fees voted for as a validator.
+    pub voting: VotingConfig,
+    /// The `[transaction_queue]` tuning.
+    pub transaction_queue: TransactionQueueConfig,
+    /// The `[node_size]`; if not set, rippled picks one based on the machine's memory.
+    pub node_size: Option<NodeSize>,
+    /// The `[ledger_history]`; if not set, rippled keeps its default of 256 ledgers.
+    pub ledger_history: Option<LedgerHistory>,
+    /// Admin commands run on startup.
+    pub rpc_startup: Vec<serde_json::Value>,
+    /// The StatsD server to report the metrics to.
+    pub insight: Option<InsightConfig>,
+    /// Raw sections replacing or extending the generated rippled.cfg.
+    pub extra_sections: Vec<ConfigSection>,
 }
 
 impl Default for NodeConfig {
     fn default() -> Self {
         Self {
-            local_addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, DEFAULT_PORT)),
+            // Both ports are allocated when the node is started.
+            local_addr: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+            rpc_port: 0,
             initial_peers: Default::default(),
             max_peers: 0,
+            peers_in_max: None,
+            peers_out_max: None,
+            peer_private: None,
+            compression: None,
+            overlay: Default::default(),
+            ips: vec![],
+            sntp_servers: vec![],
             validator_token: None,
             network_id: None,
             log_to_stdout: false,
+            log_buffer_size: LOG_BUFFER_SIZE,
+            log_file: None,
             enable_sharding: false,
             enable_cluster: false,
+            cluster_nodes: vec![SYNTHETIC_NODE_PUBLIC_KEY.into()],
+            voting: Default::default(),
+            transaction_queue: Default::default(),
+            node_size: None,
+            ledger_history: None,
+            rpc_startup: vec![],
+            insight: None,
+            extra_sections: vec![],
         }
     }
 }
 
 pub struct Node {
-    child: Child,
+    /// The process id of the running node.
+    pid: u32,
+    /// The node's directory.
+    path: PathBuf,
+    /// The exit status, set once the node exits or waiting for it fails.
+    exit: watch::Receiver<Option<io::Result<ExitStatus>>>,
+    /// The captured log output.
+    logs: NodeLogs,
+    /// The [position](NodeLogs::position) of the first log line of the current run.
+    log_start: u64,
+    /// The task following the `debug.log` file.
+    debug_log_tail: JoinHandle<()>,
+    /// Used to tell a crash from a requested stop.
+    crash_monitor: CrashMonitor,
     config: NodeConfig,
-    #[allow(dead_code)]
     meta: NodeMetaData,
 }
 
@@ -279,63 +640,306 @@ impl Node {
             .unwrap()
     }
 
-    pub fn stop(&mut self) -> io::Result<ChildExitCode> {
-        match self.child.try_wait()? {
-            None => self.child.kill()?,
-            Some(status) => return Ok(ChildExitCode::ErrorCode(status.code())),
+    /// Stops the node gracefully, see [Node::stop_timeout].
+    pub async fn stop(&mut self) -> io::Result<ChildExitCode> {
+        self.stop_timeout(NODE_STOP_TIMEOUT).await
+    }
+
+    /// Asks the node to shut down with a `SIGTERM` and kills it with a `SIGKILL` if it doesn't
+    /// exit before the timeout, in which case [ChildExitCode::Killed] is returned.
+    ///
+    /// If the node already exited on its own and the crash wasn't reported yet, e.g. by
+    /// [Node::check_crash], the error wraps the [NodeCrash] report. Once reported, a crashed
+    /// node can be stopped and restarted like any other.
+    pub async fn stop_timeout(&mut self, timeout: Duration) -> io::Result<ChildExitCode> {
+        if let Some(crash) = self.unreported_crash() {
+            return Err(io::Error::other(crash));
+        }
+        if let Some(status) = self.exit_status() {
+            return Ok(status.into());
         }
 
-        let exit_status = self.child.wait()?;
+        self.crash_monitor.set_stopping();
+        self.signal(libc::SIGTERM)?;
+        match tokio::time::timeout(timeout, self.wait_until_exit()).await {
+            Ok(status) => Ok(status?.into()),
+            Err(_) => {
+                self.signal(libc::SIGKILL)?;
+                self.wait_until_exit().await?;
+                Ok(ChildExitCode::Killed)
+            }
+        }
+    }
+
+    /// Stops the node and starts it again on the same directory, keeping its database,
+    /// addresses and logs.
+    ///
+    /// A crashed node is only restarted once the crash was reported, e.g. by
+    /// [Node::check_crash]; otherwise the error wraps the [NodeCrash] report, like with
+    /// [Node::stop_timeout]. The same goes for the other restarts.
+    pub async fn restart(&mut self) -> Result<()> {
+        self.restart_with(|_| ()).await
+    }
+
+    /// Stops the node and starts it again on the same directory, with the settings written to
+    /// rippled.cfg changed by the closure.
+    pub async fn restart_with(&mut self, change: impl FnOnce(&mut NodeConfig)) -> Result<()> {
+        let mut config = self.config.clone();
+        change(&mut config);
+        self.respawn(config, self.meta.clone()).await
+    }
 
-        match exit_status.code() {
-            None => Ok(ChildExitCode::Success),
-            Some(code) if code == 0 => Ok(ChildExitCode::Success),
-            Some(code) => Ok(ChildExitCode::ErrorCode(Some(code))),
+    /// Stops the node and starts it again on the same directory with another `rippled` binary,
+    /// e.g. to upgrade it while keeping its database.
+    pub async fn restart_with_binary(&mut self, rippled: &Path) -> Result<()> {
+        let rippled = rippled.canonicalize()?;
+        let mut meta = self.meta.clone();
+        if let Some(dir) = rippled.parent() {
+            meta.path = dir.to_path_buf();
         }
+        meta.start_command = rippled.into();
+
+        self.respawn(self.config.clone(), meta).await
     }
 
-    /// Non-blocking function which periodically checks the node's status code.
-    pub async fn wait_until_exit(&mut self) -> ExitStatus {
-        // Once the async Drop trait support is introduced in Rust,
-        // we can remove the loop and use a non-blocking tokio::process::Command
-        // and then call a tokio version of wait() function on it.
-        //
-        // Because, calling wait() on std::process::Command will block the
-        // entire tokio runtime and the whole test process will be stopped until
-        // the node is actually killed - which is bad if we have other
-        // tokio threads running.
-        //
-        // So looping with a non-blocking try_wait() is the alternative solution.
-        loop {
-            match self.child.try_wait().expect("waiting try failed") {
-                None => {
-                    tokio::time::sleep(Duration::from_millis(500)).await;
-                    continue;
+    /// The version of the binary the node was started with.
+    pub fn version(&self) -> Result<RippledVersion> {
+        self.meta.version()
+    }
+
+    /// Kills the node with a `SIGKILL`, without letting it shut down gracefully, e.g. to
+    /// simulate a power loss. The exit isn't reported as a crash, and the node can be restarted.
+    ///
+    /// Like [Node::stop_timeout], returns the crash report if the node crashed unnoticed.
+    pub async fn kill(&mut self) -> io::Result<()> {
+        if let Some(crash) = self.unreported_crash() {
+            return Err(io::Error::other(crash));
+        }
+
+        self.crash_monitor.set_stopping();
+        self.signal(libc::SIGKILL)?;
+        self.wait_until_exit().await.map(|_| ())
+    }
+
+    // Stops the node, unless it has already been stopped, and starts it again on its directory.
+    async fn respawn(&mut self, config: NodeConfig, meta: NodeMetaData) -> Result<()> {
+        // A crash has to be noticed before restarting, otherwise it's returned instead.
+        if let Some(crash) = self.unreported_crash() {
+            return Err(crash.into());
+        }
+        self.stop().await?;
+        write_config(&config, &self.path)?;
+
+        let node = Node::spawn(config, meta, &self.path, self.logs.clone())?;
+        wait_for_start(&node).await?;
+        // The stopped node is simply dropped.
+        *self = node;
+
+        Ok(())
+    }
+
+    /// Returns a future which resolves once the node exits.
+    ///
+    /// The future doesn't borrow the node, so it can be raced against the test itself,
+    /// to get notified right away when the node crashes.
+    pub fn wait_until_exit(&self) -> impl Future<Output = io::Result<ExitStatus>> + 'static {
+        let mut exit = self.exit.clone();
+
+        async move {
+            loop {
+                match &*exit.borrow_and_update() {
+                    Some(Ok(status)) => return Ok(*status),
+                    // The error isn't cloneable, so every caller gets its own copy.
+                    Some(Err(e)) => {
+                        return Err(io::Error::new(
+                            e.kind(),
+                            format!("failed to wait for the node: {e}"),
+                        ))
+                    }
+                    None => (),
                 }
-                Some(status) => return status,
+                exit.changed()
+                    .await
+                    .map_err(|_| io::Error::other("lost track of the node process"))?;
             }
         }
     }
 
+    /// Returns the crash report if the node exited without being asked to.
+    ///
+    /// The crash counts as reported afterwards, so it's no longer returned by [Node::stop] or
+    /// printed once the node is dropped.
+    pub fn check_crash(&self) -> Option<NodeCrash> {
+        match self.exit_status() {
+            Some(status) if !self.crash_monitor.is_stopping() => {
+                Some(self.crash_monitor.report(status))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the node exited without being asked to, reported or not.
+    pub fn has_crashed(&self) -> bool {
+        self.exit_status().is_some() && !self.crash_monitor.is_stopping()
+    }
+
+    // Returns the crash report, unless the node didn't crash or the crash was already reported.
+    fn unreported_crash(&self) -> Option<NodeCrash> {
+        if self.crash_monitor.is_reported() {
+            return None;
+        }
+
+        self.check_crash()
+    }
+
+    /// Returns a future which resolves with the crash report if the node exits without
+    /// being asked to; it never resolves otherwise. The crash counts as reported, like with
+    /// [Node::check_crash].
+    pub fn crashed(&self) -> impl Future<Output = NodeCrash> + 'static {
+        let exit = self.wait_until_exit();
+        let crash_monitor = self.crash_monitor.clone();
+
+        async move {
+            match exit.await {
+                Ok(status) if !crash_monitor.is_stopping() => crash_monitor.report(status),
+                _ => pending().await,
+            }
+        }
+    }
+
+    /// Runs the future unless the node crashes first, e.g.:
+    ///
+    /// ```ignore
+    /// node.until_crash(synth_node.send_random_bytes(addr)).await.unwrap();
+    /// ```
+    pub async fn until_crash<F: Future>(&self, future: F) -> Result<F::Output, NodeCrash> {
+        tokio::select! {
+            // A node which has already crashed is reported without running the future.
+            biased;
+            crash = self.crashed() => Err(crash),
+            output = future => Ok(output),
+        }
+    }
+
+    /// The exit status of the node, if it has already exited.
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        match *self.exit.borrow() {
+            Some(Ok(status)) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// The process id of the node.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    // Sends a signal to the node process, unless it has already exited.
+    fn signal(&self, signal: libc::c_int) -> io::Result<()> {
+        if self.exit_status().is_some() {
+            return Ok(());
+        }
+
+        // SAFETY: `kill` has no memory safety requirements.
+        match unsafe { libc::kill(self.pid as libc::pid_t, signal) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Reads the current resource usage of the node's process.
+    pub fn resource_usage(&self) -> io::Result<ResourceSample> {
+        ResourceSample::read(self.pid)
+    }
+
+    /// Starts sampling the resource usage of the node's process at the given interval.
+    pub fn sample_resources(&self, interval: Duration) -> ResourceSampler {
+        ResourceSampler::start(self.pid, interval)
+    }
+
+    /// The captured log output of the node.
+    pub fn logs(&self) -> &NodeLogs {
+        &self.logs
+    }
+
+    /// Waits until the node logs a line matching the regex, see [NodeLogs::wait_for].
+    ///
+    /// Only the lines logged since the last (re)start are searched.
+    pub async fn wait_for_log(&self, regex: &Regex, timeout: Duration) -> Result<LogLine, Elapsed> {
+        self.logs
+            .wait_for_since(self.log_start, regex, timeout)
+            .await
+    }
+
+    /// The node's directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the buffered log lines to a file under `~/.ziggurat/ripple/logs`, which
+    /// isn't removed along with the node's directory.
+    pub fn keep_logs(&self) -> io::Result<PathBuf> {
+        let dir = build_ripple_work_path()?.join(KEPT_LOGS_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!(
+            "{}-{}-{}.log",
+            self.config.local_addr.ip(),
+            self.config.local_addr.port(),
+            Utc::now().format("%Y%m%d-%H%M%S%.3f"),
+        ));
+        self.logs.sync_debug_log();
+        self.logs.dump(&path)?;
+
+        Ok(path)
+    }
+
     pub fn addr(&self) -> SocketAddr {
         self.config.local_addr
     }
 
-    pub fn rpc_url(&self) -> String {
-        format!(
-            "http://{addr}:{port}",
-            addr = self.config.local_addr.ip(),
-            port = JSON_RPC_PORT
+    /// All of the addresses the node listens on.
+    pub fn addrs(&self) -> NodeAddrs {
+        NodeAddrs::new(
+            self.config.local_addr.ip(),
+            self.config.local_addr.port(),
+            self.config.rpc_port,
         )
     }
+
+    pub fn rpc_url(&self) -> String {
+        self.addrs().rpc_url()
+    }
 }
 
 impl Drop for Node {
     fn drop(&mut self) {
+        self.debug_log_tail.abort();
+        let crash = self.unreported_crash();
+
+        // The node is dropped while unwinding from a failed test, so the logs are worth keeping.
+        if std::thread::panicking() || crash.is_some() {
+            match self.keep_logs() {
+                Ok(path) => eprintln!("the node's logs were kept in {}", path.display()),
+                Err(e) => eprintln!("failed to keep the node's logs: {e}"),
+            }
+        }
+
+        // An async stop isn't possible here, so the node is simply killed if it's still running;
+        // the exit is still collected by the waiting task.
         // We should avoid a panic.
-        if let Err(e) = self.stop() {
+        if let Err(e) = self.signal(libc::SIGKILL) {
             eprintln!("failed to stop the node: {e}");
         }
+
+        // A crash which wasn't noticed by the test fails it here.
+        if let Some(crash) = crash {
+            if std::thread::panicking() {
+                eprintln!("{crash}");
+            } else {
+                panic!("{crash}");
+            }
+        }
     }
 }
 
@@ -357,6 +961,27 @@ mod test {
 
     const SLEEP: Duration = Duration::from_millis(100);
 
+    #[test]
+    fn exit_codes() {
+        // The raw wait statuses: the exit code in the second byte, or the signal number.
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+        let signaled = ExitStatus::from_raw;
+
+        assert_eq!(ChildExitCode::from(exited(0)), ChildExitCode::Success);
+        assert_eq!(
+            ChildExitCode::from(exited(1)),
+            ChildExitCode::ErrorCode(Some(1))
+        );
+        assert_eq!(
+            ChildExitCode::from(signaled(libc::SIGTERM)),
+            ChildExitCode::Success
+        );
+        assert_eq!(
+            ChildExitCode::from(signaled(libc::SIGSEGV)),
+            ChildExitCode::ErrorCode(None)
+        );
+    }
+
     #[tokio::test]
     #[ignore = "use only when changing src/setup files"]
     async fn run_stateless_nodes_in_parallel() {
@@ -376,7 +1001,7 @@ mod test {
         sleep(SLEEP).await;
 
         for mut node in nodes {
-            node.stop().unwrap();
+            node.stop().await.unwrap();
         }
     }
 
@@ -394,7 +1019,7 @@ mod test {
                 .expect("Unable to start node");
 
             sleep(SLEEP).await;
-            node.stop().unwrap();
+            node.stop().await.unwrap();
         }
     }
 
@@ -412,7 +1037,7 @@ mod test {
                 .expect("Unable to start node");
 
             sleep(SLEEP).await;
-            node.stop().unwrap();
+            node.stop().await.unwrap();
         }
     }
 
@@ -430,10 +1055,95 @@ mod test {
                 .expect("Unable to start node");
 
             sleep(SLEEP).await;
-            node.stop().unwrap();
+            node.stop().await.unwrap();
         }
     }
 
+    #[tokio::test]
+    #[ignore = "use only when changing src/setup files"]
+    async fn restart_stateful_node() {
+        let target = TempDir::new().expect("Can't build tmp dir");
+        let mut node = NodeBuilder::stateful()
+            .expect("Can't build a stateful node")
+            .start(target.path(), NodeType::Stateful)
+            .await
+            .expect("Unable to start node");
+        let addrs = node.addrs();
+
+        sleep(SLEEP).await;
+        node.restart_with(|config| config.max_peers = 5)
+            .await
+            .expect("Unable to restart node");
+        assert_eq!(node.addrs(), addrs);
+        node.stop().await.unwrap();
+
+        // The stopped node's directory can be picked up by a new builder as well.
+        let mut node = NodeBuilder::stateful()
+            .expect("Can't build a stateful node")
+            .reuse(target.path())
+            .expect("Unable to reuse the node's directory")
+            .start_reused(NodeType::Stateful)
+            .await
+            .expect("Unable to start node");
+        assert_eq!(node.addrs(), addrs);
+        assert_eq!(node.config.max_peers, 5);
+
+        sleep(SLEEP).await;
+        node.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "use only when changing src/setup files"]
+    async fn restart_crashed_node() {
+        let target = TempDir::new().expect("Can't build tmp dir");
+        let mut node = NodeBuilder::stateful()
+            .expect("Can't build a stateful node")
+            .start(target.path(), NodeType::Stateful)
+            .await
+            .expect("Unable to start node");
+
+        // SAFETY: `kill` has no memory safety requirements.
+        assert_eq!(
+            unsafe { libc::kill(node.pid() as libc::pid_t, libc::SIGSEGV) },
+            0
+        );
+        let crash = node.until_crash(sleep(Duration::from_secs(10))).await;
+        assert_eq!(crash.unwrap_err().signal, Some(libc::SIGSEGV));
+        assert!(node.has_crashed());
+
+        // The crash was noticed, so the node can be stopped and restarted.
+        assert!(node.stop().await.is_ok());
+        node.restart().await.expect("Unable to restart node");
+        assert!(node.check_crash().is_none());
+        node.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "use only when changing src/setup files"]
+    async fn restart_unnoticed_crash() {
+        let target = TempDir::new().expect("Can't build tmp dir");
+        let mut node = NodeBuilder::stateful()
+            .expect("Can't build a stateful node")
+            .start(target.path(), NodeType::Stateful)
+            .await
+            .expect("Unable to start node");
+
+        // SAFETY: `kill` has no memory safety requirements.
+        assert_eq!(
+            unsafe { libc::kill(node.pid() as libc::pid_t, libc::SIGSEGV) },
+            0
+        );
+        node.wait_until_exit().await.unwrap();
+
+        // The crash is returned instead of restarting the node, after which it's reported.
+        let err = node.restart().await.unwrap_err();
+        let crash = err.downcast_ref::<NodeCrash>().expect("not a crash report");
+        assert_eq!(crash.signal, Some(libc::SIGSEGV));
+
+        node.restart().await.expect("Unable to restart node");
+        node.stop().await.unwrap();
+    }
+
     #[tokio::test]
     #[ignore = "use only when changing src/setup files"]
     async fn run_stateful_nodes_in_parallel() {
@@ -453,7 +1163,7 @@ mod test {
         sleep(SLEEP).await;
 
         for mut node in nodes {
-            node.stop().unwrap();
+            node.stop().await.unwrap();
         }
     }
 }
@@ -0,0 +1,252 @@
+//! Sampling of the resources used by a [Node](crate::setup::node::Node)'s process,
+//! read from `/proc/<pid>`.
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tabled::Tabled;
+use tokio::{
+    task::JoinHandle,
+    time::{interval, Instant, MissedTickBehavior},
+};
+
+/// A single measurement of the process's resource usage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Tabled)]
+pub struct ResourceSample {
+    /// The time since the sampling started.
+    #[tabled(rename = "time (s)", display_with = "fmt_secs")]
+    pub elapsed: Duration,
+    /// The resident set size in bytes.
+    #[tabled(rename = "rss (MiB)", display_with = "fmt_mib")]
+    pub rss: u64,
+    /// The CPU time spent in both user and kernel mode.
+    #[tabled(rename = "cpu time (s)", display_with = "fmt_secs")]
+    pub cpu_time: Duration,
+    #[tabled(rename = "threads")]
+    pub threads: u64,
+    /// The number of open file descriptors, sockets included.
+    #[tabled(rename = "fds")]
+    pub fds: u64,
+    #[tabled(rename = "sockets")]
+    pub sockets: u64,
+}
+
+impl ResourceSample {
+    /// Reads the current resource usage of the process.
+    pub fn read(pid: u32) -> io::Result<Self> {
+        let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+
+        let status = fs::read_to_string(proc_dir.join("status"))?;
+        let status_field = |name: &str| -> io::Result<u64> {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|value| value.split_whitespace().next()?.parse().ok())
+                .ok_or_else(|| invalid_data(format!("missing {name} in the process status")))
+        };
+
+        let mut fds = 0;
+        let mut sockets = 0;
+        for entry in fs::read_dir(proc_dir.join("fd"))? {
+            // The descriptor could have been closed in the meantime.
+            let target = match fs::read_link(entry?.path()) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            fds += 1;
+            if target.to_string_lossy().starts_with("socket:") {
+                sockets += 1;
+            }
+        }
+
+        Ok(Self {
+            elapsed: Duration::ZERO,
+            rss: status_field("VmRSS")? * 1024,
+            cpu_time: read_cpu_time(&fs::read_to_string(proc_dir.join("stat"))?)?,
+            threads: status_field("Threads")?,
+            fds,
+            sockets,
+        })
+    }
+}
+
+fn invalid_data(error: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// Sums up `utime` and `stime` from the contents of `/proc/<pid>/stat`.
+fn read_cpu_time(stat: &str) -> io::Result<Duration> {
+    // The command name can contain spaces, the fields are counted from the end of it.
+    let fields = stat
+        .rsplit_once(')')
+        .map(|(_, fields)| fields.split_whitespace().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    // `utime` and `stime` are the 14th and 15th field; the first two precede the fields.
+    let ticks = fields
+        .get(11..13)
+        .ok_or_else(|| invalid_data("truncated process stat".into()))?
+        .iter()
+        .map(|field| {
+            field
+                .parse::<u64>()
+                .map_err(|e| invalid_data(e.to_string()))
+        })
+        .sum::<io::Result<u64>>()?;
+
+    // SAFETY: `sysconf` has no memory safety requirements.
+    let ticks_per_sec = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    };
+
+    Ok(Duration::from_secs_f64(ticks as f64 / ticks_per_sec as f64))
+}
+
+fn fmt_secs(duration: &Duration) -> String {
+    format!("{:.2}", duration.as_secs_f64())
+}
+
+fn fmt_mib(bytes: &u64) -> String {
+    format!("{:.1}", *bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Peak resource usage over a series of samples, e.g. of a single test run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Tabled)]
+pub struct ResourceStats {
+    /// Describes the run the samples come from.
+    #[tabled(rename = "run")]
+    pub label: String,
+    #[tabled(rename = "samples")]
+    pub samples: usize,
+    #[tabled(rename = "peak rss (MiB)", display_with = "fmt_mib")]
+    pub peak_rss: u64,
+    /// The CPU time spent during the run.
+    #[tabled(rename = "cpu time (s)", display_with = "fmt_secs")]
+    pub cpu_time: Duration,
+    #[tabled(rename = "peak threads")]
+    pub peak_threads: u64,
+    #[tabled(rename = "peak fds")]
+    pub peak_fds: u64,
+    #[tabled(rename = "peak sockets")]
+    pub peak_sockets: u64,
+}
+
+impl ResourceStats {
+    pub fn new(label: impl Into<String>, samples: &[ResourceSample]) -> Self {
+        let peak = |value: fn(&ResourceSample) -> u64| samples.iter().map(value).max();
+
+        Self {
+            label: label.into(),
+            samples: samples.len(),
+            peak_rss: peak(|sample| sample.rss).unwrap_or_default(),
+            cpu_time: match (samples.first(), samples.last()) {
+                (Some(first), Some(last)) => last.cpu_time.saturating_sub(first.cpu_time),
+                _ => Duration::ZERO,
+            },
+            peak_threads: peak(|sample| sample.threads).unwrap_or_default(),
+            peak_fds: peak(|sample| sample.fds).unwrap_or_default(),
+            peak_sockets: peak(|sample| sample.sockets).unwrap_or_default(),
+        }
+    }
+}
+
+/// Periodically samples the resource usage of a process, until it's stopped or the process exits.
+pub struct ResourceSampler {
+    samples: Arc<Mutex<Vec<ResourceSample>>>,
+    task: JoinHandle<()>,
+}
+
+impl ResourceSampler {
+    pub fn start(pid: u32, period: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        let task = tokio::spawn({
+            let samples = samples.clone();
+            async move {
+                let start = Instant::now();
+                let mut interval = interval(period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+                loop {
+                    interval.tick().await;
+                    match ResourceSample::read(pid) {
+                        Ok(sample) => samples.lock().unwrap().push(ResourceSample {
+                            elapsed: start.elapsed(),
+                            ..sample
+                        }),
+                        // The process is gone.
+                        Err(_) => break,
+                    }
+                }
+            }
+        });
+
+        Self { samples, task }
+    }
+
+    /// The samples collected so far.
+    pub fn samples(&self) -> Vec<ResourceSample> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    /// Stops the sampling and returns the collected samples.
+    pub fn stop(self) -> Vec<ResourceSample> {
+        self.task.abort();
+        self.samples()
+    }
+}
+
+impl Drop for ResourceSampler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn cpu_time_from_stat() {
+        let stat = "42 (rippled (main)) S 1 42 42 0 -1 4194560 100 0 0 0 250 150 0 0 20 0 12 0";
+        // SAFETY: `sysconf` has no memory safety requirements.
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+
+        assert_eq!(
+            read_cpu_time(stat).unwrap(),
+            Duration::from_secs_f64(400.0 / ticks_per_sec)
+        );
+        assert!(read_cpu_time("42 (rippled) S 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn sample_own_process() {
+        let _listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let sampler = ResourceSampler::start(std::process::id(), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let samples = sampler.stop();
+
+        assert!(samples.len() > 1);
+        assert!(samples
+            .windows(2)
+            .all(|pair| pair[0].elapsed < pair[1].elapsed));
+
+        let sample = samples.last().unwrap();
+        assert!(sample.rss > 0);
+        assert!(sample.threads > 0);
+        assert!(sample.sockets >= 1);
+        assert!(sample.fds >= sample.sockets);
+
+        let stats = ResourceStats::new("own", &samples);
+        assert_eq!(stats.samples, samples.len());
+        assert!(stats.peak_sockets >= 1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Generation of the data loaded by stateful nodes: a [TestNet] funds a few accounts, after which
+//! its nodes' directories are kept as the snapshots of [NodeType::Stateful](crate::setup::node::NodeType::Stateful) nodes.
+
+use std::{fs, io, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use fs_extra::dir;
+use serde_json::json;
+use tokio::time::{sleep, timeout};
+
+use crate::{
+    setup::{
+        build_ripple_work_path,
+        constants::{NODE_CONFIG_FILE, RIPPLED_CONFIG, STATEFUL_NODES_COUNT, STATEFUL_NODES_DIR},
+        testnet::TestNet,
+    },
+    tools::{
+        constants::{GENESIS_ACCOUNT, GENESIS_SEED, TEST_ACCOUNT},
+        rpc::{
+            get_ledger_info, sign_and_submit, wait_for_account_data,
+            wait_for_validated_transaction, wallet_propose,
+        },
+    },
+};
+
+/// The number of payments submitted at once.
+///
+/// Rippled queues the transactions above the open ledger's minimum (5 by default) and the queue
+/// only holds 10 transactions per account, so the payments are submitted in batches.
+const PAYMENTS_PER_BATCH: usize = 5;
+
+/// The parameters of the generated snapshot.
+#[derive(Debug, Clone)]
+pub struct SnapshotParams {
+    /// The number of accounts funded by the genesis account, at least one; the first one is
+    /// always [TEST_ACCOUNT], the others are derived from [account_passphrase].
+    pub accounts: usize,
+    /// The balance each account is funded with, in drops.
+    pub balance: u64,
+    /// The minimum index of the last validated ledger in the snapshot.
+    pub ledgers: u32,
+    /// Timeout of each of the steps, e.g. waiting for the funding to be validated.
+    pub step_timeout: Duration,
+}
+
+impl Default for SnapshotParams {
+    fn default() -> Self {
+        Self {
+            accounts: 1,
+            // 5000 XRP, as expected by the stateful tests.
+            balance: 5_000_000_000,
+            ledgers: 10,
+            step_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// The passphrase of the funded account with the given index (other than the first one), to be
+/// passed to [wallet_propose] to obtain the account's keys.
+pub fn account_passphrase(idx: usize) -> String {
+    format!("ziggurat-account-{idx}")
+}
+
+/// Runs a testnet, funds the accounts and replaces the stateful nodes' data with the testnet's.
+///
+/// Returns the funded accounts.
+pub async fn generate_stateful_snapshots(params: &SnapshotParams) -> Result<Vec<String>> {
+    if params.accounts == 0 {
+        bail!("at least one account has to be funded, the stateful tests rely on {TEST_ACCOUNT}");
+    }
+
+    let mut testnet = TestNet::new()?;
+    testnet.start().await?;
+    testnet.wait_ready(params.step_timeout).await?;
+    let rpc_url = testnet.node(0).rpc_url();
+
+    let genesis = wait_for_account_data(&rpc_url, GENESIS_ACCOUNT, params.step_timeout)
+        .await
+        .context("the testnet didn't validate the genesis ledger")?;
+
+    let mut accounts = vec![TEST_ACCOUNT.to_owned()];
+    for idx in 1..params.accounts {
+        let wallet = wallet_propose(&rpc_url, &account_passphrase(idx)).await?;
+        accounts.push(wallet.result.account_id);
+    }
+
+    let mut sequence = genesis.result.account_data.sequence;
+    for batch in accounts.chunks(PAYMENTS_PER_BATCH) {
+        let mut hashes = Vec::with_capacity(batch.len());
+        for account in batch {
+            let payment = json!({
+                "TransactionType": "Payment",
+                "Account": GENESIS_ACCOUNT,
+                "Destination": account,
+                "Amount": params.balance.to_string(),
+                "Sequence": sequence,
+            });
+            let response = sign_and_submit(&rpc_url, GENESIS_SEED, payment).await?;
+
+            let result = response.result.engine_result;
+            if result != "tesSUCCESS" && result != "terQUEUED" {
+                bail!("funding {account} failed with {result}");
+            }
+            hashes.push(response.result.tx_json.hash);
+            sequence += 1;
+        }
+
+        for hash in hashes {
+            wait_for_validated_transaction(&rpc_url, &hash, params.step_timeout)
+                .await
+                .map_err(|_| anyhow!("the payment {hash} wasn't validated"))?;
+        }
+    }
+
+    timeout(params.step_timeout, async {
+        loop {
+            if let Ok(info) = get_ledger_info(&rpc_url).await {
+                if info.result.ledger.ledger_index.parse().unwrap_or(0) >= params.ledgers {
+                    break;
+                }
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("the testnet didn't reach {} ledgers", params.ledgers))?;
+
+    // The nodes are stopped first, so their databases are consistent.
+    for idx in 0..STATEFUL_NODES_COUNT {
+        testnet.stop_node(idx).await?;
+    }
+
+    // The snapshots are copied next to the old ones first, so a failed copy leaves those intact.
+    let work_path = build_ripple_work_path()?;
+    let stateful_path = work_path.join(STATEFUL_NODES_DIR);
+    let new_path = work_path.join(format!("{STATEFUL_NODES_DIR}.new"));
+    let old_path = work_path.join(format!("{STATEFUL_NODES_DIR}.old"));
+    ignore_not_found(fs::remove_dir_all(&new_path))?;
+    for (idx, node) in testnet.nodes() {
+        let target = new_path.join(idx.to_string());
+        fs::create_dir_all(&target)?;
+
+        let mut copy_options = dir::CopyOptions::new();
+        copy_options.content_only = true;
+        dir::copy(node.path(), &target, &copy_options)?;
+
+        // The stateful nodes get configured on each start.
+        ignore_not_found(fs::remove_file(target.join(RIPPLED_CONFIG)))?;
+        ignore_not_found(fs::remove_file(target.join(NODE_CONFIG_FILE)))?;
+    }
+
+    ignore_not_found(fs::remove_dir_all(&old_path))?;
+    ignore_not_found(fs::rename(&stateful_path, &old_path))?;
+    fs::rename(&new_path, &stateful_path)?;
+    ignore_not_found(fs::remove_dir_all(&old_path))?;
+
+    Ok(accounts)
+}
+
+// The removed files may not exist, so we let that error through.
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
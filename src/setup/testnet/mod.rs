@@ -1,23 +1,42 @@
-//! Utilities for setting up a testnet consisting of 3 nodes.
+//! Utilities for setting up a small testnet of validators, optionally with trackers, connected
+//! in a configurable [Topology]. The links between the nodes can be cut or degraded with
+//! [fault injection](TestNet::with_fault_injection).
 
 use std::{
+    collections::BTreeMap,
     fmt,
     fmt::Write,
     fs, io,
-    net::{IpAddr, SocketAddr},
+    net::SocketAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::setup::{
-    build_ripple_work_path,
-    constants::{
-        DEFAULT_PORT, STATEFUL_NODES_COUNT, TESTNET_NETWORK_ID, VALIDATORS_FILE_NAME, VALIDATOR_IPS,
+use tempfile::TempDir;
+use tokio::{sync::watch, time::Instant};
+
+use crate::{
+    setup::{
+        addrs::NodeAddrs,
+        constants::{STATEFUL_NODES_COUNT, TESTNET_NETWORK_ID, VALIDATORS_FILE_NAME},
+        node::{ChildExitCode, Node, NodeBuilder, NodeType},
+        testnet::{
+            proxy::{LinkConditions, LinkProxy},
+            ready::{
+                lagging_nodes, ReadyExpectation, TestNetNotReady, TRACKER_READY_STATE,
+                VALIDATOR_READY_STATE,
+            },
+            topology::Topology,
+            validator_keys::ValidatorKeys,
+        },
     },
-    node::{Node, NodeBuilder, NodeType},
+    tools::rpc::get_server_info,
 };
 
-/// Testnet's directory for nodes' configs.
-const TESTNET_DIR: &str = "testnet";
+pub mod proxy;
+pub mod ready;
+pub mod topology;
+pub mod validator_keys;
 
 const VALIDATOR_KEYS: [&str; STATEFUL_NODES_COUNT] = [
     "nHUSqn9qjEF7JJkVqvY7BFLMKdqP5KLLEjo5oB4QH43ADDndRawB",
@@ -25,6 +44,9 @@ const VALIDATOR_KEYS: [&str; STATEFUL_NODES_COUNT] = [
     "nHUuYdS49cPfRmCXPTwu7MVVFZFFmfG7y5sRttirVMhwuD7xStQp",
 ];
 
+/// How often [TestNet::wait_ready] polls the nodes.
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Get validator token.
 pub fn get_validator_token(stateful_node_idx: usize) -> String {
     match stateful_node_idx {
@@ -38,77 +60,400 @@ pub fn get_validator_token(stateful_node_idx: usize) -> String {
 /// A struct to conveniently start and stop a small testnet.
 pub struct TestNet {
     // Setup information for each node. Used for writing configuration.
-    pub setups: [NodeSetup; VALIDATOR_KEYS.len()],
-    // Running nodes. Used to stop the testnet.
-    pub running: Vec<Node>,
+    pub setups: Vec<NodeSetup>,
+    // The started nodes, by the indices of their setups; none for the synthetic slots.
+    nodes: Vec<Option<Node>>,
+    // Which nodes are peers; the indices are those of the setups.
+    topology: Topology,
+    // Whether the nodes are connected through proxies.
+    fault_injection: bool,
+    // The links between the nodes, by their indices (lower first), when using fault injection.
+    links: BTreeMap<(usize, usize), Link>,
     // Sets whether to log the node's output to Ziggurat's output stream.
     use_stdout: bool,
     // Path under which all nodes will be built
     path: PathBuf,
+    // Removes the testnet's temporary directory when the testnet is dropped.
+    _temp_dir: Option<TempDir>,
 }
 
 impl TestNet {
-    /// Creates a new TestNet (without starting it).
+    /// Creates a new TestNet (without starting it) of the 3 validators whose data the stateful
+    /// nodes are started with.
+    ///
+    /// The nodes are built in a temporary directory and listen on unique addresses,
+    /// so multiple testnets can run at the same time.
     pub fn new() -> io::Result<Self> {
+        let setups = VALIDATOR_KEYS
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                Ok(NodeSetup::new(
+                    NodeAddrs::allocate()?,
+                    Some(key.to_string()),
+                    Some(get_validator_token(i)),
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Self::with_setups(setups)
+    }
+
+    /// Creates a new TestNet (without starting it) of `count` validators, whose keys are
+    /// generated, see [ValidatorKeys::indexed].
+    pub fn with_validators(count: usize) -> io::Result<Self> {
+        let setups = (0..count as u32)
+            .map(|i| {
+                let keys = ValidatorKeys::indexed(i);
+                Ok(NodeSetup::new(
+                    NodeAddrs::allocate()?,
+                    Some(keys.public_key()),
+                    Some(keys.token()),
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Self::with_setups(setups)
+    }
+
+    /// Adds `count` trackers: nodes which follow the validators' consensus without validating.
+    ///
+    /// The trackers are started after the validators, so their indices follow the validators'.
+    pub fn with_trackers(mut self, count: usize) -> io::Result<Self> {
+        for _ in 0..count {
+            let idx = self.setups.iter().filter(|setup| !setup.synthetic).count();
+            self.setups
+                .insert(idx, NodeSetup::new(NodeAddrs::allocate()?, None, None));
+        }
+        Ok(self)
+    }
+
+    /// Reserves `count` slots in the topology for synthetic nodes, which take the last indices.
+    ///
+    /// The testnet doesn't start them: a synthetic node is expected to listen on the slot's
+    /// [peer address](NodeAddrs::peer_addr), see [addrs](Self::addrs), where its rippled peers
+    /// connect to it.
+    pub fn with_synthetic_relays(mut self, count: usize) -> io::Result<Self> {
+        for _ in 0..count {
+            let mut setup = NodeSetup::new(NodeAddrs::allocate()?, None, None);
+            setup.synthetic = true;
+            self.setups.push(setup);
+        }
+        Ok(self)
+    }
+
+    /// Connects the nodes in the given topology instead of the full mesh.
+    ///
+    /// Outside of the full mesh, each node's peer limit is set to its number of peers and the
+    /// nodes don't share each other's addresses, so they only connect to their fixed peers.
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Routes the connections between the nodes through proxies, so the links of the started
+    /// testnet can be cut with [partition](Self::partition) or degraded with
+    /// [degrade_link](Self::degrade_link).
+    ///
+    /// The nodes don't share each other's addresses, so they only connect through the proxies.
+    pub fn with_fault_injection(mut self) -> Self {
+        self.fault_injection = true;
+        self
+    }
+
+    /// Cuts the links between the two groups of nodes, e.g. `partition(&[0, 1], &[2])`; the
+    /// links within each group are kept.
+    pub fn partition(&self, group_a: &[usize], group_b: &[usize]) {
+        assert!(self.fault_injection, "fault injection isn't enabled");
+
+        for &a in group_a {
+            for &b in group_b {
+                if let Some(link) = self.link(a, b) {
+                    link.conditions
+                        .send_modify(|conditions| conditions.cut = true);
+                }
+            }
+        }
+    }
+
+    /// Restores all the links, removing both the partitions and the degradations.
+    pub fn heal(&self) {
+        for link in self.links.values() {
+            link.conditions.send_replace(LinkConditions::default());
+        }
+    }
+
+    /// Adds latency and packet loss to the link between two nodes, see [LinkConditions].
+    pub fn degrade_link(&self, a: usize, b: usize, latency: Duration, loss: f64) {
+        assert!(self.fault_injection, "fault injection isn't enabled");
+        assert!((0.0..=1.0).contains(&loss), "the loss must be in 0.0..=1.0");
+
+        let link = self
+            .link(a, b)
+            .unwrap_or_else(|| panic!("nodes {a} and {b} aren't peers"));
+        link.conditions.send_modify(|conditions| {
+            conditions.latency = latency;
+            conditions.loss = loss;
+        });
+    }
+
+    /// The current conditions of the link between two nodes, if they're peers.
+    pub fn link_conditions(&self, a: usize, b: usize) -> Option<LinkConditions> {
+        self.link(a, b).map(|link| *link.conditions.borrow())
+    }
+
+    /// The addresses of the node (or the synthetic relay) with the given index.
+    pub fn addrs(&self, idx: usize) -> NodeAddrs {
+        self.setups[idx].addrs
+    }
+
+    fn with_setups(setups: Vec<NodeSetup>) -> io::Result<Self> {
+        let temp_dir = TempDir::new()?;
+
         Ok(Self {
-            setups: [
-                NodeSetup::new(
-                    VALIDATOR_IPS[0].parse().unwrap(),
-                    VALIDATOR_KEYS[0].into(),
-                    get_validator_token(0),
-                ),
-                NodeSetup::new(
-                    VALIDATOR_IPS[1].parse().unwrap(),
-                    VALIDATOR_KEYS[1].into(),
-                    get_validator_token(1),
-                ),
-                NodeSetup::new(
-                    VALIDATOR_IPS[2].parse().unwrap(),
-                    VALIDATOR_KEYS[2].into(),
-                    get_validator_token(2),
-                ),
-            ],
-            running: vec![],
+            setups,
+            nodes: vec![],
+            topology: Topology::default(),
+            fault_injection: false,
+            links: BTreeMap::new(),
             use_stdout: false,
-            path: build_testnet_path()?,
+            path: temp_dir.path().to_path_buf(),
+            _temp_dir: Some(temp_dir),
         })
     }
 
     /// Starts a testnet.
+    ///
+    /// The synthetic relays are skipped, see [node](Self::node). Fails if the [Topology]
+    /// can't be built for the testnet's nodes.
     pub async fn start(&mut self) -> anyhow::Result<()> {
+        self.topology.validate(self.setups.len())?;
+        let neighbours = self.topology.neighbours(self.setups.len());
         self.cleanup().await?;
         let validators_contents = self.build_validators_file_contents().await?;
 
-        for (i, setup) in self.setups.iter().enumerate() {
-            let node = self
-                .start_node(&i.to_string(), setup, &validators_contents)
-                .await?;
-            self.running.push(node);
+        for (i, neighbours) in neighbours.iter().enumerate() {
+            let node = if self.setups[i].synthetic {
+                None
+            } else {
+                let peers = self.connect_peers(i, neighbours).await?;
+                Some(self.start_node(i, peers, &validators_contents).await?)
+            };
+            self.nodes.push(node);
         }
         Ok(())
     }
 
     /// Stops the testnet.
     pub async fn stop(mut self) -> anyhow::Result<()> {
-        self.running.iter_mut().for_each(|node| {
-            if let Err(e) = node.stop() {
+        for node in self.nodes.iter_mut().flatten() {
+            if let Err(e) = node.stop().await {
                 eprintln!("Unable to stop node: {e:?}");
             }
-        });
+        }
         Ok(())
     }
 
-    // Creates `validators.txt` file with keys of all nodes.
+    /// The started node with the given index, which is also the index of its setup.
+    ///
+    /// Panics if the index is that of a synthetic slot or the node wasn't started.
+    pub fn node(&self, idx: usize) -> &Node {
+        self.nodes
+            .get(idx)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| panic!("node {idx} wasn't started by the testnet"))
+    }
+
+    /// The started nodes along with their indices, including the stopped ones.
+    pub fn nodes(&self) -> impl Iterator<Item = (usize, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, node)| Some((idx, node.as_ref()?)))
+    }
+
+    /// Stops a single node gracefully, while the rest of the testnet keeps running.
+    ///
+    /// The node keeps its data and can be started again with [restart_node](Self::restart_node).
+    pub async fn stop_node(&mut self, idx: usize) -> io::Result<ChildExitCode> {
+        self.node_mut(idx).stop().await
+    }
+
+    /// Kills a single node without letting it shut down gracefully, see [Node::kill].
+    pub async fn kill_node(&mut self, idx: usize) -> io::Result<()> {
+        self.node_mut(idx).kill().await
+    }
+
+    /// Restarts a single node, stopping it first if it's still running; the node keeps its data.
+    /// A crashed node has to be acknowledged with [Node::check_crash] first, see [Node::restart].
+    ///
+    /// The node's `validators.txt` is rewritten beforehand, so the node trusts the validators
+    /// [added](Self::add_node) since it was started.
+    pub async fn restart_node(&mut self, idx: usize) -> anyhow::Result<()> {
+        let validators_contents = self.build_validators_file_contents().await?;
+        let node = self.node_mut(idx);
+        write_validators_file(node.path(), &validators_contents).await?;
+        node.restart().await
+    }
+
+    /// Restarts a single node with another `rippled` binary, keeping its data, e.g. to run
+    /// a rolling upgrade or a network of mixed versions.
+    pub async fn swap_binary(&mut self, idx: usize, rippled: &Path) -> anyhow::Result<()> {
+        self.node_mut(idx).restart_with_binary(rippled).await
+    }
+
+    /// Starts a new node while the testnet is running and returns its index.
+    ///
+    /// The node is a validator with generated keys (see [ValidatorKeys::indexed]) if `validator`
+    /// is set, a tracker otherwise. It's peered with all the other nodes, so only the full mesh
+    /// topology is supported. The nodes started before it only trust the new validator once
+    /// they're [restarted](Self::restart_node).
+    pub async fn add_node(&mut self, validator: bool) -> anyhow::Result<usize> {
+        assert_eq!(
+            self.topology,
+            Topology::FullMesh,
+            "nodes can only be added to a full mesh"
+        );
+
+        let idx = self.setups.len();
+        assert_eq!(self.nodes.len(), idx, "the testnet has to be started first");
+        let setup = if validator {
+            let keys = ValidatorKeys::indexed(idx as u32);
+            NodeSetup::new(
+                NodeAddrs::allocate()?,
+                Some(keys.public_key()),
+                Some(keys.token()),
+            )
+        } else {
+            NodeSetup::new(NodeAddrs::allocate()?, None, None)
+        };
+        self.setups.push(setup);
+
+        let neighbours = self.topology.neighbours(self.setups.len());
+        let validators_contents = self.build_validators_file_contents().await?;
+        let peers = self.connect_peers(idx, &neighbours[idx]).await?;
+        let node = self.start_node(idx, peers, &validators_contents).await?;
+        self.nodes.push(Some(node));
+
+        Ok(idx)
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node {
+        self.nodes
+            .get_mut(idx)
+            .and_then(Option::as_mut)
+            .unwrap_or_else(|| panic!("node {idx} wasn't started by the testnet"))
+    }
+
+    /// Waits until every running node reports the expected state (`proposing` for validators,
+    /// `full` for trackers), is connected to all of its running rippled peers in the topology
+    /// and validated the same ledger as the others.
+    ///
+    /// The nodes stopped on purpose are skipped, while the crashed ones are reported, as are
+    /// the ones whose `server_info` requests time out. Returns the nodes which still weren't ready when the `timeout` elapsed.
+    pub async fn wait_ready(&self, timeout: Duration) -> Result<(), TestNetNotReady> {
+        let neighbours = self.topology.neighbours(self.setups.len());
+        let is_running = |idx: usize| match self.nodes.get(idx) {
+            Some(Some(node)) => node.exit_status().is_none() || node.has_crashed(),
+            _ => false,
+        };
+
+        let expectations = self
+            .nodes()
+            .filter(|(idx, _)| is_running(*idx))
+            .map(|(idx, node)| {
+                let expected = ReadyExpectation {
+                    idx,
+                    addr: node.addr(),
+                    state: if self.setups[idx].validator_token.is_some() {
+                        VALIDATOR_READY_STATE
+                    } else {
+                        TRACKER_READY_STATE
+                    },
+                    peers: neighbours[idx]
+                        .iter()
+                        .filter(|&&peer| is_running(peer))
+                        .count() as u32,
+                };
+                (expected, node)
+            })
+            .collect::<Vec<_>>();
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut statuses = Vec::with_capacity(expectations.len());
+            for (expected, node) in &expectations {
+                // A hanging node must not stall the wait past the deadline, but the last poll
+                // still gets a chance to report why the nodes aren't ready.
+                let request_deadline = deadline.max(Instant::now() + READY_POLL_INTERVAL);
+                let url = node.rpc_url();
+                let info =
+                    match tokio::time::timeout_at(request_deadline, get_server_info(&url)).await {
+                        Ok(Ok(response)) => Ok(response.result.info),
+                        Ok(Err(e)) => Err(e.to_string()),
+                        Err(_) => Err("the request timed out".to_owned()),
+                    };
+                statuses.push((expected.clone(), info));
+            }
+
+            let lagging = lagging_nodes(&statuses);
+            if lagging.is_empty() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(TestNetNotReady { lagging });
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+
+    // Returns the addresses the node connects to its peers on, which are those of the proxies
+    // with fault injection.
+    async fn connect_peers(&mut self, idx: usize, peers: &[usize]) -> io::Result<Vec<SocketAddr>> {
+        if !self.fault_injection {
+            return Ok(peers
+                .iter()
+                .map(|&peer| self.setups[peer].addrs.peer_addr())
+                .collect());
+        }
+
+        let mut addrs = Vec::with_capacity(peers.len());
+        for &peer in peers {
+            let target = self.setups[peer].addrs;
+            let link = self
+                .links
+                .entry((idx.min(peer), idx.max(peer)))
+                .or_default();
+
+            // The proxy stands in for the peer, so it listens on the peer's IP.
+            let proxy = LinkProxy::start(
+                target.ip,
+                self.setups[idx].addrs.ip,
+                target.peer_addr(),
+                link.conditions.subscribe(),
+            )
+            .await?;
+            addrs.push(proxy.addr());
+            link.proxies.push(proxy);
+        }
+        Ok(addrs)
+    }
+
+    fn link(&self, a: usize, b: usize) -> Option<&Link> {
+        self.links.get(&(a.min(b), a.max(b)))
+    }
+
+    // Creates `validators.txt` file with keys of all validators.
     async fn build_validators_file_contents(&self) -> Result<String, fmt::Error> {
         let mut config_str = String::new();
         writeln!(&mut config_str, "[validators]")?;
-        for n in &self.setups {
-            writeln!(&mut config_str, "{}", n.validator_key)?;
+        for key in self.setups.iter().filter_map(|n| n.validator_key.as_ref()) {
+            writeln!(&mut config_str, "{key}")?;
         }
         Ok(config_str)
     }
 
-    // Removes ~/.ziggurat/ripple/testnet directory
+    // Removes the testnet's directory
     async fn cleanup(&self) -> io::Result<()> {
         if let Err(e) = fs::remove_dir_all(&self.path) {
             // Directory may not exist, so we let that error through
@@ -119,41 +464,36 @@ impl TestNet {
         Ok(())
     }
 
-    // Starts a node in the testnet. `suffix` is used to determine a name for the node's subdirectory.
+    // Starts the node with the given index in its own subdirectory, named after the index.
     async fn start_node(
         &self,
-        suffix: &str,
-        setup: &NodeSetup,
+        idx: usize,
+        peers: Vec<SocketAddr>,
         validators_contents: &str,
     ) -> anyhow::Result<Node> {
-        let target_path = self.path.join(suffix);
+        let setup = &self.setups[idx];
+        let target_path = self.path.join(idx.to_string());
         if !target_path.exists() {
             fs::create_dir_all(&target_path)?;
         }
 
         write_validators_file(&target_path, validators_contents).await?;
-        NodeBuilder::stateless()?
-            .initial_peers(self.collect_other_peers(setup))
-            .set_addr(SocketAddr::new(setup.ip, DEFAULT_PORT))
-            .validator_token(setup.validator_token.clone())
+        let mut builder = NodeBuilder::stateless()?;
+        if self.topology != Topology::FullMesh {
+            builder = builder.max_peers(peers.len());
+        }
+        if self.topology != Topology::FullMesh || self.fault_injection {
+            builder = builder.peer_private(true);
+        }
+        builder = builder
+            .initial_peers(peers)
+            .addrs(setup.addrs)
             .network_id(TESTNET_NETWORK_ID)
-            .log_to_stdout(self.use_stdout)
-            .start(&target_path, NodeType::Testnet)
-            .await
-    }
-
-    // Builds a list of peers for the node. Each node has two peers (the other nodes in the testnet).
-    fn collect_other_peers(&self, setup: &NodeSetup) -> Vec<SocketAddr> {
-        self.setups
-            .iter()
-            .filter_map(|peer| {
-                if peer.ip != setup.ip {
-                    Some(SocketAddr::new(peer.ip, DEFAULT_PORT))
-                } else {
-                    None
-                }
-            })
-            .collect()
+            .log_to_stdout(self.use_stdout);
+        if let Some(token) = &setup.validator_token {
+            builder = builder.validator_token(token.clone());
+        }
+        builder.start(&target_path, NodeType::Testnet).await
     }
 }
 
@@ -163,27 +503,44 @@ async fn write_validators_file(path: &Path, contents: &str) -> io::Result<()> {
     fs::write(path, contents)
 }
 
-// Convenience function to build testnet's path.
-fn build_testnet_path() -> io::Result<PathBuf> {
-    Ok(build_ripple_work_path()?.join(TESTNET_DIR))
+// A link between two nodes, with a proxy for each direction the nodes connect in.
+struct Link {
+    conditions: watch::Sender<LinkConditions>,
+    proxies: Vec<LinkProxy>,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            conditions: watch::channel(LinkConditions::default()).0,
+            proxies: Vec::new(),
+        }
+    }
 }
 
 // Describes each node's setup.
 pub struct NodeSetup {
-    // The node's ip address.
-    ip: IpAddr,
-    // The node's validator key to be put in the validators.txt file.
-    validator_key: String,
-    // The node's validator token to be put in the rippled.cfg file.
-    pub validator_token: String,
+    // The node's addresses.
+    addrs: NodeAddrs,
+    // The node's validator key to be put in the validators.txt file, none for trackers.
+    validator_key: Option<String>,
+    // The node's validator token to be put in the rippled.cfg file, none for trackers.
+    pub validator_token: Option<String>,
+    // Whether the slot is taken by a synthetic node, which isn't started by the testnet.
+    synthetic: bool,
 }
 
 impl NodeSetup {
-    fn new(ip: IpAddr, validator_key: String, validator_token: String) -> Self {
+    fn new(
+        addrs: NodeAddrs,
+        validator_key: Option<String>,
+        validator_token: Option<String>,
+    ) -> Self {
         Self {
-            ip,
+            addrs,
             validator_key,
             validator_token,
+            synthetic: false,
         }
     }
 }
@@ -192,16 +549,151 @@ impl NodeSetup {
 mod test {
     use std::time::Duration;
 
-    use crate::setup::testnet::TestNet;
+    use super::*;
+    use crate::{
+        setup::{
+            build_ripple_work_path,
+            config::NodeMetaData,
+            constants::{RIPPLE_SETUP_DIR, TESTNET_READY_TIMEOUT},
+        },
+        tools::{
+            config::SynthNodeCfg,
+            constants::GENESIS_ACCOUNT,
+            rpc::{get_ledger_info, wait_for_account_data},
+            synth_node::SyntheticNode,
+        },
+        wait_until,
+    };
 
-    #[ignore = "used to set up a small testnet that can be used to procure node state"]
+    #[ignore = "used to set up a small testnet that can be inspected manually"]
     #[tokio::test]
     async fn run_testnet() {
         let mut testnet = TestNet::new().unwrap();
         testnet.use_stdout = false;
         testnet.start().await.unwrap();
-        // TODO wait for nodes to start and verify state. At the moment the test is successful it it doesn't panic.
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
         tokio::time::sleep(Duration::from_secs(10 * 60)).await;
         testnet.stop().await.unwrap();
     }
+
+    #[ignore = "use only when changing src/setup files"]
+    #[tokio::test]
+    async fn generated_validators_reach_consensus() {
+        let mut testnet = TestNet::with_validators(5)
+            .unwrap()
+            .with_trackers(1)
+            .unwrap();
+        assert_eq!(
+            testnet
+                .build_validators_file_contents()
+                .await
+                .unwrap()
+                .lines()
+                .count(),
+            6
+        );
+
+        testnet.start().await.unwrap();
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
+        // The tracker only knows the genesis account once the validators validate a ledger.
+        let tracker = testnet.node(5);
+        wait_for_account_data(&tracker.rpc_url(), GENESIS_ACCOUNT, TESTNET_READY_TIMEOUT)
+            .await
+            .unwrap();
+        testnet.stop().await.unwrap();
+    }
+
+    #[ignore = "use only when changing src/setup files"]
+    #[tokio::test]
+    async fn synthetic_relay_in_star_topology() {
+        // All the validators are only connected to the synthetic node in the center.
+        let mut testnet = TestNet::with_validators(3)
+            .unwrap()
+            .with_synthetic_relays(1)
+            .unwrap()
+            .topology(Topology::Star { center: 3 });
+
+        let relay_addrs = testnet.addrs(3);
+        let mut config = SynthNodeCfg::default();
+        config.pea2pea_config.listener_ip = Some(relay_addrs.ip);
+        config.pea2pea_config.desired_listening_port = Some(relay_addrs.peer_port);
+        let synth_node = SyntheticNode::new(&config).await;
+        synth_node.start_listening().await.unwrap();
+
+        testnet.start().await.unwrap();
+        assert_eq!(testnet.nodes().count(), 3);
+        wait_until!(TESTNET_READY_TIMEOUT, synth_node.num_connected() == 3);
+
+        synth_node.shut_down().await;
+        testnet.stop().await.unwrap();
+    }
+
+    async fn validated_ledger_index(node: &Node) -> u32 {
+        get_ledger_info(&node.rpc_url())
+            .await
+            .map(|info| info.result.ledger.ledger_index.parse().unwrap())
+            .unwrap_or(0)
+    }
+
+    #[ignore = "use only when changing src/setup files"]
+    #[tokio::test]
+    async fn partitioned_validator_catches_up_after_healing() {
+        // The quorum of 5 validators is 4, so only the majority keeps validating.
+        let mut testnet = TestNet::with_validators(5).unwrap().with_fault_injection();
+        testnet.start().await.unwrap();
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
+
+        testnet.partition(&[0], &[1, 2, 3, 4]);
+        assert!(testnet.link_conditions(0, 1).unwrap().cut);
+        assert!(!testnet.link_conditions(1, 2).unwrap().cut);
+
+        let isolated = validated_ledger_index(testnet.node(0)).await;
+        let majority = validated_ledger_index(testnet.node(1)).await;
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        assert!(validated_ledger_index(testnet.node(0)).await <= isolated + 1);
+        let advanced = validated_ledger_index(testnet.node(1)).await;
+        assert!(advanced > majority);
+
+        testnet.heal();
+        wait_until!(
+            TESTNET_READY_TIMEOUT,
+            validated_ledger_index(testnet.node(0)).await >= advanced,
+            Duration::from_secs(1)
+        );
+        testnet.stop().await.unwrap();
+    }
+
+    #[ignore = "use only when changing src/setup files"]
+    #[tokio::test]
+    async fn validator_churn_and_binary_swap() {
+        let mut testnet = TestNet::with_validators(4).unwrap();
+        testnet.start().await.unwrap();
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
+
+        // The quorum of 4 validators is 3, so the rest keep validating without one of them.
+        testnet.stop_node(0).await.unwrap();
+        let validated = validated_ledger_index(testnet.node(1)).await;
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
+        wait_until!(
+            TESTNET_READY_TIMEOUT,
+            validated_ledger_index(testnet.node(1)).await > validated,
+            Duration::from_secs(1)
+        );
+        testnet.restart_node(0).await.unwrap();
+
+        // A killed node isn't reported as crashed and restarts on its data.
+        testnet.kill_node(1).await.unwrap();
+        testnet.restart_node(1).await.unwrap();
+
+        let tracker = testnet.add_node(false).await.unwrap();
+        assert_eq!(tracker, 4);
+
+        // Swapping in the same binary still restarts the node through the given path.
+        let meta =
+            NodeMetaData::new(build_ripple_work_path().unwrap().join(RIPPLE_SETUP_DIR)).unwrap();
+        testnet.swap_binary(2, &meta.rippled_path()).await.unwrap();
+
+        testnet.wait_ready(TESTNET_READY_TIMEOUT).await.unwrap();
+        testnet.stop().await.unwrap();
+    }
 }
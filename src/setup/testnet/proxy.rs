@@ -0,0 +1,276 @@
+//! A userspace TCP proxy placed between two [TestNet](super::TestNet) nodes, so the link between
+//! them can be cut or degraded without root privileges or `tc`.
+
+use std::{
+    future::pending,
+    io,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use rand::Rng;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpSocket, TcpStream,
+    },
+    sync::{mpsc, watch},
+    task::{JoinHandle, JoinSet},
+    time::{sleep_until, Instant},
+};
+use tracing::*;
+
+use crate::setup::addrs::allocate_port;
+
+/// The extra delay of the data whose packet is "lost", standing for TCP's retransmission
+/// timeout; the data itself can't be dropped without corrupting the stream.
+pub const RETRANSMISSION_DELAY: Duration = Duration::from_millis(200);
+
+/// The number of chunks of data in flight in each direction of a connection.
+const CHUNK_QUEUE_DEPTH: usize = 1024;
+
+/// The conditions of a link between two nodes, applied in both directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkConditions {
+    /// Whether the link is cut: its connections are closed and new ones are refused.
+    pub cut: bool,
+    /// The delay added to the data.
+    pub latency: Duration,
+    /// The probability (`0.0..=1.0`) of a chunk of data being delayed by a further
+    /// [RETRANSMISSION_DELAY], as if its packet got lost.
+    pub loss: f64,
+}
+
+/// Forwards the connections made to its address to the target, subject to the link's conditions.
+pub struct LinkProxy {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl LinkProxy {
+    /// Starts the proxy on a newly allocated port of `listen_ip`. The connections to the `target` are made
+    /// from `source_ip`, so the target sees the actual peer's address.
+    pub async fn start(
+        listen_ip: IpAddr,
+        source_ip: IpAddr,
+        target: SocketAddr,
+        conditions: watch::Receiver<LinkConditions>,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind((listen_ip, allocate_port(listen_ip)?)).await?;
+        let addr = listener.local_addr()?;
+
+        let task = tokio::spawn(async move {
+            // The connections are aborted along with the proxy, when the set is dropped.
+            let mut connections = JoinSet::new();
+            loop {
+                let inbound = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            debug!("proxy to {target} failed to accept: {e}");
+                            continue;
+                        }
+                    },
+                    // Cleans up the finished connections.
+                    Some(_) = connections.join_next() => continue,
+                };
+
+                if conditions.borrow().cut {
+                    continue;
+                }
+                connections.spawn(proxy_connection(
+                    inbound,
+                    source_ip,
+                    target,
+                    conditions.clone(),
+                ));
+            }
+        });
+
+        Ok(Self { addr, task })
+    }
+
+    /// The address to connect to instead of the target.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for LinkProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn proxy_connection(
+    inbound: TcpStream,
+    source_ip: IpAddr,
+    target: SocketAddr,
+    conditions: watch::Receiver<LinkConditions>,
+) {
+    let outbound = match connect_from(source_ip, target).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!("proxy failed to connect to {target}: {e}");
+            return;
+        }
+    };
+
+    let (inbound_read, inbound_write) = inbound.into_split();
+    let (outbound_read, outbound_write) = outbound.into_split();
+
+    let forwarding = {
+        let (outgoing, incoming) = (conditions.clone(), conditions.clone());
+        async move {
+            tokio::join!(
+                forward(inbound_read, outbound_write, outgoing),
+                forward(outbound_read, inbound_write, incoming),
+            )
+        }
+    };
+
+    // Dropping the halves closes the connections once the link is cut.
+    tokio::select! {
+        _ = forwarding => (),
+        _ = wait_for_cut(conditions) => (),
+    }
+}
+
+async fn connect_from(source_ip: IpAddr, target: SocketAddr) -> io::Result<TcpStream> {
+    let socket = if target.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.bind(SocketAddr::new(source_ip, 0))?;
+    socket.connect(target).await
+}
+
+async fn wait_for_cut(mut conditions: watch::Receiver<LinkConditions>) {
+    loop {
+        if conditions.borrow_and_update().cut {
+            return;
+        }
+        if conditions.changed().await.is_err() {
+            // The link can't change anymore.
+            pending::<()>().await;
+        }
+    }
+}
+
+// Forwards the data in one direction, delaying it according to the link's conditions while
+// keeping its order.
+async fn forward(
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    conditions: watch::Receiver<LinkConditions>,
+) {
+    let (tx, mut rx) = mpsc::channel::<(Instant, Vec<u8>)>(CHUNK_QUEUE_DEPTH);
+
+    let read = async move {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let len = match reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => len,
+            };
+
+            let LinkConditions { latency, loss, .. } = *conditions.borrow();
+            let mut delay = latency;
+            if loss > 0.0 && rand::thread_rng().gen_bool(loss) {
+                delay += RETRANSMISSION_DELAY;
+            }
+
+            if tx
+                .send((Instant::now() + delay, buf[..len].to_vec()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+
+    let write = async move {
+        while let Some((deliver_at, data)) = rx.recv().await {
+            sleep_until(deliver_at).await;
+            if writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    };
+
+    tokio::join!(read, write);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    async fn start_echo_server() -> SocketAddr {
+        let listener = TcpListener::bind((LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn echo(stream: &mut TcpStream, data: &[u8]) -> io::Result<Vec<u8>> {
+        stream.write_all(data).await?;
+        let mut buf = vec![0; data.len()];
+        stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    #[tokio::test]
+    async fn proxy_applies_link_conditions() {
+        let target = start_echo_server().await;
+        let (conditions, receiver) = watch::channel(LinkConditions::default());
+        let proxy = LinkProxy::start(LOCALHOST, LOCALHOST, target, receiver)
+            .await
+            .unwrap();
+
+        let mut stream = TcpStream::connect(proxy.addr()).await.unwrap();
+        assert_eq!(echo(&mut stream, b"ping").await.unwrap(), b"ping");
+
+        // The latency is added in both directions.
+        conditions.send_modify(|c| c.latency = Duration::from_millis(100));
+        let start = Instant::now();
+        assert_eq!(echo(&mut stream, b"pong").await.unwrap(), b"pong");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // Every chunk gets "lost" once in each direction.
+        conditions.send_modify(|c| {
+            c.latency = Duration::ZERO;
+            c.loss = 1.0;
+        });
+        let start = Instant::now();
+        echo(&mut stream, b"ping").await.unwrap();
+        assert!(start.elapsed() >= RETRANSMISSION_DELAY * 2);
+        conditions.send_modify(|c| c.loss = 0.0);
+
+        // Cutting the link closes the connection and refuses the new ones.
+        conditions.send_modify(|c| c.cut = true);
+        let mut buf = [0; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap_or(0), 0);
+        let mut stream = TcpStream::connect(proxy.addr()).await.unwrap();
+        assert!(echo(&mut stream, b"ping").await.is_err());
+
+        // Healing it lets the connections through again.
+        conditions.send_modify(|c| c.cut = false);
+        let mut stream = TcpStream::connect(proxy.addr()).await.unwrap();
+        assert_eq!(echo(&mut stream, b"ping").await.unwrap(), b"ping");
+    }
+}
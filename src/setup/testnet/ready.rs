@@ -0,0 +1,225 @@
+//! Checking whether the [TestNet](super::TestNet)'s nodes reached consensus.
+
+use std::{collections::HashMap, fmt, net::SocketAddr};
+
+use thiserror::Error;
+
+use crate::tools::rpc::{ServerInfoResponse, ValidatedLedgerInfo};
+
+/// The state of a validator taking part in consensus.
+pub const VALIDATOR_READY_STATE: &str = "proposing";
+/// The state of a tracker which follows the network.
+pub const TRACKER_READY_STATE: &str = "full";
+
+/// What a node is expected to report once the testnet is ready.
+#[derive(Debug, Clone)]
+pub struct ReadyExpectation {
+    /// The node's index in the testnet.
+    pub idx: usize,
+    pub addr: SocketAddr,
+    /// The expected `server_state`.
+    pub state: &'static str,
+    /// The minimum number of peers.
+    pub peers: u32,
+}
+
+/// A node which isn't ready, with the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaggingNode {
+    /// The node's index in the testnet.
+    pub idx: usize,
+    pub addr: SocketAddr,
+    pub reason: LagReason,
+}
+
+/// Why a node isn't ready.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LagReason {
+    #[error("doesn't respond to server_info: {0}")]
+    Unresponsive(String),
+
+    #[error("is {actual}, expected {expected}")]
+    State {
+        actual: String,
+        expected: &'static str,
+    },
+
+    #[error("has {actual} peers, expected at least {expected}")]
+    Peers { actual: u32, expected: u32 },
+
+    #[error("has no validated ledger")]
+    NoValidatedLedger,
+
+    #[error("validated ledger {} ({}), while most nodes validated {} ({})", .actual.seq, .actual.hash, .expected.seq, .expected.hash)]
+    Diverged {
+        actual: ValidatedLedgerInfo,
+        expected: ValidatedLedgerInfo,
+    },
+}
+
+/// The error returned when the testnet doesn't become ready in time; it lists the nodes
+/// which weren't ready at the last check.
+#[derive(Debug, Clone, Error)]
+pub struct TestNetNotReady {
+    pub lagging: Vec<LaggingNode>,
+}
+
+impl fmt::Display for TestNetNotReady {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the testnet isn't ready:")?;
+        for node in &self.lagging {
+            write!(f, "\n  node {} ({}) {}", node.idx, node.addr, node.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the nodes which don't meet the expectations yet, given their `server_info` responses.
+///
+/// The nodes agree on the validated ledger if they all report the one most of them do, so
+/// checks which happen to span a ledger's validation are just retried.
+pub fn lagging_nodes(
+    nodes: &[(ReadyExpectation, Result<ServerInfoResponse, String>)],
+) -> Vec<LaggingNode> {
+    let mut counts = HashMap::new();
+    for (_, info) in nodes {
+        if let Ok(ledger) = info.as_ref().map(|info| &info.validated_ledger) {
+            *counts.entry(ledger.clone()).or_insert(0) += 1;
+        }
+    }
+    let majority_ledger = counts
+        .into_iter()
+        .filter_map(|(ledger, count)| Some((ledger?, count)))
+        .max_by_key(|(ledger, count)| (*count, ledger.seq))
+        .map(|(ledger, _)| ledger);
+
+    let mut lagging = vec![];
+    for (expected, info) in nodes {
+        let reason = match info {
+            Err(e) => Some(LagReason::Unresponsive(e.clone())),
+            Ok(info) if info.server_state != expected.state => Some(LagReason::State {
+                actual: info.server_state.clone(),
+                expected: expected.state,
+            }),
+            Ok(info) if info.peers < expected.peers => Some(LagReason::Peers {
+                actual: info.peers,
+                expected: expected.peers,
+            }),
+            Ok(ServerInfoResponse {
+                validated_ledger: None,
+                ..
+            }) => Some(LagReason::NoValidatedLedger),
+            Ok(ServerInfoResponse {
+                validated_ledger: Some(ledger),
+                ..
+            }) => match &majority_ledger {
+                Some(majority) if majority != ledger => Some(LagReason::Diverged {
+                    actual: ledger.clone(),
+                    expected: majority.clone(),
+                }),
+                _ => None,
+            },
+        };
+
+        if let Some(reason) = reason {
+            lagging.push(LaggingNode {
+                idx: expected.idx,
+                addr: expected.addr,
+                reason,
+            });
+        }
+    }
+    lagging
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expectation(idx: usize) -> ReadyExpectation {
+        ReadyExpectation {
+            idx,
+            addr: ([127, 0, 1, idx as u8 + 1], 51235).into(),
+            state: VALIDATOR_READY_STATE,
+            peers: 2,
+        }
+    }
+
+    fn info(state: &str, peers: u32, seq: u32) -> Result<ServerInfoResponse, String> {
+        Ok(ServerInfoResponse {
+            server_state: state.into(),
+            peers,
+            validated_ledger: Some(ValidatedLedgerInfo {
+                hash: format!("HASH{seq}"),
+                seq,
+            }),
+        })
+    }
+
+    #[test]
+    fn ready_nodes() {
+        let nodes = (0..3)
+            .map(|idx| (expectation(idx), info("proposing", 2, 7)))
+            .collect::<Vec<_>>();
+        assert!(lagging_nodes(&nodes).is_empty());
+    }
+
+    #[test]
+    fn lagging_nodes_are_named() {
+        let mut nodes = (0..5)
+            .map(|idx| (expectation(idx), info("proposing", 2, 7)))
+            .collect::<Vec<_>>();
+        nodes[0].1 = Err("connection refused".into());
+        nodes[1].1 = info("syncing", 2, 7);
+        nodes[2].1 = info("proposing", 1, 7);
+        nodes[3].1 = info("proposing", 2, 6);
+
+        let lagging = lagging_nodes(&nodes);
+        let reasons = lagging
+            .iter()
+            .map(|node| (node.idx, node.reason.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            [
+                (0, LagReason::Unresponsive("connection refused".into())),
+                (
+                    1,
+                    LagReason::State {
+                        actual: "syncing".into(),
+                        expected: VALIDATOR_READY_STATE
+                    }
+                ),
+                (
+                    2,
+                    LagReason::Peers {
+                        actual: 1,
+                        expected: 2
+                    }
+                ),
+                (
+                    3,
+                    LagReason::Diverged {
+                        actual: info("", 0, 6).unwrap().validated_ledger.unwrap(),
+                        expected: info("", 0, 7).unwrap().validated_ledger.unwrap(),
+                    }
+                ),
+            ]
+        );
+
+        let error = TestNetNotReady { lagging }.to_string();
+        assert!(error.contains("node 1 (127.0.1.2:51235) is syncing, expected proposing"));
+        assert!(error.contains("node 3 (127.0.1.4:51235) validated ledger 6 (HASH6)"));
+    }
+
+    #[test]
+    fn missing_validated_ledger() {
+        let mut no_ledger = info("full", 2, 0).unwrap();
+        no_ledger.validated_ledger = None;
+        let mut expected = expectation(0);
+        expected.state = TRACKER_READY_STATE;
+
+        let lagging = lagging_nodes(&[(expected, Ok(no_ledger))]);
+        assert_eq!(lagging[0].reason, LagReason::NoValidatedLedger);
+    }
+}
@@ -0,0 +1,220 @@
+//! The topologies a [TestNet](super::TestNet)'s nodes can be connected in.
+
+use std::{collections::BTreeSet, io};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Describes which nodes of a testnet are peers, by the nodes' indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Every node is connected to every other node.
+    #[default]
+    FullMesh,
+    /// Each node is connected to the previous and the next one.
+    Line,
+    /// Like [Line](Self::Line), with the last node connected to the first one.
+    Ring,
+    /// Every node is only connected to the center node.
+    Star { center: usize },
+    /// The given (undirected) connections.
+    Edges(Vec<(usize, usize)>),
+    /// A random graph where every node has `degree` peers; the same seed results in the same
+    /// graph. The graph isn't guaranteed to be connected.
+    RandomRegular { degree: usize, seed: u64 },
+}
+
+impl Topology {
+    /// Checks that the topology can be built for `nodes` nodes.
+    pub fn validate(&self, nodes: usize) -> io::Result<()> {
+        let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+
+        match self {
+            Self::FullMesh | Self::Line | Self::Ring => Ok(()),
+            Self::Star { center } if *center >= nodes => {
+                invalid(format!("the center {center} is out of range"))
+            }
+            Self::Star { .. } => Ok(()),
+            Self::Edges(edges) => {
+                for &(a, b) in edges {
+                    if a >= nodes || b >= nodes {
+                        return invalid(format!("the edge {a}-{b} is out of range"));
+                    }
+                    if a == b {
+                        return invalid(format!("the node {a} can't be its own peer"));
+                    }
+                }
+                Ok(())
+            }
+            Self::RandomRegular { degree, .. } if *degree >= nodes => invalid(format!(
+                "the degree {degree} must be lower than the node count {nodes}"
+            )),
+            Self::RandomRegular { degree, .. } if !(nodes * degree).is_multiple_of(2) => invalid(
+                format!("either the node count {nodes} or the degree {degree} must be even"),
+            ),
+            Self::RandomRegular { .. } => Ok(()),
+        }
+    }
+
+    /// Returns the connections between `nodes` nodes, each as `(lower, higher)` index.
+    ///
+    /// Panics if the topology can't be built for the number of nodes, see [validate](Self::validate).
+    pub fn edges(&self, nodes: usize) -> BTreeSet<(usize, usize)> {
+        let edges: Vec<(usize, usize)> = match self {
+            Self::FullMesh => (0..nodes)
+                .flat_map(|a| (a + 1..nodes).map(move |b| (a, b)))
+                .collect(),
+            Self::Line => (1..nodes).map(|b| (b - 1, b)).collect(),
+            Self::Ring if nodes < 3 => return Self::Line.edges(nodes),
+            Self::Ring => (0..nodes).map(|a| (a, (a + 1) % nodes)).collect(),
+            Self::Star { center } => {
+                assert!(*center < nodes, "the center {center} is out of range");
+                (0..nodes)
+                    .filter(|node| node != center)
+                    .map(|node| (*center, node))
+                    .collect()
+            }
+            Self::Edges(edges) => edges.clone(),
+            Self::RandomRegular { degree, seed } => random_regular(nodes, *degree, *seed),
+        };
+
+        edges
+            .into_iter()
+            .map(|(a, b)| {
+                assert!(a < nodes && b < nodes, "the edge {a}-{b} is out of range");
+                assert_ne!(a, b, "a node can't be its own peer");
+                (a.min(b), a.max(b))
+            })
+            .collect()
+    }
+
+    /// Returns the peers of each of the `nodes` nodes.
+    pub fn neighbours(&self, nodes: usize) -> Vec<Vec<usize>> {
+        let mut neighbours = vec![vec![]; nodes];
+        for (a, b) in self.edges(nodes) {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+        }
+        neighbours
+    }
+}
+
+// Starts from a circulant graph, which is regular, and shuffles it with random double edge swaps
+// (`a-b, c-d` to `a-d, c-b`), which keep every node's degree.
+fn random_regular(nodes: usize, degree: usize, seed: u64) -> Vec<(usize, usize)> {
+    assert!(
+        degree < nodes,
+        "the degree must be lower than the node count"
+    );
+    assert!(
+        (nodes * degree).is_multiple_of(2),
+        "either the node count or the degree must be even"
+    );
+
+    let normalize = |a: usize, b: usize| (a.min(b), a.max(b));
+
+    let mut edges = BTreeSet::new();
+    for a in 0..nodes {
+        for offset in 1..=degree / 2 {
+            edges.insert(normalize(a, (a + offset) % nodes));
+        }
+        // An odd degree means an even node count, so each node can be joined with the opposite one.
+        if degree % 2 == 1 && a < nodes / 2 {
+            edges.insert((a, a + nodes / 2));
+        }
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut list = edges.iter().copied().collect::<Vec<_>>();
+    for _ in 0..list.len() * 10 {
+        let (i, j) = (rng.gen_range(0..list.len()), rng.gen_range(0..list.len()));
+        let ((a, b), (mut c, mut d)) = (list[i], list[j]);
+        if rng.gen() {
+            (c, d) = (d, c);
+        }
+
+        let (first, second) = (normalize(a, d), normalize(c, b));
+        if a == d || c == b || edges.contains(&first) || edges.contains(&second) {
+            continue;
+        }
+
+        edges.remove(&list[i]);
+        edges.remove(&list[j]);
+        edges.insert(first);
+        edges.insert(second);
+        (list[i], list[j]) = (first, second);
+    }
+
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrees(topology: &Topology, nodes: usize) -> Vec<usize> {
+        topology.neighbours(nodes).iter().map(Vec::len).collect()
+    }
+
+    #[test]
+    fn simple_topologies() {
+        assert_eq!(Topology::FullMesh.edges(4).len(), 6);
+        assert_eq!(degrees(&Topology::FullMesh, 4), [3, 3, 3, 3]);
+
+        assert_eq!(
+            Topology::Line.edges(4),
+            [(0, 1), (1, 2), (2, 3)].into_iter().collect()
+        );
+        assert_eq!(degrees(&Topology::Ring, 5), [2; 5]);
+        assert!(Topology::Ring.edges(5).contains(&(0, 4)));
+        assert_eq!(Topology::Ring.edges(2), Topology::Line.edges(2));
+
+        assert_eq!(
+            Topology::Star { center: 2 }.neighbours(4),
+            [vec![2], vec![2], vec![0, 1, 3], vec![2]]
+        );
+        assert_eq!(
+            Topology::Edges(vec![(1, 0), (0, 1), (2, 3)]).edges(4),
+            [(0, 1), (2, 3)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn edges_out_of_range() {
+        Topology::Edges(vec![(0, 4)]).edges(4);
+    }
+
+    #[test]
+    fn invalid_topologies() {
+        for topology in [
+            Topology::Star { center: 5 },
+            Topology::Edges(vec![(0, 5)]),
+            Topology::Edges(vec![(1, 1)]),
+            Topology::RandomRegular { degree: 5, seed: 0 },
+            Topology::RandomRegular { degree: 3, seed: 0 },
+        ] {
+            let e = topology.validate(5).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{topology:?}");
+        }
+
+        assert!(Topology::Star { center: 4 }.validate(5).is_ok());
+        assert!(Topology::RandomRegular { degree: 4, seed: 0 }
+            .validate(5)
+            .is_ok());
+    }
+
+    #[test]
+    fn random_regular_topologies() {
+        for (nodes, degree) in [(10, 3), (10, 8), (7, 4), (5, 2)] {
+            for seed in 0..5 {
+                let topology = Topology::RandomRegular { degree, seed };
+                assert_eq!(degrees(&topology, nodes), vec![degree; nodes]);
+                assert_eq!(topology.edges(nodes), topology.edges(nodes));
+            }
+        }
+
+        let edges = |seed| Topology::RandomRegular { degree: 3, seed }.edges(20);
+        assert_ne!(edges(1), edges(2));
+    }
+}
@@ -0,0 +1,248 @@
+//! Validator keys generated for the [TestNet](super::TestNet), along with their manifests and
+//! the `[validator_token]`s, as produced by rippled's `validator-keys` tool.
+//!
+//! Both the master and the ephemeral (signing) keys are secp256k1 keys.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::tools::keys::{encode_public_key, NodeKeys};
+
+/// The deterministic key set the master keys are taken from.
+const MASTER_KEY_SET: u64 = 0x7a69_6767_0001;
+/// The deterministic key set the ephemeral keys are taken from.
+const SIGNING_KEY_SET: u64 = 0x7a69_6767_0002;
+
+/// Prefix of the data signed in a manifest.
+const MANIFEST_PREFIX: &[u8] = b"MAN\x00";
+
+// The serialized field ids, in the canonical order of the fields.
+const FIELD_SEQUENCE: &[u8] = &[0x24];
+const FIELD_PUBLIC_KEY: &[u8] = &[0x71];
+const FIELD_SIGNING_PUBLIC_KEY: &[u8] = &[0x73];
+const FIELD_SIGNATURE: &[u8] = &[0x76];
+const FIELD_MASTER_SIGNATURE: &[u8] = &[0x70, 0x12];
+
+/// The keys of a single validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorKeys {
+    master: (SecretKey, PublicKey),
+    signing: (SecretKey, PublicKey),
+    /// The manifest's sequence; a higher sequence replaces the previous signing key.
+    sequence: u32,
+}
+
+/// The JSON encoded in a `[validator_token]`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ValidatorToken {
+    /// The base64-encoded manifest.
+    manifest: String,
+    /// The hex-encoded secret ephemeral key.
+    validation_secret_key: String,
+}
+
+impl ValidatorKeys {
+    /// Returns the n-th validator's keys; the same index always results in the same keys.
+    pub fn indexed(index: u32) -> Self {
+        let keypair = |set| {
+            NodeKeys::Indexed { set, index }
+                .keypair()
+                .expect("indexed keys are always valid")
+        };
+
+        Self {
+            master: keypair(MASTER_KEY_SET),
+            signing: keypair(SIGNING_KEY_SET),
+            sequence: 1,
+        }
+    }
+
+    /// Generates new random keys.
+    pub fn random() -> Self {
+        let engine = Secp256k1::new();
+        let mut rng = secp256k1::rand::thread_rng();
+
+        Self {
+            master: engine.generate_keypair(&mut rng),
+            signing: engine.generate_keypair(&mut rng),
+            sequence: 1,
+        }
+    }
+
+    /// The base58-encoded master public key, which identifies the validator in `validators.txt`.
+    pub fn public_key(&self) -> String {
+        encode_public_key(&self.master.1)
+    }
+
+    /// The base58-encoded ephemeral public key, which signs the validations.
+    pub fn signing_public_key(&self) -> String {
+        encode_public_key(&self.signing.1)
+    }
+
+    /// The serialized manifest, binding the ephemeral key to the master key.
+    pub fn manifest(&self) -> Vec<u8> {
+        let unsigned = self.unsigned_manifest();
+        let signature = sign(&self.signing.0, &unsigned);
+        let master_signature = sign(&self.master.0, &unsigned);
+
+        let mut manifest = unsigned;
+        put_blob(&mut manifest, FIELD_SIGNATURE, &signature);
+        put_blob(&mut manifest, FIELD_MASTER_SIGNATURE, &master_signature);
+        manifest
+    }
+
+    /// The contents of the `[validator_token]` section of the validator's `rippled.cfg`.
+    pub fn token(&self) -> String {
+        let token = ValidatorToken {
+            manifest: STANDARD.encode(self.manifest()),
+            validation_secret_key: hex::encode_upper(self.signing.0.secret_bytes()),
+        };
+
+        STANDARD.encode(serde_json::to_vec(&token).expect("the token is always serializable"))
+    }
+
+    // The manifest's fields covered by the signatures.
+    fn unsigned_manifest(&self) -> Vec<u8> {
+        let mut manifest = Vec::with_capacity(256);
+        manifest.extend_from_slice(FIELD_SEQUENCE);
+        manifest.extend_from_slice(&self.sequence.to_be_bytes());
+        put_blob(&mut manifest, FIELD_PUBLIC_KEY, &self.master.1.serialize());
+        put_blob(
+            &mut manifest,
+            FIELD_SIGNING_PUBLIC_KEY,
+            &self.signing.1.serialize(),
+        );
+        manifest
+    }
+}
+
+// Serializes a variable length field; the fields used in manifests are always shorter than
+// 193 bytes, so the length fits in a single byte.
+fn put_blob(buf: &mut Vec<u8>, field: &[u8], blob: &[u8]) {
+    buf.extend_from_slice(field);
+    buf.push(blob.len() as u8);
+    buf.extend_from_slice(blob);
+}
+
+// Signs the prefixed manifest the way rippled does for secp256k1 keys: the DER-encoded signature
+// of the first half of its SHA-512 hash.
+fn sign(key: &SecretKey, unsigned_manifest: &[u8]) -> Vec<u8> {
+    Secp256k1::new()
+        .sign_ecdsa(&manifest_digest(unsigned_manifest), key)
+        .serialize_der()
+        .to_vec()
+}
+
+fn manifest_digest(unsigned_manifest: &[u8]) -> Message {
+    let mut hasher = Sha512::new();
+    hasher.update(MANIFEST_PREFIX);
+    hasher.update(unsigned_manifest);
+    let hash = hasher.finalize();
+
+    Message::from_slice(&hash[..32]).expect("the digest is 32 bytes long")
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::ecdsa::Signature;
+
+    use super::*;
+
+    // Splits a manifest into its fields, assuming single-byte lengths.
+    fn fields(manifest: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut fields = vec![];
+        let mut rest = manifest;
+        while !rest.is_empty() {
+            let id_len = if rest[0] == FIELD_MASTER_SIGNATURE[0] {
+                2
+            } else {
+                1
+            };
+            let (id, tail) = rest.split_at(id_len);
+            let (value, tail) = if id == FIELD_SEQUENCE {
+                tail.split_at(4)
+            } else {
+                tail[1..].split_at(tail[0] as usize)
+            };
+            fields.push((id, value));
+            rest = tail;
+        }
+        fields
+    }
+
+    #[test]
+    fn indexed_keys_are_deterministic() {
+        assert_eq!(ValidatorKeys::indexed(3), ValidatorKeys::indexed(3));
+        assert_ne!(
+            ValidatorKeys::indexed(3).public_key(),
+            ValidatorKeys::indexed(4).public_key()
+        );
+        assert_ne!(ValidatorKeys::random(), ValidatorKeys::random());
+    }
+
+    #[test]
+    fn manifest_is_signed_by_both_keys() {
+        let keys = ValidatorKeys::indexed(0);
+        let manifest = keys.manifest();
+
+        let fields = fields(&manifest);
+        let ids = fields.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                FIELD_SEQUENCE,
+                FIELD_PUBLIC_KEY,
+                FIELD_SIGNING_PUBLIC_KEY,
+                FIELD_SIGNATURE,
+                FIELD_MASTER_SIGNATURE
+            ]
+        );
+        assert_eq!(fields[1].1, keys.master.1.serialize());
+        assert_eq!(fields[2].1, keys.signing.1.serialize());
+
+        let engine = Secp256k1::new();
+        let digest = manifest_digest(&keys.unsigned_manifest());
+        for (signature, key) in [(fields[3].1, keys.signing.1), (fields[4].1, keys.master.1)] {
+            let signature = Signature::from_der(signature).unwrap();
+            engine.verify_ecdsa(&digest, &signature, &key).unwrap();
+        }
+    }
+
+    #[test]
+    fn token_has_manifest_and_secret() {
+        let keys = ValidatorKeys::indexed(1);
+
+        let json = STANDARD.decode(keys.token()).unwrap();
+        let token: ValidatorToken = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(STANDARD.decode(token.manifest).unwrap(), keys.manifest());
+        let secret = hex::decode(token.validation_secret_key).unwrap();
+        assert_eq!(
+            SecretKey::from_slice(&secret)
+                .unwrap()
+                .public_key(&Secp256k1::new()),
+            keys.signing.1
+        );
+    }
+
+    #[test]
+    fn field_layout_matches_rippled_tokens() {
+        // A token generated by rippled's `validator-keys` tool.
+        let token = include_str!("validator_token0.txt").replace('\n', "");
+        let token: ValidatorToken =
+            serde_json::from_slice(&STANDARD.decode(token).unwrap()).unwrap();
+        let manifest = STANDARD.decode(token.manifest).unwrap();
+
+        let expected = fields(&manifest)
+            .iter()
+            .map(|(id, _)| id.to_vec())
+            .collect::<Vec<_>>();
+        let generated = fields(&ValidatorKeys::indexed(0).manifest())
+            .iter()
+            .map(|(id, _)| id.to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(generated, expected);
+    }
+}
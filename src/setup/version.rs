@@ -0,0 +1,161 @@
+//! The version of a rippled binary, as reported by `rippled --version`, so tests can branch their
+//! expectations on the release they're run against.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use anyhow::{anyhow, Context, Error, Result};
+
+/// A rippled release, e.g. `1.9.3` or `1.12.0-rc1`.
+///
+/// The versions are ordered like semantic versions: a pre-release precedes its release.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RippledVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The pre-release tag, e.g. `rc1` or `b4`.
+    pub pre_release: Option<String>,
+}
+
+impl RippledVersion {
+    /// A release version, to be compared with, e.g. `version >= RippledVersion::new(1, 10, 0)`.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre_release: None,
+        }
+    }
+
+    /// Parses the output of `rippled --version`, e.g. `rippled version 1.9.3`.
+    pub fn from_version_output(output: &str) -> Result<Self> {
+        output
+            .split_whitespace()
+            .skip_while(|word| *word != "version")
+            .nth(1)
+            .ok_or_else(|| anyhow!("no version in rippled's output: {output:?}"))?
+            .parse()
+    }
+}
+
+impl FromStr for RippledVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // The build metadata doesn't affect the version.
+        let version = s.split('+').next().unwrap_or_default();
+        let (release, pre_release) = match version.split_once('-') {
+            Some((release, pre_release)) => (release, Some(pre_release.to_owned())),
+            None => (version, None),
+        };
+
+        let mut numbers = release.split('.').map(|number| {
+            number
+                .parse::<u32>()
+                .with_context(|| format!("invalid rippled version {s:?}"))
+        });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err(anyhow!("invalid rippled version {s:?}")))
+        };
+        let version = Self {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+            pre_release,
+        };
+
+        if numbers.next().is_some() {
+            return Err(anyhow!("invalid rippled version {s:?}"));
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for RippledVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre_release) = &self.pre_release {
+            write!(f, "-{pre_release}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for RippledVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => cmp_pre_release(a, b),
+            })
+    }
+}
+
+// Compares the pre-release tags by their prefix, then by their number, so `rc2` precedes `rc10`.
+fn cmp_pre_release(a: &str, b: &str) -> Ordering {
+    fn split(tag: &str) -> (&str, Option<u64>) {
+        let prefix = tag.trim_end_matches(|c: char| c.is_ascii_digit());
+        (prefix, tag[prefix.len()..].parse().ok())
+    }
+
+    // The tags themselves break the tie of e.g. `rc1` and `rc01`, to stay consistent with `Eq`.
+    split(a).cmp(&split(b)).then_with(|| a.cmp(b))
+}
+
+impl PartialOrd for RippledVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_output() {
+        assert_eq!(
+            RippledVersion::from_version_output("rippled version 1.9.3\n").unwrap(),
+            RippledVersion::new(1, 9, 3)
+        );
+
+        let version = RippledVersion::from_version_output("rippled version 1.12.0-rc1").unwrap();
+        assert_eq!(version.pre_release.as_deref(), Some("rc1"));
+        assert_eq!(version.to_string(), "1.12.0-rc1");
+
+        assert_eq!(
+            "2.0.0+DEBUG".parse::<RippledVersion>().unwrap(),
+            RippledVersion::new(2, 0, 0)
+        );
+        assert!(RippledVersion::from_version_output("rippled").is_err());
+        assert!("1.9".parse::<RippledVersion>().is_err());
+        assert!("1.9.3.1".parse::<RippledVersion>().is_err());
+        assert!("1.x.3".parse::<RippledVersion>().is_err());
+    }
+
+    #[test]
+    fn versions_are_ordered() {
+        let versions = [
+            "1.9.3",
+            "1.9.4",
+            "1.10.0-b1",
+            "1.10.0-b2",
+            "1.10.0-rc",
+            "1.10.0-rc1",
+            "1.10.0-rc2",
+            "1.10.0-rc10",
+            "1.10.0",
+            "2.0.0",
+        ]
+        .map(|version| version.parse::<RippledVersion>().unwrap());
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(versions[2] >= RippledVersion::new(1, 9, 4));
+        assert!(versions[7] < RippledVersion::new(1, 10, 0));
+    }
+}
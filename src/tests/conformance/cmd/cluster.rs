@@ -1,5 +1,3 @@
-use std::net::IpAddr;
-
 use tempfile::TempDir;
 
 use crate::{
@@ -8,10 +6,11 @@ use crate::{
         proto::{TmCluster, TmClusterNode},
     },
     setup::{
-        constants::{DEFAULT_PORT, SYNTHETIC_NODE_PUBLIC_KEY},
+        addrs::NodeAddrs,
+        constants::SYNTHETIC_NODE_PUBLIC_KEY,
         node::{Node, NodeType},
     },
-    tools::{config::SynthNodeCfg, synth_node::SyntheticNode},
+    tools::{config::SynthNodeCfg, keys::NodeKeys, synth_node::SyntheticNode},
 };
 
 #[allow(non_snake_case)]
@@ -20,11 +19,11 @@ async fn c024_TM_CLUSTER_node_should_connect_to_other_nodes_in_cluster() {
     // ZG-CONFORMANCE-024
 
     // Start a synthetic node configured to use known keys so that rippled knows who it's talking to.
-    let synth_node_ip = "127.0.0.2".parse().unwrap();
+    let synth_node_addrs = NodeAddrs::allocate().expect("unable to allocate an address");
     let mut test_config = SynthNodeCfg::default();
-    test_config.pea2pea_config.listener_ip = Some(IpAddr::V4(synth_node_ip));
-    test_config.pea2pea_config.desired_listening_port = Some(DEFAULT_PORT);
-    test_config.generate_new_keys = false;
+    test_config.pea2pea_config.listener_ip = Some(synth_node_addrs.ip);
+    test_config.pea2pea_config.desired_listening_port = Some(synth_node_addrs.peer_port);
+    test_config.keys = NodeKeys::Predefined;
 
     let mut synth_node = SyntheticNode::new(&test_config).await;
     let listening_addr = synth_node
@@ -53,7 +52,7 @@ async fn c024_TM_CLUSTER_node_should_connect_to_other_nodes_in_cluster() {
 
     // Shutdown.
     synth_node.shut_down().await;
-    node.stop().expect("unable to stop the rippled node");
+    node.stop().await.expect("unable to stop the rippled node");
 }
 
 fn public_key_in_cluster_nodes(cluster_nodes: &[TmClusterNode]) -> bool {
@@ -98,7 +98,7 @@ async fn c009_TM_SQUELCH_cannot_squelch_peer_ledger_proposals() {
     .expect("TmProposeLedger not received in time");
 
     synth_node.shut_down().await;
-    node.stop().expect("Unable to stop the stateful node");
+    node.stop().await.expect("Unable to stop the stateful node");
 }
 
 #[tokio::test]
@@ -206,9 +206,12 @@ async fn c016_TM_SQUELCH_squelch_distant_validators() {
     assert!(expect_timeout_err_for_squelched_nodes.is_err());
 
     synth_node.shut_down().await;
-    peer_node.stop().expect("Unable to stop the stateful node");
+    peer_node
+        .stop()
+        .await
+        .expect("Unable to stop the stateful node");
     for mut node in distant_nodes {
-        node.stop().expect("Unable to stop the stateful node");
+        node.stop().await.expect("Unable to stop the stateful node");
     }
 }
 
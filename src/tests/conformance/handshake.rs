@@ -1,7 +1,7 @@
 use tempfile::TempDir;
 
 use crate::{
-    protocol::codecs::message::BinaryMessage,
+    protocol::{codecs::message::BinaryMessage, handshake::ConnectionMode},
     setup::{
         constants::CONNECTION_TIMEOUT,
         node::{Node, NodeType},
@@ -34,7 +34,7 @@ async fn c001_handshake_when_node_receives_connection() {
 
     // Shutdown both nodes
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -63,7 +63,7 @@ async fn c002_handshake_when_node_initiates_connection() {
 
     // Shutdown both nodes
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -73,6 +73,9 @@ async fn c006_node_should_not_send_any_messages_if_no_handshake() {
     // ZG-CONFORMANCE-006
     let response_check = |_: &BinaryMessage| true;
 
-    perform_expected_message_test(TestConfig::default().with_handshake(None), &response_check)
-        .await;
+    perform_expected_message_test(
+        TestConfig::default().with_mode(ConnectionMode::Tcp),
+        &response_check,
+    )
+    .await;
 }
@@ -3,19 +3,14 @@ use tempfile::TempDir;
 use crate::{
     protocol::{
         codecs::message::{BinaryMessage, Payload},
-        handshake::HandshakeCfg,
+        handshake::ConnectionMode,
     },
     setup::{
         constants::TESTNET_READY_TIMEOUT,
         node::{Node, NodeType},
         testnet::TestNet,
     },
-    tools::{
-        config::SynthNodeCfg,
-        constants::GENESIS_ACCOUNT,
-        rpc::{submit_transaction, wait_for_account_data},
-        synth_node::SyntheticNode,
-    },
+    tools::{config::SynthNodeCfg, rpc::submit_transaction, synth_node::SyntheticNode},
 };
 
 mod cmd;
@@ -34,7 +29,7 @@ pub const PUBLIC_KEY_TYPES: &[u8] = &[
 /// Ripple epoch starts at Jan-1-2000. The number here equals number of seconds since unix epoch (Jan-1-1970)
 pub const RIPPLE_EPOCH: u32 = 946684800;
 
-// A transaction blob representing a signed transaction. Extracted by executing `tools/transfer.py` and listening with `tcpdump -A -i lo dst port 5005 or src port 5005`.
+// A transaction blob representing a signed transaction with LastLedgerSequence 0x1E. Extracted by executing the former xrpl-py `tools/transfer.py` (see the git history) and listening with `tcpdump -A -i lo dst port 5005 or src port 5005`.
 pub const TRANSACTION_BLOB: &str = "12000022000000002400000001201B0000001E61400000012A05F20068400000000000000A73210330E7FC9D56BB25D6893BA3F317AE5BCF33B3291BD63DB32654A313222F7FD020744630440220297389244D36AF12115296F409C446D9A5D808880DC7FF323AA207ED529CE6C802207AAC5D2A96CB102CBDE85D2A4BA814253CA133AC9277041CAE2E1A349FB233FF8114B5F762798A53D543A014CAF8B297CFF8F2F937E883149193D6AED0CBBC25790ADE05D020C9C6D9201DCF";
 
 /// Test configuration for tests using the below helper test function.
@@ -54,9 +49,9 @@ impl TestConfig {
         self
     }
 
-    /// Allow custom handshake configuration.
-    pub fn with_handshake(mut self, handshake: Option<HandshakeCfg>) -> Self {
-        self.synth_node_cfg.handshake = handshake;
+    /// Allow a custom connection mode.
+    pub fn with_mode(mut self, mode: ConnectionMode) -> Self {
+        self.synth_node_cfg.mode = mode;
         self
     }
 }
@@ -92,7 +87,7 @@ async fn perform_expected_message_test(
 
     // Shutdown both nodes
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 /// Performs a check for the required message after a new transaction in the testnet.
@@ -107,24 +102,21 @@ pub async fn perform_testnet_transaction_check(check: &dyn Fn(&BinaryMessage) ->
     // Start a testnet.
     let mut testnet = TestNet::new().unwrap();
     testnet.start().await.unwrap();
-    wait_for_account_data(
-        &testnet.running[NODE_IDS[0]].rpc_url(),
-        GENESIS_ACCOUNT,
-        TESTNET_READY_TIMEOUT,
-    )
-    .await
-    .expect("Unable to get the account data.");
+    testnet
+        .wait_ready(TESTNET_READY_TIMEOUT)
+        .await
+        .expect("The testnet isn't ready.");
 
     // Start a synthetic node and connect to the second node in the testnet.
     let mut synth_node = SyntheticNode::new(&Default::default()).await;
     synth_node
-        .connect(testnet.running[NODE_IDS[1]].addr())
+        .connect(testnet.node(NODE_IDS[1]).addr())
         .await
         .expect("Unable to connect to the second node");
 
     // Submit a transaction to the first node via RPC.
     let transaction = submit_transaction(
-        &testnet.running[NODE_IDS[0]].rpc_url(),
+        &testnet.node(NODE_IDS[0]).rpc_url(),
         TRANSACTION_BLOB.into(),
         false,
     )
@@ -303,5 +303,5 @@ async fn c026_TM_VALIDATOR_LIST_send_validator_list() {
     // Shutdown.
     synth_node1.shut_down().await;
     synth_node2.shut_down().await;
-    node.stop().expect(ERR_NODE_STOP);
+    node.stop().await.expect(ERR_NODE_STOP);
 }
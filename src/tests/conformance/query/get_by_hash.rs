@@ -78,7 +78,7 @@ async fn c007_TM_GET_OBJECT_BY_HASH_get_transaction_by_hash() {
     assert!(synth_node.expect_message(&check).await);
 
     synth_node.shut_down().await;
-    node.stop().expect("unable to stop stateful node");
+    node.stop().await.expect("unable to stop stateful node");
 }
 
 #[tokio::test]
@@ -128,5 +128,5 @@ async fn c008_TM_HAVE_TRANSACTIONS_query_for_transactions_after_have_transaction
     assert!(synth_node.expect_message(&check).await);
 
     synth_node.shut_down().await;
-    node.stop().expect("unable to stop stateful node");
+    node.stop().await.expect("unable to stop stateful node");
 }
@@ -91,14 +91,14 @@ async fn check_relay_for_key_type(key_type: u8, relays: u32) {
     // Verify the public key and ensure that the `relays` number got subtracted.
     let check = |m: &BinaryMessage| {
         matches!(&m.payload, Payload::TmGetPeerShardInfoV2(TmGetPeerShardInfoV2{peer_chain, relays: received_relays})
-          if peer_chain.get(0) == Some(&public_key) && *received_relays == relays.saturating_sub(1))
+          if peer_chain.first() == Some(&public_key) && *received_relays == relays.saturating_sub(1))
     };
     assert!(synth_node2.expect_message(&check).await);
 
     // Shutdown.
     synth_node1.shut_down().await;
     synth_node2.shut_down().await;
-    node.stop().expect(ERR_NODE_STOP);
+    node.stop().await.expect(ERR_NODE_STOP);
 }
 
 #[tokio::test]
@@ -142,5 +142,5 @@ async fn c023_TM_PEER_SHARD_INFO_V2_node_should_respond_with_shard_info_if_shard
 
     // Shutdown.
     synth_node.shut_down().await;
-    node.stop().expect(ERR_NODE_STOP);
+    node.stop().await.expect(ERR_NODE_STOP);
 }
@@ -110,7 +110,7 @@ async fn c003_t2_TM_PING_expect_ping() {
 
     // Shutdown both nodes
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -149,5 +149,5 @@ async fn c003_t3_TM_PING_send_pong() {
 
     // Shutdown both nodes
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -41,7 +41,7 @@ async fn c025_TM_PROOF_PATH_REQUEST_TM_PROOF_PATH_RESPONSE_send_req_expect_rsp()
 
     // Shutdown.
     synth_node.shut_down().await;
-    node.stop().expect("unable to stop the rippled node");
+    node.stop().await.expect("unable to stop the rippled node");
 }
 
 async fn get_proof_path_for_state(
@@ -50,5 +50,5 @@ async fn c022_TM_REPLAY_DELTA_REQUEST_TM_REPLAY_DELTA_RESPONSE_node_should_respo
 
     // Shutdown.
     synth_node.shut_down().await;
-    node.stop().expect("Unable to stop the rippled node.");
+    node.stop().await.expect("Unable to stop the rippled node.");
 }
@@ -27,5 +27,5 @@ async fn should_start_stop_stateful_node() {
             .expect("unable to get account data");
     assert_eq!(account_data.result.account_data.balance, "5000000000");
 
-    node.stop().expect("unable to stop stateful node");
+    node.stop().await.expect("unable to stop stateful node");
 }
@@ -59,5 +59,5 @@ async fn c010_TM_STATUS_CHANGE_node_should_send_ledger_information_using_status_
 
     // Cleanup.
     sn.shut_down().await;
-    node.stop().expect("unable to stop stateful node");
+    node.stop().await.expect("unable to stop stateful node");
 }
@@ -2,14 +2,14 @@
 //!
 //! Some helper tools:
 //!  - Change process niceness:
-//!     sudo renice -n -19 -p $(pidof rippled)
+//!    sudo renice -n -19 -p $(pidof rippled)
 //!
 use std::{net::SocketAddr, path::Path};
 
 use chrono::{DateTime, Utc};
 use reqwest::Client;
 use tempfile::TempDir;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, Duration, Instant};
 use ziggurat_core_utils::err_constants::{
     ERR_NODE_BUILD, ERR_SYNTH_CONNECT, ERR_SYNTH_START_LISTENING, ERR_SYNTH_UNICAST,
     ERR_TEMPDIR_NEW,
@@ -185,7 +185,7 @@ async fn node_run_forever(cfg: DevTestCfg) {
         _ => None,
     };
 
-    let mut node = node_start(target.path(), log_to_stdout, initial_peers.clone()).await;
+    let node = node_start(target.path(), log_to_stdout, initial_peers.clone()).await;
     let addr = node.addr();
 
     if let Some(synth_node) = synth_node.as_ref() {
@@ -207,9 +207,13 @@ async fn node_run_forever(cfg: DevTestCfg) {
     }
 
     // The node should run forever unless something bad happens to it.
-    node.wait_until_exit().await;
-
-    println!("\tThe node has stopped running ({})", current_time_str());
+    match node.wait_until_exit().await {
+        Ok(status) => println!(
+            "\tThe node has stopped running with {status} ({})",
+            current_time_str()
+        ),
+        Err(e) => println!("\tThe node was lost: {e} ({})", current_time_str()),
+    }
 }
 
 /// Create and start the node and print the extra useful debug info.
@@ -237,14 +241,23 @@ async fn node_start(path: &Path, log_to_stdout: bool, initial_peers: Vec<SocketA
 }
 
 /// Use recv_message to clear up the inbound queue and print out
-/// the received messages.
+/// the received messages along with periodic traffic statistics.
 ///
 /// Only replies to the ping messages so the connection is never dropped.
 async fn spawn_periodic_msg_recv(mut synth_node: SyntheticNode) {
+    const TRAFFIC_STATS_PERIOD: Duration = Duration::from_secs(60);
+
     tokio::spawn(async move {
+        let mut last_stats = Instant::now();
+
         loop {
             let (from_addr, msg) = synth_node.recv_message().await;
 
+            if last_stats.elapsed() >= TRAFFIC_STATS_PERIOD {
+                println!("{}", synth_node.traffic_stats());
+                last_stats = Instant::now();
+            }
+
             let payload = msg.payload;
             tracing::info!("message received: {payload:?}");
 
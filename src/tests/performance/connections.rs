@@ -15,7 +15,10 @@ use ziggurat_core_utils::err_constants::{
 };
 
 use crate::{
-    setup::node::{Node, NodeType},
+    setup::{
+        node::{Node, NodeType},
+        resources::ResourceStats,
+    },
     tools::{config::SynthNodeCfg, ips::ips, synth_node::SyntheticNode},
 };
 
@@ -24,6 +27,9 @@ const METRIC_TERMINATED: &str = "perf_conn_terminated";
 const METRIC_REJECTED: &str = "perf_conn_rejected";
 const METRIC_ERROR: &str = "perf_conn_error";
 
+/// How often the node's resource usage is sampled.
+const RESOURCE_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
 #[cfg_attr(
     not(feature = "performance"),
     ignore = "run this test with the 'performance' feature enabled"
@@ -129,6 +135,7 @@ async fn p002_connections_load() {
     let synth_counts = vec![1, 5, 10, 20, 30, 50, 100];
 
     let mut all_stats = Vec::new();
+    let mut all_resources = Vec::new();
 
     for synth_count in synth_counts {
         let target = TempDir::new().expect(ERR_TEMPDIR_NEW);
@@ -139,6 +146,7 @@ async fn p002_connections_load() {
             .await
             .expect(ERR_NODE_BUILD);
         let node_addr = node.addr();
+        let sampler = node.sample_resources(RESOURCE_SAMPLE_INTERVAL);
 
         let mut synth_sockets = Vec::with_capacity(synth_count);
         let mut ips = ips();
@@ -221,12 +229,17 @@ async fn p002_connections_load() {
                 synth_count as u16 - stats.accepted - stats.rejected - stats.conn_error;
         }
         all_stats.push(stats);
+        all_resources.push(ResourceStats::new(
+            format!("{synth_count} peers"),
+            &sampler.stop(),
+        ));
 
-        node.stop().expect(ERR_NODE_STOP);
+        node.stop().await.expect(ERR_NODE_STOP);
     }
 
     // Display results table
     println!("\r\n{}", fmt_table(Table::new(&all_stats)));
+    println!("\r\n{}", fmt_table(Table::new(&all_resources)));
 
     // Check that results are okay
     for stats in all_stats.iter() {
@@ -164,7 +164,7 @@ async fn p003_t1_GET_TRANSACTION_latency() {
             }
         }
 
-        node.stop().expect(ERR_NODE_STOP);
+        node.stop().await.expect(ERR_NODE_STOP);
     }
 
     // Display results table
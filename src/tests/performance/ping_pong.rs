@@ -147,7 +147,7 @@ async fn p001_t1_PING_PONG_throughput() {
             }
         }
 
-        node.stop().expect(ERR_NODE_STOP);
+        node.stop().await.expect(ERR_NODE_STOP);
     }
 
     // Display results table
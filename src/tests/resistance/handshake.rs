@@ -43,10 +43,7 @@ async fn r001_t1_HANDSHAKE_reject_if_user_agent_too_long() {
 
     // Start the first synthetic node with a 'User-Agent' header that's too long.
     let mut cfg = SynthNodeCfg::default();
-    cfg.handshake = cfg.handshake.map(|mut hs_cfg| {
-        hs_cfg.http_ident = format!("{:8192}", 0);
-        hs_cfg
-    });
+    cfg.handshake.http_ident = format!("{:8192}", 0);
 
     let synth_node1 = SyntheticNode::new(&cfg).await;
     // Ensure this connection was rejected by the node.
@@ -64,7 +61,7 @@ async fn r001_t1_HANDSHAKE_reject_if_user_agent_too_long() {
     // Shutdown all nodes.
     synth_node1.shut_down().await;
     synth_node2.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[allow(non_snake_case)]
@@ -75,10 +72,7 @@ async fn r001_t2_HANDSHAKE_reject_if_server_too_long() {
     // Start the first synthetic node. Set identification ('Server' header) for the value that's too long.
     let mut cfg = SynthNodeCfg::default();
     cfg.pea2pea_config.listener_ip = Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)));
-    cfg.handshake = cfg.handshake.map(|mut hs_cfg| {
-        hs_cfg.http_ident = format!("{:8192}", 0);
-        hs_cfg
-    });
+    cfg.handshake.http_ident = format!("{:8192}", 0);
 
     let synth_node1 = SyntheticNode::new(&cfg).await;
     let sn1_listening_addr = synth_node1
@@ -112,7 +106,7 @@ async fn r001_t2_HANDSHAKE_reject_if_server_too_long() {
     // Shutdown all nodes.
     synth_node1.shut_down().await;
     synth_node2.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 /// Decide whether to enable node logs and tracing for synthetic nodes.
@@ -176,7 +170,10 @@ async fn run_handshake_req_test_with_cfg(cfg: SynthNodeCfg, debug: Debug) -> boo
 
     // Gracefully shut down the nodes.
     synthetic_node.shut_down().await;
-    assert_eq!(node.stop().expect(ERR_NODE_STOP), ChildExitCode::Success);
+    assert_eq!(
+        node.stop().await.expect(ERR_NODE_STOP),
+        ChildExitCode::Success
+    );
 
     handshake_established
 }
@@ -207,10 +204,10 @@ async fn r001_t3_HANDSHAKE_connection_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |connection: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_connection: connection,
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -256,10 +253,10 @@ async fn r001_t5_HANDSHAKE_crawl_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |crawl: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_crawl: Some(crawl),
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -290,10 +287,10 @@ async fn r001_t4_HANDSHAKE_connect_as_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |connect_as: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_connect_as: connect_as,
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -338,10 +335,10 @@ async fn r001_t6_HANDSHAKE_x_protocol_ctl_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |protocol: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_x_protocol_ctl: protocol,
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -377,10 +374,10 @@ async fn r001_t7_HANDSHAKE_network_time_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |time: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_network_time: Some(time),
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -427,10 +424,10 @@ async fn r001_t8_HANDSHAKE_upgrade_req_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |version: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_upgrade_req: version,
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -473,10 +470,10 @@ async fn r001_t9_HANDSHAKE_user_agent_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |ident: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_ident: ident,
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -509,10 +506,10 @@ async fn r001_t10_HANDSHAKE_closed_ledger_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |ledger: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_closed_ledger: Some(ledger),
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -550,10 +547,10 @@ async fn r001_t11_HANDSHAKE_previous_ledger_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |ledger: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_prev_ledger: Some(ledger),
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -576,10 +573,10 @@ async fn r001_t12_HANDSHAKE_extra_field() {
     let debug = Debug::disable();
 
     let gen_cfg = |value: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
+        handshake: HandshakeCfg {
             http_unexpected_extra_field_and_value: Some(value),
             ..Default::default()
-        }),
+        },
         ..Default::default()
     };
 
@@ -595,10 +592,7 @@ async fn r003_t1_HANDSHAKE_reject_if_public_key_has_bit_flipped() {
 
     // Prepare config for a synthetic node. Flip bit in the public_key.
     let mut cfg = SynthNodeCfg::default();
-    cfg.handshake = cfg.handshake.map(|mut hs_cfg| {
-        hs_cfg.bitflip_pub_key = true;
-        hs_cfg
-    });
+    cfg.handshake.bitflip_pub_key = true;
 
     run_and_assert_handshake_failure(&cfg, Responder).await;
     run_and_assert_handshake_failure(&cfg, Initiator).await;
@@ -611,10 +605,7 @@ async fn r003_t2_HANDSHAKE_reject_if_shared_value_has_bit_flipped() {
 
     // Prepare config for a synthetic node. Flip bit in the shared_value.
     let mut cfg = SynthNodeCfg::default();
-    cfg.handshake = cfg.handshake.map(|mut hs_cfg| {
-        hs_cfg.bitflip_shared_val = true;
-        hs_cfg
-    });
+    cfg.handshake.bitflip_shared_val = true;
 
     run_and_assert_handshake_failure(&cfg, Responder).await;
     run_and_assert_handshake_failure(&cfg, Initiator).await;
@@ -654,5 +645,5 @@ async fn run_and_assert_handshake_failure(config: &SynthNodeCfg, connection_side
 
     // Shutdown all nodes.
     synth_node.shut_down().await;
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -4,6 +4,7 @@ use tempfile::TempDir;
 
 use crate::{
     fuzzing::{random_bytes, seeded_rng},
+    protocol::handshake::ConnectionMode,
     setup::node::{Node, NodeType},
     tools::{config::SynthNodeCfg, synth_node::SyntheticNode},
     wait_until,
@@ -27,19 +28,25 @@ async fn r002_node_must_disconnect_when_receiving_random_bytes() {
         .expect("unable to start the node");
 
     for payload in payloads {
-        let synth_node = SyntheticNode::new(&Default::default()).await;
-        synth_node.connect(node.addr()).await.unwrap();
-        synth_node.unicast_bytes(node.addr(), payload).unwrap();
-
-        // Ensure that the node has disconnected.
-        wait_until!(
-            DISCONNECT_TIMEOUT,
-            !synth_node.is_connected_ip(node.addr().ip())
-        );
-        synth_node.shut_down().await;
+        // A crash caused by the payload fails the test with the node's crash report.
+        node.until_crash(async {
+            let mut synth_node = SyntheticNode::new(&Default::default()).await;
+            synth_node.connect(node.addr()).await.unwrap();
+            synth_node.unicast_bytes(node.addr(), payload).unwrap();
+
+            // Ensure that the node has disconnected because of the payload.
+            let reason = synth_node
+                .wait_for_disconnect(node.addr(), DISCONNECT_TIMEOUT)
+                .await
+                .expect("the node didn't disconnect");
+            assert!(reason.is_remote(), "unexpected disconnect: {reason:?}");
+            synth_node.shut_down().await;
+        })
+        .await
+        .unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
 
 #[tokio::test]
@@ -57,22 +64,27 @@ async fn r004_node_must_disconnect_when_receiving_random_bytes_pre_handshake() {
         .expect("unable to start the node");
 
     let cfg = SynthNodeCfg {
-        handshake: None, // Disable handshake.
+        mode: ConnectionMode::Tcp, // Disable handshake.
         ..Default::default()
     };
 
     for payload in payloads {
-        let synth_node = SyntheticNode::new(&cfg).await;
-        synth_node.connect(node.addr()).await.unwrap();
-        synth_node.unicast_bytes(node.addr(), payload).unwrap();
-
-        // Ensure that the node has disconnected.
-        wait_until!(
-            DISCONNECT_TIMEOUT,
-            !synth_node.is_connected_ip(node.addr().ip())
-        );
-        synth_node.shut_down().await;
+        // A crash caused by the payload fails the test with the node's crash report.
+        node.until_crash(async {
+            let synth_node = SyntheticNode::new(&cfg).await;
+            synth_node.connect(node.addr()).await.unwrap();
+            synth_node.unicast_bytes(node.addr(), payload).unwrap();
+
+            // Ensure that the node has disconnected.
+            wait_until!(
+                DISCONNECT_TIMEOUT,
+                !synth_node.is_connected_ip(node.addr().ip())
+            );
+            synth_node.shut_down().await;
+        })
+        .await
+        .unwrap();
     }
 
-    node.stop().unwrap();
+    node.stop().await.unwrap();
 }
@@ -0,0 +1,421 @@
+//! Recording of the wire traffic seen by the [InnerNode](crate::tools::inner_node::InnerNode).
+//!
+//! Frames are recorded after TLS decryption, so the capture contains the plain message
+//! headers and protobuf payloads exactly as they were read from or written to the stream.
+//!
+//! The capture file format:
+//!
+//! ```text
+//! file   = magic record*
+//! magic  = "ZGCAP001"                    (8 bytes)
+//! record = length                        (u32, big-endian, size of the rest of the record)
+//!          direction                     (u8, 0 = inbound, 1 = outbound)
+//!          kind                          (u8, 0 = frame, 1 = raw bytes)
+//!          timestamp                     (u64, big-endian, microseconds since the UNIX epoch)
+//!          peer_length                   (u8)
+//!          peer                          (peer_length bytes, UTF-8, e.g. "127.0.0.1:51235")
+//!          bytes                         (the rest of the record)
+//! ```
+//!
+//! A frame is a complete message: the header followed by the protobuf payload. Raw bytes are
+//! anything that doesn't form a valid message, e.g. bytes sent with
+//! [unicast_bytes](crate::tools::synth_node::SyntheticNode::unicast_bytes) or inbound bytes
+//! which couldn't be decoded.
+//!
+//! Captures can be converted to pcapng with [export_pcapng] and opened in Wireshark.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    net::{SocketAddr, SocketAddrV4},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+use tracing::Span;
+
+use crate::{
+    protocol::codecs::message::{BinaryMessage, MessageCodec},
+    tools::traffic::Direction,
+};
+
+/// The magic bytes at the start of every capture file.
+pub const CAPTURE_MAGIC: &[u8; 8] = b"ZGCAP001";
+
+/// What the recorded bytes represent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// A complete message: header and protobuf payload.
+    Frame,
+    /// Bytes which don't form a valid message.
+    Raw,
+}
+
+/// A single entry in the capture file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub direction: Direction,
+    pub kind: RecordKind,
+    pub timestamp: SystemTime,
+    pub peer: SocketAddr,
+    pub bytes: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Decodes the recorded frame.
+    ///
+    /// Returns `None` for raw bytes.
+    pub fn decode(&self) -> io::Result<Option<BinaryMessage>> {
+        if self.kind == RecordKind::Raw {
+            return Ok(None);
+        }
+
+        decode_frame(&self.bytes)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let peer = self.peer.to_string();
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let len = 1 + 1 + 8 + 1 + peer.len() + self.bytes.len();
+        let mut buf = Vec::with_capacity(4 + len);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+        buf.push(match self.direction {
+            Direction::Inbound => 0,
+            Direction::Outbound => 1,
+        });
+        buf.push(match self.kind {
+            RecordKind::Frame => 0,
+            RecordKind::Raw => 1,
+        });
+        buf.extend_from_slice(&timestamp.to_be_bytes());
+        buf.push(peer.len() as u8);
+        buf.extend_from_slice(peer.as_bytes());
+        buf.extend_from_slice(&self.bytes);
+
+        buf
+    }
+
+    fn parse(record: &[u8]) -> io::Result<Self> {
+        const FIXED_LEN: usize = 1 + 1 + 8 + 1;
+
+        if record.len() < FIXED_LEN {
+            return Err(invalid_data("truncated record"));
+        }
+
+        let direction = match record[0] {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            other => return Err(invalid_data(format!("invalid direction {other}"))),
+        };
+        let kind = match record[1] {
+            0 => RecordKind::Frame,
+            1 => RecordKind::Raw,
+            other => return Err(invalid_data(format!("invalid record kind {other}"))),
+        };
+        let micros = u64::from_be_bytes(record[2..10].try_into().unwrap());
+        let peer_len = record[10] as usize;
+
+        let peer = record
+            .get(FIXED_LEN..FIXED_LEN + peer_len)
+            .ok_or_else(|| invalid_data("truncated peer address"))?;
+        let peer = std::str::from_utf8(peer)
+            .ok()
+            .and_then(|peer| peer.parse().ok())
+            .ok_or_else(|| invalid_data("invalid peer address"))?;
+
+        Ok(Self {
+            direction,
+            kind,
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            peer,
+            bytes: record[FIXED_LEN + peer_len..].to_vec(),
+        })
+    }
+}
+
+/// Decodes a single recorded frame.
+///
+/// Returns `None` if the frame is incomplete.
+pub fn decode_frame(bytes: &[u8]) -> io::Result<Option<BinaryMessage>> {
+    MessageCodec::new(Span::none()).decode(&mut BytesMut::from(bytes))
+}
+
+/// Writes the traffic to a capture file. Cloned instances share the same file.
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    /// Creates the capture file, overwriting any existing one.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(CAPTURE_MAGIC)?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Appends a record to the capture file.
+    ///
+    /// Every record is written at once, so the capture stays readable if the test panics.
+    pub fn record(
+        &self,
+        direction: Direction,
+        kind: RecordKind,
+        peer: SocketAddr,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let record = CaptureRecord {
+            direction,
+            kind,
+            timestamp: SystemTime::now(),
+            peer,
+            bytes: bytes.to_vec(),
+        };
+
+        self.file.lock().unwrap().write_all(&record.encode())
+    }
+}
+
+/// Reads all the records from a capture file.
+pub fn read_capture(path: &Path) -> io::Result<Vec<CaptureRecord>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; CAPTURE_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != CAPTURE_MAGIC {
+        return Err(invalid_data("not a capture file"));
+    }
+
+    let mut records = Vec::new();
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        let mut record = vec![0u8; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut record)?;
+        records.push(CaptureRecord::parse(&record)?);
+    }
+
+    Ok(records)
+}
+
+// pcapng constants, see https://www.ietf.org/archive/id/draft-tuexen-opsawg-pcapng-05.html.
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x00000006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const LINKTYPE_RAW: u16 = 101;
+// Keeps the IPv4 total length within bounds.
+const MAX_SEGMENT_SIZE: usize = 60000;
+
+/// Exports the records into a pcapng file.
+///
+/// Each record is wrapped in synthetic IPv4 and TCP headers between the peer and the given
+/// local address. As the payloads are already decrypted, Wireshark can dissect them directly.
+/// Records of IPv6 peers are skipped.
+pub fn export_pcapng(
+    records: &[CaptureRecord],
+    local_addr: SocketAddrV4,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    // Section header block.
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+    shb.extend_from_slice(&1u16.to_le_bytes()); // major version
+    shb.extend_from_slice(&0u16.to_le_bytes()); // minor version
+    shb.extend_from_slice(&(-1i64).to_le_bytes()); // unspecified section length
+    write_block(out, PCAPNG_SECTION_HEADER, &shb)?;
+
+    // Interface description block, the default timestamp resolution is in microseconds.
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    idb.extend_from_slice(&0u32.to_le_bytes()); // no snapshot length limit
+    write_block(out, PCAPNG_INTERFACE_DESCRIPTION, &idb)?;
+
+    // The next TCP sequence number for each peer and direction.
+    let mut seqs: HashMap<(SocketAddr, Direction), u32> = HashMap::new();
+
+    for record in records {
+        let peer = match record.peer {
+            SocketAddr::V4(peer) => peer,
+            SocketAddr::V6(_) => continue,
+        };
+        let (src, dst) = match record.direction {
+            Direction::Inbound => (peer, local_addr),
+            Direction::Outbound => (local_addr, peer),
+        };
+        let micros = record
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        for segment in record.bytes.chunks(MAX_SEGMENT_SIZE) {
+            let seq = seqs.entry((record.peer, record.direction)).or_insert(1);
+            let packet = build_tcp_packet(src, dst, *seq, segment);
+            *seq = seq.wrapping_add(segment.len() as u32);
+
+            let mut epb = Vec::with_capacity(20 + packet.len() + 3);
+            epb.extend_from_slice(&0u32.to_le_bytes()); // interface id
+            epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+            epb.extend_from_slice(&(micros as u32).to_le_bytes());
+            epb.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // captured length
+            epb.extend_from_slice(&(packet.len() as u32).to_le_bytes()); // original length
+            epb.extend_from_slice(&packet);
+            write_block(out, PCAPNG_ENHANCED_PACKET, &epb)?;
+        }
+    }
+
+    out.flush()
+}
+
+// Writes a pcapng block, padding the body to 32 bits.
+fn write_block(out: &mut impl Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let padding = (4 - body.len() % 4) % 4;
+    let total_len = (12 + body.len() + padding) as u32;
+
+    out.write_all(&block_type.to_le_bytes())?;
+    out.write_all(&total_len.to_le_bytes())?;
+    out.write_all(body)?;
+    out.write_all(&[0u8; 3][..padding])?;
+    out.write_all(&total_len.to_le_bytes())
+}
+
+// Builds an IPv4 packet carrying a TCP segment with the PSH and ACK flags set.
+fn build_tcp_packet(src: SocketAddrV4, dst: SocketAddrV4, seq: u32, payload: &[u8]) -> Vec<u8> {
+    const IP_HEADER_LEN: usize = 20;
+    const TCP_HEADER_LEN: usize = 20;
+
+    let total_len = (IP_HEADER_LEN + TCP_HEADER_LEN + payload.len()) as u16;
+    let mut packet = Vec::with_capacity(total_len as usize);
+
+    // IPv4 header.
+    packet.push(0x45); // version 4, header length 5 words
+    packet.push(0); // type of service
+    packet.extend_from_slice(&total_len.to_be_bytes());
+    packet.extend_from_slice(&0u16.to_be_bytes()); // identification
+    packet.extend_from_slice(&0x4000u16.to_be_bytes()); // don't fragment
+    packet.push(64); // time to live
+    packet.push(6); // TCP
+    packet.extend_from_slice(&0u16.to_be_bytes()); // checksum placeholder
+    packet.extend_from_slice(&src.ip().octets());
+    packet.extend_from_slice(&dst.ip().octets());
+    let checksum = ipv4_checksum(&packet[..IP_HEADER_LEN]);
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    // TCP header, the checksum is left empty.
+    packet.extend_from_slice(&src.port().to_be_bytes());
+    packet.extend_from_slice(&dst.port().to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(&0u32.to_be_bytes()); // acknowledgment number
+    packet.push((TCP_HEADER_LEN as u8 / 4) << 4);
+    packet.push(0x18); // PSH, ACK
+    packet.extend_from_slice(&u16::MAX.to_be_bytes()); // window
+    packet.extend_from_slice(&0u16.to_be_bytes()); // checksum
+    packet.extend_from_slice(&0u16.to_be_bytes()); // urgent pointer
+
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        protocol::{codecs::message::Payload, proto::TmPing},
+        tools::testing::ping_frame,
+    };
+
+    #[test]
+    fn write_and_read_capture() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("traffic.zgcap");
+        let peer: SocketAddr = "127.0.0.1:51235".parse().unwrap();
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder
+            .record(Direction::Outbound, RecordKind::Frame, peer, &ping_frame(7))
+            .unwrap();
+        recorder
+            .record(Direction::Inbound, RecordKind::Raw, peer, b"garbage")
+            .unwrap();
+
+        let records = read_capture(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Outbound);
+        assert_eq!(records[0].peer, peer);
+        assert_eq!(records[1].bytes, b"garbage");
+        assert!(records[1].decode().unwrap().is_none());
+
+        let message = records[0].decode().unwrap().unwrap();
+        assert!(matches!(
+            message.payload,
+            Payload::TmPing(TmPing { seq: Some(7), .. })
+        ));
+    }
+
+    #[test]
+    fn export_to_pcapng() {
+        let peer: SocketAddr = "127.0.0.2:51235".parse().unwrap();
+        let records = vec![CaptureRecord {
+            direction: Direction::Inbound,
+            kind: RecordKind::Frame,
+            timestamp: SystemTime::now(),
+            peer,
+            bytes: ping_frame(7).to_vec(),
+        }];
+
+        let mut out = Vec::new();
+        export_pcapng(&records, "127.0.0.1:0".parse().unwrap(), &mut out).unwrap();
+
+        // Section header and interface description blocks.
+        assert_eq!(&out[..4], &PCAPNG_SECTION_HEADER.to_le_bytes());
+        assert_eq!(out.len() % 4, 0);
+        let shb_len = u32::from_le_bytes(out[4..8].try_into().unwrap()) as usize;
+        let idb_len =
+            u32::from_le_bytes(out[shb_len + 4..shb_len + 8].try_into().unwrap()) as usize;
+
+        // The packet block carries the frame after the IPv4 and TCP headers.
+        let epb = &out[shb_len + idb_len..];
+        assert_eq!(&epb[..4], &PCAPNG_ENHANCED_PACKET.to_le_bytes());
+        let packet_len = u32::from_le_bytes(epb[20..24].try_into().unwrap()) as usize;
+        assert_eq!(packet_len, 40 + ping_frame(7).len());
+        assert_eq!(&epb[28 + 12..28 + 16], &[127, 0, 0, 2]);
+        assert_eq!(&epb[28 + 40..28 + packet_len], &ping_frame(7)[..]);
+    }
+}
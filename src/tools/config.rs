@@ -1,17 +1,32 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
 
-use crate::protocol::handshake::HandshakeCfg;
+use crate::{
+    protocol::handshake::{ConnectionMode, HandshakeCfg},
+    tools::{keys::NodeKeys, shaping::ShapingCfg},
+};
 
 /// Synthetic Node Configuration.
 #[derive(Clone)]
 pub struct SynthNodeCfg {
-    /// Whether or not to generate new keys for a handshake.
-    pub generate_new_keys: bool,
+    /// The source of the node's identity keys used for a handshake.
+    pub keys: NodeKeys,
 
     /// Handshake configuration.
+    pub handshake: HandshakeCfg,
+
+    /// The layers established on every connection, see [ConnectionMode].
+    pub mode: ConnectionMode,
+
+    /// Path of the file to record the wire traffic into.
     ///
-    /// If not set, the handshake will be skipped.
-    pub handshake: Option<HandshakeCfg>,
+    /// If not set, the traffic isn't recorded.
+    pub capture_path: Option<PathBuf>,
+
+    /// Traffic shaping applied to every connection, unless it's set for the connection explicitly.
+    pub shaping: ShapingCfg,
 
     /// Pea2Pea configuration.
     pub pea2pea_config: pea2pea::Config,
@@ -21,8 +36,11 @@ impl Default for SynthNodeCfg {
     fn default() -> Self {
         let ip_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
         Self {
-            generate_new_keys: true,
-            handshake: Some(Default::default()),
+            keys: NodeKeys::Random,
+            handshake: Default::default(),
+            mode: Default::default(),
+            capture_path: None,
+            shaping: Default::default(),
             pea2pea_config: pea2pea::Config {
                 listener_ip: Some(ip_addr),
                 ..Default::default()
@@ -6,8 +6,21 @@ pub const EXPECTED_RESULT_TIMEOUT: Duration = Duration::from_secs(20);
 /// Channel buffer bound for [InnerNode](crate::tools::inner_node::InnerNode) -> [SyntheticNode](crate::tools::synth_node::SyntheticNode) messages.
 pub const SYNTH_NODE_QUEUE_DEPTH: usize = 100;
 
+/// Channel buffer bound for [ConnectionEvent](crate::tools::events::ConnectionEvent)s emitted by the [InnerNode](crate::tools::inner_node::InnerNode).
+pub const SYNTH_NODE_EVENT_QUEUE_DEPTH: usize = 100;
+
+/// Channel buffer bound for the writes held back by the [PeerStream](crate::tools::stream::PeerStream)'s shaping.
+pub const PEER_STREAM_WRITE_QUEUE_DEPTH: usize = 64;
+
+/// How long a dropped [PeerStream](crate::tools::stream::PeerStream) keeps sending the bytes
+/// which are already due, in case the peer stopped reading.
+pub const PEER_STREAM_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Ripple's genesis account. This is an account that holds all XRP when rippled starts from scratch.
 pub const GENESIS_ACCOUNT: &str = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh";
 
+/// The well-known secret of the [GENESIS_ACCOUNT].
+pub const GENESIS_SEED: &str = "snoPBrXtMeMyMHUVTgbuqAfg1SUTb";
+
 /// A random but valid account that will be created in tests/setup by sending XRP from the GENESIS_ACCOUNT.
 pub const TEST_ACCOUNT: &str = "rNGknFCRBZguXcPqC63k6xTZnonSe6ZuWt";
@@ -116,7 +116,9 @@ fn get_ports_to_try(from_response: Option<u16>) -> HashSet<u16> {
 
 async fn try_handshake(addr: SocketAddr, known_network: Arc<KnownNetwork>) {
     let (sender, _receiver) = tokio::sync::mpsc::channel(1024);
-    let node = InnerNode::new(&Default::default(), sender).await;
+    let node = InnerNode::new(&Default::default(), sender)
+        .await
+        .expect("the default node config is valid");
     node.enable_handshake().await;
 
     let result = node.connect(addr).await.is_ok();
@@ -0,0 +1,236 @@
+//! Connection lifecycle events emitted by the [InnerNode](crate::tools::inner_node::InnerNode).
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use pea2pea::ConnectionSide;
+use tokio::sync::broadcast;
+
+use crate::tools::constants::SYNTH_NODE_EVENT_QUEUE_DEPTH;
+
+/// A single event in the lifetime of a connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionEvent {
+    /// The address of the peer.
+    pub addr: SocketAddr,
+    /// The time at which the event was observed.
+    pub timestamp: Instant,
+    /// What happened.
+    pub kind: ConnectionEventKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConnectionEventKind {
+    /// A TCP connection was established. The side is our own side of the connection.
+    Connected(ConnectionSide),
+    /// The handshake was completed successfully.
+    HandshakeCompleted(PeerInfo),
+    /// The connection was closed.
+    Disconnected(DisconnectReason),
+}
+
+/// Information presented by the peer during the handshake.
+#[derive(Debug, Clone, Default)]
+pub struct PeerInfo {
+    /// The 'Public-Key' header.
+    pub public_key: Option<String>,
+    /// The 'User-Agent' or 'Server' header, depending on the peer's side.
+    pub ident: Option<String>,
+    /// The 'Upgrade' header.
+    pub upgrade: Option<String>,
+    /// The 'Network-Time' header.
+    pub network_time: Option<String>,
+    /// The 'Closed-Ledger' header.
+    pub closed_ledger: Option<String>,
+    /// The 'Previous-Ledger' header.
+    pub prev_ledger: Option<String>,
+    /// All headers in the order they were received.
+    pub headers: Vec<(String, String)>,
+}
+
+impl PeerInfo {
+    /// Builds the peer information out of raw HTTP headers.
+    pub fn from_headers(headers: Vec<(String, String)>) -> Self {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+
+        Self {
+            public_key: find("Public-Key"),
+            ident: find("Server").or_else(|| find("User-Agent")),
+            upgrade: find("Upgrade"),
+            network_time: find("Network-Time"),
+            closed_ledger: find("Closed-Ledger"),
+            prev_ledger: find("Previous-Ledger"),
+            headers,
+        }
+    }
+}
+
+/// The reason a connection was closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The peer closed the stream.
+    Eof,
+    /// The stream failed with an I/O error, e.g. it was reset by the peer.
+    Io(io::ErrorKind),
+    /// An inbound frame couldn't be decoded.
+    Codec(String),
+    /// The handshake couldn't be completed.
+    Handshake(String),
+    /// The connection was closed by us, either explicitly or during the shutdown.
+    Local,
+    /// The reason couldn't be determined.
+    Unknown,
+}
+
+impl DisconnectReason {
+    /// Returns true if the connection was closed by the peer.
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::Eof | Self::Io(_))
+    }
+}
+
+/// Collects connection events and the reasons behind disconnects.
+#[derive(Clone)]
+pub struct ConnectionEvents {
+    sender: broadcast::Sender<ConnectionEvent>,
+    // The first observed reason for each connection that is about to close.
+    reasons: Arc<Mutex<HashMap<SocketAddr, DisconnectReason>>>,
+}
+
+impl Default for ConnectionEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(SYNTH_NODE_EVENT_QUEUE_DEPTH);
+
+        Self {
+            sender,
+            reasons: Default::default(),
+        }
+    }
+}
+
+impl ConnectionEvents {
+    /// Creates a new receiver for all events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Emits an event for the given peer.
+    pub fn emit(&self, addr: SocketAddr, kind: ConnectionEventKind) {
+        if let ConnectionEventKind::Connected(_) = kind {
+            // Forget any stale reason left over from a previous connection to the same address.
+            self.reasons.lock().unwrap().remove(&addr);
+        }
+
+        // An error only means there are no subscribers at the moment.
+        let _ = self.sender.send(ConnectionEvent {
+            addr,
+            timestamp: Instant::now(),
+            kind,
+        });
+    }
+
+    /// Records why the connection is about to close. Only the first reason is kept.
+    pub fn set_reason(&self, addr: SocketAddr, reason: DisconnectReason) {
+        self.reasons.lock().unwrap().entry(addr).or_insert(reason);
+    }
+
+    /// Removes and returns the recorded reason for the connection.
+    pub fn take_reason(&self, addr: SocketAddr) -> DisconnectReason {
+        self.reasons
+            .lock()
+            .unwrap()
+            .remove(&addr)
+            .unwrap_or(DisconnectReason::Unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::tools::{
+        synth_node::SyntheticNode,
+        testing::{mock, TIMEOUT},
+    };
+
+    #[tokio::test]
+    async fn handshake_and_local_disconnect() {
+        let mock = mock().await;
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+
+        let event = synth_node.recv_event_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(event.addr, mock.addr());
+        assert!(matches!(
+            event.kind,
+            ConnectionEventKind::Connected(ConnectionSide::Initiator)
+        ));
+
+        let event = synth_node.recv_event_timeout(TIMEOUT).await.unwrap();
+        assert_eq!(event.addr, mock.addr());
+        match event.kind {
+            ConnectionEventKind::HandshakeCompleted(info) => {
+                assert!(info.public_key.is_some());
+                assert_eq!(info.ident.as_deref(), Some("rippled-1.9.4"));
+                assert!(!info.headers.is_empty());
+            }
+            kind => panic!("unexpected event: {kind:?}"),
+        }
+
+        assert!(synth_node.disconnect(mock.addr()).await);
+        assert_eq!(
+            synth_node
+                .wait_for_disconnect(mock.addr(), TIMEOUT)
+                .await
+                .unwrap(),
+            DisconnectReason::Local
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_disconnect() {
+        let mock = mock().await;
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+
+        mock.shut_down().await;
+        let reason = synth_node
+            .wait_for_disconnect(mock.addr(), TIMEOUT)
+            .await
+            .unwrap();
+        assert!(reason.is_remote(), "unexpected reason: {reason:?}");
+    }
+
+    #[tokio::test]
+    async fn handshake_failure() {
+        // A peer which closes every connection right away.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        assert!(synth_node.connect(addr).await.is_err());
+
+        let reason = synth_node.wait_for_disconnect(addr, TIMEOUT).await.unwrap();
+        assert!(
+            matches!(reason, DisconnectReason::Handshake(_)),
+            "unexpected reason: {reason:?}"
+        );
+        assert!(!synth_node.is_connected(addr));
+    }
+}
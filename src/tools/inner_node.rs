@@ -4,18 +4,25 @@ use std::{
     sync::Arc,
 };
 
+use futures_util::future::join_all;
 use openssl::ssl::{SslAcceptor, SslConnector, SslMethod, SslVerifyMode};
 use pea2pea::{Node, Pea2Pea};
-use secp256k1::{
-    constants::{PUBLIC_KEY_SIZE, SECRET_KEY_SIZE},
-    PublicKey, Secp256k1, SecretKey,
-};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
 use tokio::{net::TcpSocket, sync::mpsc::Sender};
 
 use crate::{
-    protocol::{codecs::message::BinaryMessage, handshake::HandshakeCfg},
-    setup::constants::{SYNTHETIC_NODE_PRIVATE_KEY, SYNTHETIC_NODE_PUBLIC_KEY},
-    tools::{config::SynthNodeCfg, tls_cert},
+    protocol::{
+        codecs::message::BinaryMessage,
+        handshake::{ConnectionMode, HandshakeCfg},
+    },
+    tools::{
+        capture::Recorder,
+        config::SynthNodeCfg,
+        events::{ConnectionEvents, DisconnectReason},
+        shaping::Shapers,
+        tls_cert,
+        traffic::TrafficStats,
+    },
 };
 
 // A synthetic node adhering to Ripple's network protocol.
@@ -25,7 +32,12 @@ pub struct InnerNode {
     pub(crate) sender: Sender<(SocketAddr, BinaryMessage)>,
     pub crypto: Arc<Crypto>,
     pub tls: Tls,
-    pub handshake_cfg: Option<HandshakeCfg>,
+    pub handshake_cfg: HandshakeCfg,
+    pub mode: ConnectionMode,
+    pub events: ConnectionEvents,
+    pub stats: TrafficStats,
+    pub recorder: Option<Recorder>,
+    pub shaping: Shapers,
 }
 
 // An object containing TLS handlers.
@@ -49,15 +61,14 @@ impl Pea2Pea for InnerNode {
 }
 
 impl InnerNode {
-    pub async fn new(cfg: &SynthNodeCfg, sender: Sender<(SocketAddr, BinaryMessage)>) -> Self {
+    pub async fn new(
+        cfg: &SynthNodeCfg,
+        sender: Sender<(SocketAddr, BinaryMessage)>,
+    ) -> io::Result<Self> {
         // generate the keypair and prepare the crypto engine
 
         let engine = Secp256k1::new();
-        let (private_key, public_key) = if cfg.generate_new_keys {
-            engine.generate_keypair(&mut secp256k1::rand::thread_rng())
-        } else {
-            decode_predefined_keys().expect("invalid predefined keys")
-        };
+        let (private_key, public_key) = cfg.keys.keypair()?;
         let crypto = Arc::new(Crypto {
             engine,
             private_key,
@@ -79,8 +90,16 @@ impl InnerNode {
         connector.set_verify(SslVerifyMode::NONE); // we might remove it once the keypair is solid
         let connector = connector.build();
 
+        // wire traffic recorder
+
+        let recorder = cfg
+            .capture_path
+            .as_deref()
+            .map(Recorder::create)
+            .transpose()?;
+
         // the node
-        Self {
+        Ok(Self {
             node: Node::new(cfg.pea2pea_config.clone()),
             sender,
             crypto,
@@ -89,7 +108,12 @@ impl InnerNode {
                 connector,
             },
             handshake_cfg: cfg.handshake.clone(),
-        }
+            mode: cfg.mode,
+            events: Default::default(),
+            stats: Default::default(),
+            recorder,
+            shaping: Shapers::new(cfg.shaping),
+        })
     }
 
     pub fn is_connected_ip(&self, ip: IpAddr) -> bool {
@@ -101,40 +125,49 @@ impl InnerNode {
 
     /// Connects to the target address.
     pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
-        self.node.connect(target).await?;
-        Ok(())
+        let result = self.node.connect(target).await;
+        self.forget_failed(target, &result);
+        result
+    }
+
+    /// Connects to all the target addresses concurrently.
+    ///
+    /// Returns the connection result for every address.
+    pub async fn connect_all(&self, targets: &[SocketAddr]) -> Vec<(SocketAddr, io::Result<()>)> {
+        join_all(
+            targets
+                .iter()
+                .map(|&target| async move { (target, self.connect(target).await) }),
+        )
+        .await
     }
 
     /// Connects to the target address.
     pub async fn connect_from(&self, target: SocketAddr, socket: TcpSocket) -> io::Result<()> {
-        self.node.connect_using_socket(target, socket).await
+        let result = self.node.connect_using_socket(target, socket).await;
+        self.forget_failed(target, &result);
+        result
+    }
+
+    // Forgets the shaping set for a target which couldn't be connected to, unless the
+    // connection already exists.
+    fn forget_failed(&self, target: SocketAddr, result: &io::Result<()>) {
+        if matches!(result, Err(e) if e.kind() != io::ErrorKind::AlreadyExists) {
+            self.shaping.detach(target);
+        }
+    }
+
+    /// Disconnects from the target address.
+    pub async fn disconnect(&self, target: SocketAddr) -> bool {
+        self.events.set_reason(target, DisconnectReason::Local);
+        self.node.disconnect(target).await
     }
 
     /// Gracefully shuts down the node.
     pub async fn shut_down(&self) {
+        for addr in self.node.connected_addrs() {
+            self.events.set_reason(addr, DisconnectReason::Local);
+        }
         self.node.shut_down().await
     }
 }
-
-fn decode_to_vec(base58str: &str, size: usize) -> bs58::decode::Result<Vec<u8>> {
-    let mut bytes = bs58::decode(base58str)
-        .with_alphabet(bs58::Alphabet::RIPPLE)
-        .into_vec()?;
-    // Remove the first byte as it's an extra byte added before serialization to distinguish
-    // hashes of different things (i.e. public/private keys, accounts, transactions and so on).
-    bytes.remove(0);
-    bytes.truncate(size);
-    Ok(bytes)
-}
-
-fn decode_predefined_keys() -> Result<(SecretKey, PublicKey), secp256k1::Error> {
-    let bytes = decode_to_vec(SYNTHETIC_NODE_PRIVATE_KEY, SECRET_KEY_SIZE)
-        .expect("unable to decode the private key");
-    let private_key = SecretKey::from_slice(bytes.as_slice())?;
-
-    let bytes = decode_to_vec(SYNTHETIC_NODE_PUBLIC_KEY, PUBLIC_KEY_SIZE)
-        .expect("unable to decode the public key");
-    let public_key = PublicKey::from_slice(bytes.as_slice())?;
-
-    Ok((private_key, public_key))
-}
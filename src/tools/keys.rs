@@ -0,0 +1,299 @@
+//! Node identity keys used by the [SyntheticNode](crate::tools::synth_node::SyntheticNode).
+//!
+//! Keys can be imported in the same base58 formats rippled uses:
+//!  - a node seed (starts with 's'), as found in the `[node_seed]` section of `rippled.cfg`,
+//!  - a node private key (starts with 'p'),
+//!  - a node public key (starts with 'n') is used to identify the node.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use secp256k1::{
+    constants::{PUBLIC_KEY_SIZE, SECRET_KEY_SIZE},
+    PublicKey, Secp256k1, SecretKey,
+};
+use sha2::{Digest, Sha512};
+
+use crate::setup::constants::{SYNTHETIC_NODE_PRIVATE_KEY, SYNTHETIC_NODE_PUBLIC_KEY};
+
+/// Token type prefix for node public keys.
+const TOKEN_NODE_PUBLIC: u8 = 28;
+/// Token type prefix for node private keys.
+const TOKEN_NODE_PRIVATE: u8 = 32;
+/// Token type prefix for seeds.
+const TOKEN_FAMILY_SEED: u8 = 33;
+
+/// The size of a seed in bytes.
+const SEED_SIZE: usize = 16;
+
+/// The source of the node's identity keys.
+#[derive(Clone, Debug, Default)]
+pub enum NodeKeys {
+    /// A new random keypair.
+    #[default]
+    Random,
+    /// The predefined keypair ([SYNTHETIC_NODE_PRIVATE_KEY] and [SYNTHETIC_NODE_PUBLIC_KEY]).
+    Predefined,
+    /// Keys derived from a base58-encoded node seed, the same way rippled derives them from `[node_seed]`.
+    Seed(String),
+    /// A base58-encoded node private key.
+    PrivateKey(String),
+    /// A file which contains either a base58-encoded node seed or private key.
+    File(PathBuf),
+    /// The keypair with the given index in a deterministic set of keys.
+    ///
+    /// The same set and index always result in the same keys.
+    Indexed { set: u64, index: u32 },
+}
+
+impl NodeKeys {
+    /// Returns the n-th keypair of the default deterministic set.
+    pub fn indexed(index: u32) -> Self {
+        Self::Indexed { set: 0, index }
+    }
+
+    /// Uses the keys stored in the given file. If the file doesn't exist, a new random
+    /// private key is generated and saved there first, so the identity survives between runs.
+    pub fn persistent(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+
+        if !path.exists() {
+            let (private_key, _) =
+                Secp256k1::new().generate_keypair(&mut secp256k1::rand::thread_rng());
+            save_private_key(&path, &private_key)?;
+        }
+
+        Ok(Self::File(path))
+    }
+
+    /// Resolves the keypair.
+    pub fn keypair(&self) -> io::Result<(SecretKey, PublicKey)> {
+        let engine = Secp256k1::new();
+
+        let private_key = match self {
+            Self::Random => {
+                return Ok(engine.generate_keypair(&mut secp256k1::rand::thread_rng()));
+            }
+            Self::Predefined => return decode_predefined_keys(),
+            Self::Seed(seed) => private_key_from_seed(&decode_seed(seed)?),
+            Self::PrivateKey(key) => decode_private_key(key)?,
+            Self::File(path) => {
+                let contents = fs::read_to_string(path)?;
+                let token = contents
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .ok_or_else(|| invalid_data(format!("no key found in {path:?}")))?;
+
+                if token.starts_with('s') {
+                    private_key_from_seed(&decode_seed(token)?)
+                } else {
+                    decode_private_key(token)?
+                }
+            }
+            Self::Indexed { set, index } => private_key_from_seed(&indexed_seed(*set, *index)),
+        };
+
+        Ok((private_key, private_key.public_key(&engine)))
+    }
+
+    /// Resolves the base58-encoded public key, as it's presented in the handshake.
+    ///
+    /// Useful for rippled config entries like `[cluster_nodes]`.
+    pub fn public_key_base58(&self) -> io::Result<String> {
+        let (_, public_key) = self.keypair()?;
+        Ok(encode_public_key(&public_key))
+    }
+}
+
+/// Encodes the node public key in the base58 format.
+pub fn encode_public_key(public_key: &PublicKey) -> String {
+    encode_token(TOKEN_NODE_PUBLIC, &public_key.serialize())
+}
+
+/// Encodes the node private key in the base58 format.
+pub fn encode_private_key(private_key: &SecretKey) -> String {
+    encode_token(TOKEN_NODE_PRIVATE, &private_key.secret_bytes())
+}
+
+/// Returns the base58-encoded node seed for the given index of a deterministic set.
+///
+/// The seed can be placed into the `[node_seed]` section of `rippled.cfg`.
+pub fn encode_indexed_seed(set: u64, index: u32) -> String {
+    encode_token(TOKEN_FAMILY_SEED, &indexed_seed(set, index))
+}
+
+/// Saves the base58-encoded private key to a file.
+pub fn save_private_key(path: &Path, private_key: &SecretKey) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, encode_private_key(private_key) + "\n")
+}
+
+fn encode_token(token_type: u8, bytes: &[u8]) -> String {
+    let mut payload = Vec::with_capacity(1 + bytes.len());
+    payload.push(token_type);
+    payload.extend_from_slice(bytes);
+
+    bs58::encode(payload)
+        .with_alphabet(bs58::Alphabet::RIPPLE)
+        .with_check()
+        .into_string()
+}
+
+fn decode_token(token_type: u8, token: &str, size: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = bs58::decode(token)
+        .with_alphabet(bs58::Alphabet::RIPPLE)
+        .with_check(Some(token_type))
+        .into_vec()
+        .map_err(|e| invalid_data(format!("invalid base58 token {token}: {e}")))?;
+
+    // Remove the first byte as it's an extra byte added before serialization to distinguish
+    // hashes of different things (i.e. public/private keys, accounts, transactions and so on).
+    bytes.remove(0);
+    if bytes.len() != size {
+        return Err(invalid_data(format!(
+            "unexpected token size {} for {token}, expected {size}",
+            bytes.len()
+        )));
+    }
+
+    Ok(bytes)
+}
+
+fn decode_seed(seed: &str) -> io::Result<[u8; SEED_SIZE]> {
+    let bytes = decode_token(TOKEN_FAMILY_SEED, seed, SEED_SIZE)?;
+
+    let mut seed = [0u8; SEED_SIZE];
+    seed.copy_from_slice(&bytes);
+    Ok(seed)
+}
+
+fn decode_private_key(key: &str) -> io::Result<SecretKey> {
+    let bytes = decode_token(TOKEN_NODE_PRIVATE, key, SECRET_KEY_SIZE)?;
+    SecretKey::from_slice(&bytes).map_err(|e| invalid_data(e.to_string()))
+}
+
+fn decode_predefined_keys() -> io::Result<(SecretKey, PublicKey)> {
+    let private_key = decode_private_key(SYNTHETIC_NODE_PRIVATE_KEY)?;
+
+    let bytes = decode_token(
+        TOKEN_NODE_PUBLIC,
+        SYNTHETIC_NODE_PUBLIC_KEY,
+        PUBLIC_KEY_SIZE,
+    )?;
+    let public_key = PublicKey::from_slice(&bytes).map_err(|e| invalid_data(e.to_string()))?;
+
+    Ok((private_key, public_key))
+}
+
+// Based on Ripple's `deriveDeterministicRootKey` (ripple/protocol/impl/SecretKey.cpp), which is
+// used for node identities.
+fn private_key_from_seed(seed: &[u8; SEED_SIZE]) -> SecretKey {
+    let mut buf = [0u8; SEED_SIZE + 4];
+    buf[..SEED_SIZE].copy_from_slice(seed);
+
+    for ordinal in 0u32.. {
+        buf[SEED_SIZE..].copy_from_slice(&ordinal.to_be_bytes());
+        let hash = Sha512::digest(buf);
+
+        // Only the first half of the hash is used; it's retried until it forms a valid key.
+        if let Ok(private_key) = SecretKey::from_slice(&hash[..SECRET_KEY_SIZE]) {
+            return private_key;
+        }
+    }
+
+    unreachable!("no valid private key could be derived from the seed");
+}
+
+// Derives a seed for the given index in the given set.
+fn indexed_seed(set: u64, index: u32) -> [u8; SEED_SIZE] {
+    let mut hasher = Sha512::new();
+    hasher.update(b"ziggurat-node-key");
+    hasher.update(set.to_be_bytes());
+    hasher.update(index.to_be_bytes());
+    let hash = hasher.finalize();
+
+    let mut seed = [0u8; SEED_SIZE];
+    seed.copy_from_slice(&hash[..SEED_SIZE]);
+    seed
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn predefined_keys_match() {
+        let private_key = decode_private_key(SYNTHETIC_NODE_PRIVATE_KEY).unwrap();
+        let (_, public_key) = NodeKeys::Predefined.keypair().unwrap();
+
+        assert_eq!(private_key.public_key(&Secp256k1::new()), public_key);
+        assert_eq!(encode_public_key(&public_key), SYNTHETIC_NODE_PUBLIC_KEY);
+        assert_eq!(
+            NodeKeys::PrivateKey(SYNTHETIC_NODE_PRIVATE_KEY.into())
+                .public_key_base58()
+                .unwrap(),
+            SYNTHETIC_NODE_PUBLIC_KEY
+        );
+    }
+
+    #[test]
+    fn seed_derivation_matches_rippled() {
+        // Taken from Ripple's `Seed_test` (src/test/protocol/Seed_test.cpp) for the "masterpassphrase" seed.
+        let (private_key, public_key) = NodeKeys::Seed("snoPBrXtMeMyMHUVTgbuqAfg1SUTb".into())
+            .keypair()
+            .unwrap();
+
+        assert_eq!(
+            encode_public_key(&public_key),
+            "n94a1u4jAz288pZLtw6yFWVbi89YamiC6JBXPVUj5zmExe5fTVg9"
+        );
+        assert_eq!(
+            encode_private_key(&private_key),
+            "pnen77YEeUd4fFKG7iycBWcwKpTaeFRkW2WFostaATy1DSupwXe"
+        );
+    }
+
+    #[test]
+    fn indexed_keys_are_deterministic() {
+        let first = NodeKeys::indexed(1).keypair().unwrap();
+        assert_eq!(first, NodeKeys::indexed(1).keypair().unwrap());
+        assert_ne!(first, NodeKeys::indexed(2).keypair().unwrap());
+
+        // The same keys can be imported via the corresponding seed.
+        let seed = NodeKeys::Seed(encode_indexed_seed(0, 1));
+        assert_eq!(first, seed.keypair().unwrap());
+    }
+
+    #[test]
+    fn persistent_keys_survive_reload() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("node_key.txt");
+
+        let keys = NodeKeys::persistent(&path).unwrap();
+        let first = keys.keypair().unwrap();
+        let second = NodeKeys::persistent(&path).unwrap().keypair().unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn invalid_tokens_are_rejected() {
+        assert!(NodeKeys::Seed(SYNTHETIC_NODE_PRIVATE_KEY.into())
+            .keypair()
+            .is_err());
+        assert!(NodeKeys::PrivateKey("pabEKa3CtSNR1VatkL9x".into())
+            .keypair()
+            .is_err());
+    }
+}
@@ -0,0 +1,721 @@
+//! A mock rippled peer built on top of the [InnerNode], used to test the framework itself
+//! without a rippled binary.
+//!
+//! The mock performs the regular handshake, answers pings and queries for its small fake
+//! ledger, announces new ledgers with status changes and validations, and serves `/crawl`
+//! requests on its peer port.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use pea2pea::{
+    protocols::{Disconnect, Handshake, Reading, Writing},
+    Connection, ConnectionSide, Node, Pea2Pea,
+};
+use sha2::{Digest, Sha512};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::interval,
+};
+use tokio_openssl::SslStream;
+use tracing::*;
+
+use crate::{
+    protocol::{
+        codecs::message::{BinaryMessage, Payload},
+        handshake::ConnectionMode,
+        proto::{
+            tm_ping::PingType, NodeEvent, NodeStatus, TmGetLedger, TmGetObjectByHash,
+            TmIndexedObject, TmLedgerData, TmLedgerInfoType, TmLedgerNode, TmPing,
+            TmProofPathRequest, TmProofPathResponse, TmReplyError, TmStatusChange, TmValidation,
+        },
+        writing::MessageOrBytes,
+    },
+    tools::{
+        config::SynthNodeCfg,
+        constants::SYNTH_NODE_QUEUE_DEPTH,
+        events::{ConnectionEvent, ConnectionEventKind, PeerInfo},
+        inner_node::InnerNode,
+        stream::PeerStream,
+    },
+};
+
+/// Ripple epoch starts at Jan-1-2000, it's the number of seconds since the UNIX epoch.
+const RIPPLE_EPOCH: u64 = 946684800;
+
+/// The number of fake account state objects in every ledger.
+const MOCK_LEDGER_OBJECTS: u32 = 4;
+
+/// The path rippled serves the crawl requests at, on the peer port.
+const CRAWL_PATH: &str = "/crawl";
+
+/// A small fake ledger served by the [MockRippled].
+#[derive(Debug, Clone)]
+pub struct MockLedger {
+    pub seq: u32,
+    pub hash: Vec<u8>,
+    pub parent_hash: Vec<u8>,
+    /// The fake ledger header, returned for basic ledger info requests.
+    pub header: Vec<u8>,
+    /// Fake account state objects by their keys (hashes).
+    pub objects: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MockLedger {
+    /// Creates a ledger with the given sequence number and parent.
+    pub fn new(seq: u32, parent_hash: Vec<u8>) -> Self {
+        let mut header = seq.to_be_bytes().to_vec();
+        header.extend_from_slice(&parent_hash);
+
+        let objects = (0..MOCK_LEDGER_OBJECTS)
+            .map(|index| {
+                let data = [seq.to_be_bytes(), index.to_be_bytes()].concat();
+                (sha512_half(&data), data)
+            })
+            .collect();
+
+        Self {
+            seq,
+            hash: sha512_half(&header),
+            parent_hash,
+            header,
+            objects,
+        }
+    }
+
+    /// Creates the ledger which follows this one.
+    pub fn next(&self) -> Self {
+        Self::new(self.seq + 1, self.hash.clone())
+    }
+
+    fn matches(&self, hash: Option<&[u8]>, seq: Option<u32>) -> bool {
+        hash.is_none_or(|hash| hash == self.hash) && seq.is_none_or(|seq| seq == self.seq)
+    }
+
+    // Answers the ledger data requests.
+    fn ledger_data(&self, request: &TmGetLedger) -> TmLedgerData {
+        let mut response = TmLedgerData {
+            ledger_hash: self.hash.clone(),
+            ledger_seq: self.seq,
+            r#type: request.itype,
+            nodes: vec![],
+            request_cookie: request.request_cookie.map(|cookie| cookie as u32),
+            error: None,
+        };
+
+        if !self.matches(request.ledger_hash.as_deref(), request.ledger_seq) {
+            response.error = Some(TmReplyError::ReNoLedger as i32);
+            return response;
+        }
+
+        match TmLedgerInfoType::from_i32(request.itype) {
+            Some(TmLedgerInfoType::LiBase) => response.nodes.push(TmLedgerNode {
+                nodedata: self.header.clone(),
+                nodeid: None,
+            }),
+            Some(TmLedgerInfoType::LiAsNode) => {
+                response.nodes = self
+                    .objects
+                    .iter()
+                    .map(|(key, data)| TmLedgerNode {
+                        nodedata: data.clone(),
+                        nodeid: Some(key.clone()),
+                    })
+                    .collect()
+            }
+            _ => response.error = Some(TmReplyError::ReNoNode as i32),
+        }
+
+        response
+    }
+
+    // Answers the object queries, only the known objects are returned.
+    fn objects_by_hash(&self, request: &TmGetObjectByHash) -> TmGetObjectByHash {
+        let objects = request
+            .objects
+            .iter()
+            .filter_map(|object| {
+                let hash = object.hash.as_ref()?;
+                let data = self.objects.get(hash)?;
+
+                Some(TmIndexedObject {
+                    hash: Some(hash.clone()),
+                    node_id: None,
+                    index: object.index.clone(),
+                    data: Some(data.clone()),
+                    ledger_seq: Some(self.seq),
+                })
+            })
+            .collect();
+
+        TmGetObjectByHash {
+            query: false,
+            objects,
+            ..request.clone()
+        }
+    }
+
+    // Answers the proof path requests; the path consists of the object itself.
+    fn proof_path(&self, request: &TmProofPathRequest) -> TmProofPathResponse {
+        let mut response = TmProofPathResponse {
+            key: request.key.clone(),
+            ledger_hash: request.ledger_hash.clone(),
+            r#type: request.r#type,
+            ledger_header: None,
+            path: vec![],
+            error: None,
+        };
+
+        if request.ledger_hash != self.hash {
+            response.error = Some(TmReplyError::ReNoLedger as i32);
+        } else if let Some(data) = self.objects.get(&request.key) {
+            response.ledger_header = Some(self.header.clone());
+            response.path = vec![data.clone()];
+        } else {
+            response.error = Some(TmReplyError::ReNoNode as i32);
+        }
+
+        response
+    }
+
+    // Announces the ledger.
+    fn status_change(&self) -> TmStatusChange {
+        TmStatusChange {
+            new_status: Some(NodeStatus::NsMonitoring as i32),
+            new_event: Some(NodeEvent::NeAcceptedLedger as i32),
+            ledger_seq: Some(self.seq),
+            ledger_hash: Some(self.hash.clone()),
+            ledger_hash_previous: Some(self.parent_hash.clone()),
+            network_time: Some(network_time()),
+            first_seq: Some(1),
+            last_seq: Some(self.seq),
+        }
+    }
+
+    // An unsigned validation which only carries the ledger sequence and hash.
+    fn validation(&self) -> TmValidation {
+        // The serialized sfLedgerSequence (UInt32, field 6) and sfLedgerHash (Hash256, field 1).
+        let mut validation = vec![0x26];
+        validation.extend_from_slice(&self.seq.to_be_bytes());
+        validation.push(0x51);
+        validation.extend_from_slice(&self.hash);
+
+        #[allow(deprecated)]
+        TmValidation {
+            validation,
+            checked_signature: None,
+            hops: None,
+        }
+    }
+}
+
+/// Configuration of the [MockRippled].
+#[derive(Clone)]
+pub struct MockRippledCfg {
+    /// Configuration of the underlying node.
+    pub node: SynthNodeCfg,
+    /// The sequence number of the first ledger.
+    pub ledger_seq: u32,
+    /// How often a new ledger is closed and announced to the peers. If not set, ledgers are
+    /// only closed with [MockRippled::close_ledger].
+    pub ledger_interval: Option<Duration>,
+    /// The version reported in the `/crawl` response.
+    pub build_version: String,
+}
+
+impl Default for MockRippledCfg {
+    fn default() -> Self {
+        Self {
+            node: Default::default(),
+            ledger_seq: 2,
+            ledger_interval: None,
+            build_version: "1.9.4".into(),
+        }
+    }
+}
+
+// A peer connected to the mock.
+struct MockPeer {
+    info: PeerInfo,
+    side: ConnectionSide,
+    since: Instant,
+}
+
+struct MockState {
+    ledger: MockLedger,
+    peers: HashMap<SocketAddr, MockPeer>,
+    started: Instant,
+}
+
+/// A rippled stand-in speaking the peer protocol.
+pub struct MockRippled {
+    inner: InnerNode,
+    state: Arc<Mutex<MockState>>,
+    addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockRippled {
+    /// Creates the mock and starts listening for peers.
+    pub async fn new(cfg: MockRippledCfg) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel(SYNTH_NODE_QUEUE_DEPTH);
+        let inner = InnerNode::new(&cfg.node, sender).await?;
+
+        let state = Arc::new(Mutex::new(MockState {
+            ledger: MockLedger::new(cfg.ledger_seq, vec![0; 32]),
+            peers: Default::default(),
+            started: Instant::now(),
+        }));
+
+        let events = inner.events.subscribe();
+        // Only the handshake differs from the synthetic nodes, the rest is handled as usual.
+        MockHandshake {
+            inner: inner.clone(),
+            state: state.clone(),
+            build_version: cfg.build_version.clone(),
+        }
+        .enable_handshake()
+        .await;
+        inner.enable_reading().await;
+        inner.enable_writing().await;
+        inner.enable_disconnect().await;
+        let addr = inner.node().start_listening().await?;
+
+        let mut tasks = vec![
+            tokio::spawn(track_peers(state.clone(), events)),
+            tokio::spawn(respond(inner.clone(), state.clone(), receiver)),
+        ];
+        if let Some(ledger_interval) = cfg.ledger_interval {
+            tasks.push(tokio::spawn(close_ledgers(
+                inner.clone(),
+                state.clone(),
+                ledger_interval,
+            )));
+        }
+
+        Ok(Self {
+            inner,
+            state,
+            addr,
+            tasks,
+        })
+    }
+
+    /// The address the mock listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The current ledger.
+    pub fn ledger(&self) -> MockLedger {
+        self.state.lock().unwrap().ledger.clone()
+    }
+
+    /// The addresses of the peers which completed the handshake.
+    pub fn peers(&self) -> Vec<SocketAddr> {
+        self.state.lock().unwrap().peers.keys().copied().collect()
+    }
+
+    /// Connects to a peer.
+    pub async fn connect(&self, addr: SocketAddr) -> io::Result<()> {
+        self.inner.connect(addr).await
+    }
+
+    /// Closes the current ledger and announces the new one to all the peers.
+    pub async fn close_ledger(&self) -> MockLedger {
+        close_ledger(&self.inner, &self.state).await
+    }
+
+    /// Gracefully shuts down the mock.
+    pub async fn shut_down(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        self.inner.shut_down().await
+    }
+}
+
+impl Drop for MockRippled {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Performs the regular handshake, except that the inbound connections requesting `/crawl`
+// are answered and closed like rippled does, instead of being upgraded.
+#[derive(Clone)]
+struct MockHandshake {
+    inner: InnerNode,
+    state: Arc<Mutex<MockState>>,
+    build_version: String,
+}
+
+impl Pea2Pea for MockHandshake {
+    fn node(&self) -> &Node {
+        self.inner.node()
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for MockHandshake {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        // Only the inbound connections can request `/crawl`.
+        if conn.side() == ConnectionSide::Responder || self.inner.mode != ConnectionMode::Full {
+            return self.inner.perform_handshake(conn).await;
+        }
+
+        let addr = conn.addr();
+        let inner = &self.inner;
+        let stream = PeerStream::new(
+            self.take_stream(&mut conn),
+            addr,
+            inner.events.clone(),
+            inner.shaping.attach(addr),
+        );
+
+        match self.accept(stream, addr).await {
+            Ok(Some((tls_stream, headers))) => {
+                inner.events.emit(
+                    addr,
+                    ConnectionEventKind::Connected(ConnectionSide::Responder),
+                );
+                inner.events.emit(
+                    addr,
+                    ConnectionEventKind::HandshakeCompleted(PeerInfo::from_headers(headers)),
+                );
+                self.return_stream(&mut conn, tls_stream);
+
+                Ok(conn)
+            }
+            // Neither a crawl request nor a failed handshake is a peer, so no events are emitted.
+            result => {
+                let _ = inner.events.take_reason(addr);
+                inner.shaping.detach(addr);
+
+                match result {
+                    Err(e) => Err(e),
+                    _ => Err(io::Error::other("served a crawl request")),
+                }
+            }
+        }
+    }
+}
+
+impl MockHandshake {
+    // Returns the upgraded stream with the peer's headers, or none if a crawl request was served.
+    async fn accept(
+        &self,
+        stream: PeerStream<TcpStream>,
+        addr: SocketAddr,
+    ) -> io::Result<Option<(SslStream<PeerStream<TcpStream>>, Vec<(String, String)>)>> {
+        let mut tls_stream = self
+            .inner
+            .tls_handshake(stream, ConnectionSide::Responder)
+            .await?;
+        let (path, headers) = self
+            .inner
+            .read_upgrade_request(&mut tls_stream, addr)
+            .await?;
+
+        if path.as_deref() == Some(CRAWL_PATH) {
+            let body = crawl_response(&self.state.lock().unwrap(), &self.build_version);
+            let rsp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            tls_stream.write_all(rsp.as_bytes()).await?;
+            tls_stream.shutdown().await?;
+
+            return Ok(None);
+        }
+
+        self.inner.accept_upgrade(&mut tls_stream, addr).await?;
+        Ok(Some((tls_stream, headers)))
+    }
+}
+
+// Keeps track of the connected peers for the crawl responses.
+async fn track_peers(
+    state: Arc<Mutex<MockState>>,
+    mut events: broadcast::Receiver<ConnectionEvent>,
+) {
+    let mut sides = HashMap::new();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        match event.kind {
+            ConnectionEventKind::Connected(side) => {
+                sides.insert(event.addr, side);
+            }
+            ConnectionEventKind::HandshakeCompleted(info) => {
+                let side = sides
+                    .remove(&event.addr)
+                    .unwrap_or(ConnectionSide::Responder);
+                state.lock().unwrap().peers.insert(
+                    event.addr,
+                    MockPeer {
+                        info,
+                        side,
+                        since: event.timestamp,
+                    },
+                );
+            }
+            ConnectionEventKind::Disconnected(_) => {
+                sides.remove(&event.addr);
+                state.lock().unwrap().peers.remove(&event.addr);
+            }
+        }
+    }
+}
+
+// Answers the inbound messages.
+async fn respond(
+    inner: InnerNode,
+    state: Arc<Mutex<MockState>>,
+    mut receiver: mpsc::Receiver<(SocketAddr, BinaryMessage)>,
+) {
+    while let Some((source, message)) = receiver.recv().await {
+        let response = {
+            let state = state.lock().unwrap();
+            let ledger = &state.ledger;
+
+            match &message.payload {
+                Payload::TmPing(ping) if ping.r#type == PingType::PtPing as i32 => {
+                    Some(Payload::TmPing(TmPing {
+                        r#type: PingType::PtPong as i32,
+                        ..ping.clone()
+                    }))
+                }
+                Payload::TmGetLedger(request) => {
+                    Some(Payload::TmLedgerData(ledger.ledger_data(request)))
+                }
+                Payload::TmGetObjectByHash(request) if request.query => {
+                    Some(Payload::TmGetObjectByHash(ledger.objects_by_hash(request)))
+                }
+                Payload::TmProofPathRequest(request) => {
+                    Some(Payload::TmProofPathResponse(ledger.proof_path(request)))
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(response) = response {
+            if let Err(e) = inner.unicast(source, MessageOrBytes::Payload(response)) {
+                warn!(parent: inner.node().span(), "couldn't respond to {source}: {e}");
+            }
+        }
+    }
+}
+
+// Periodically closes a ledger.
+async fn close_ledgers(inner: InnerNode, state: Arc<Mutex<MockState>>, period: Duration) {
+    let mut interval = interval(period);
+    // The first tick completes immediately.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        close_ledger(&inner, &state).await;
+    }
+}
+
+async fn close_ledger(inner: &InnerNode, state: &Mutex<MockState>) -> MockLedger {
+    let ledger = {
+        let mut state = state.lock().unwrap();
+        state.ledger = state.ledger.next();
+        state.ledger.clone()
+    };
+
+    let addrs = inner.node().connected_addrs();
+    let status = MessageOrBytes::Payload(Payload::TmStatusChange(ledger.status_change()));
+    let validation = MessageOrBytes::Payload(Payload::TmValidation(ledger.validation()));
+    for message in [status, validation] {
+        for (addr, result) in inner.multicast(&addrs, message).await {
+            if let Err(e) = result {
+                warn!(parent: inner.node().span(), "couldn't announce the ledger to {addr}: {e}");
+            }
+        }
+    }
+
+    ledger
+}
+
+// Builds the `/crawl` response body, in the same format as rippled does.
+fn crawl_response(state: &MockState, build_version: &str) -> String {
+    let active = state
+        .peers
+        .iter()
+        .map(|(addr, peer)| {
+            serde_json::json!({
+                "ip": addr.ip().to_string(),
+                "port": addr.port(),
+                "public_key": peer.info.public_key.clone().unwrap_or_default(),
+                "type": match peer.side {
+                    ConnectionSide::Initiator => "out",
+                    ConnectionSide::Responder => "in",
+                },
+                "uptime": peer.since.elapsed().as_secs(),
+                "version": peer.info.ident.clone().unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "overlay": { "active": active },
+        "server": {
+            "build_version": build_version,
+            "server_state": "full",
+            "uptime": state.started.elapsed().as_secs(),
+            "complete_ledgers": format!("1-{}", state.ledger.seq),
+        },
+    })
+    .to_string()
+}
+
+// The current time in seconds since the Ripple epoch.
+fn network_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        - RIPPLE_EPOCH
+}
+
+fn sha512_half(data: &[u8]) -> Vec<u8> {
+    Sha512::digest(data)[..32].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+
+    use super::*;
+    use crate::{
+        protocol::proto::TmLedgerMapType,
+        tools::{
+            crawl::get_crawl_response,
+            synth_node::SyntheticNode,
+            testing::{is_pong, mock, ping, TIMEOUT},
+        },
+    };
+
+    #[tokio::test]
+    async fn handshake_and_queries() {
+        let mock = mock().await;
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+
+        // Ping.
+        synth_node.unicast(mock.addr(), ping(1)).unwrap();
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert!(is_pong(&message.payload, 1));
+
+        // Basic ledger info.
+        let ledger = mock.ledger();
+        let request = Payload::TmGetLedger(TmGetLedger {
+            itype: TmLedgerInfoType::LiBase as i32,
+            ..Default::default()
+        });
+        synth_node.unicast(mock.addr(), request).unwrap();
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        match message.payload {
+            Payload::TmLedgerData(data) => {
+                assert_eq!(data.ledger_hash, ledger.hash);
+                assert_eq!(data.nodes[0].nodedata, ledger.header);
+            }
+            payload => panic!("unexpected message: {payload:?}"),
+        }
+
+        // Proof path of a known object.
+        let key = ledger.objects.keys().next().unwrap().clone();
+        let request = Payload::TmProofPathRequest(TmProofPathRequest {
+            key: key.clone(),
+            ledger_hash: ledger.hash.clone(),
+            r#type: TmLedgerMapType::LmAccountState as i32,
+        });
+        synth_node.unicast(mock.addr(), request).unwrap();
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        match message.payload {
+            Payload::TmProofPathResponse(response) => {
+                assert!(response.error.is_none());
+                assert_eq!(response.path, vec![ledger.objects[&key].clone()]);
+            }
+            payload => panic!("unexpected message: {payload:?}"),
+        }
+
+        synth_node.shut_down().await;
+        mock.shut_down().await;
+    }
+
+    #[tokio::test]
+    async fn ledgers_are_announced() {
+        let mock = mock().await;
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+
+        let ledger = mock.close_ledger().await;
+
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert!(matches!(
+            message.payload,
+            Payload::TmStatusChange(TmStatusChange { ledger_seq: Some(seq), .. }) if seq == ledger.seq
+        ));
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert!(matches!(message.payload, Payload::TmValidation(..)));
+
+        synth_node.shut_down().await;
+        mock.shut_down().await;
+    }
+
+    #[tokio::test]
+    async fn crawl_lists_peers() {
+        let mock = mock().await;
+        let synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+        crate::wait_until!(TIMEOUT, mock.peers().len() == 1);
+        let peer = mock.peers()[0];
+
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let mut events = mock.inner.events.subscribe();
+        let (response, _) = get_crawl_response(client, mock.addr()).await.unwrap();
+        // The crawler isn't a peer, so the next event already comes from the shutdown of the
+        // synthetic node.
+        synth_node.shut_down().await;
+        let event = tokio::time::timeout(TIMEOUT, events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.addr, peer);
+        assert!(
+            matches!(event.kind, ConnectionEventKind::Disconnected(_)),
+            "unexpected event: {event:?}"
+        );
+
+        assert_eq!(response.server.build_version, "1.9.4");
+        assert_eq!(response.peerlist.active.len(), 1);
+        assert_eq!(
+            response.peerlist.active[0].public_key,
+            synth_node.public_key()
+        );
+
+        mock.shut_down().await;
+    }
+}
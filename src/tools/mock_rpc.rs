@@ -0,0 +1,472 @@
+//! A mock of rippled's JSON-RPC admin API, used to test [rpc](crate::tools::rpc) without a node.
+//!
+//! The mock implements the `server_info`, `account_info`, `ledger`, `tx`, `submit` and
+//! `wallet_propose` methods over a scriptable state, and can inject errors and malformed responses.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tracing::*;
+
+/// The maximum size of a request accepted by the mock.
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// A failure returned instead of the regular response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcFault {
+    /// An HTTP error status, e.g. 503.
+    HttpStatus(u16),
+    /// A rippled error response with the given error code, e.g. `noNetwork`.
+    Error(String),
+    /// A response body which isn't valid JSON.
+    MalformedJson,
+}
+
+// An account known to the mock.
+struct Account {
+    balance: String,
+    previous_txn: String,
+    // The number of `account_info` calls answered with `actNotFound` before the account appears.
+    hidden_for: u32,
+}
+
+// A validated ledger known to the mock.
+struct Ledger {
+    hash: String,
+    index: u32,
+    account_state: Vec<String>,
+}
+
+#[derive(Default)]
+struct State {
+    server_states: VecDeque<String>,
+    accounts: HashMap<String, Account>,
+    ledger: Option<Ledger>,
+    transactions: HashSet<String>,
+    faults: HashMap<String, VecDeque<RpcFault>>,
+    calls: HashMap<String, u32>,
+}
+
+impl State {
+    // Builds the response to a request; returns the HTTP status along with the body.
+    fn respond(&mut self, request: &Value) -> (u16, String) {
+        let method = request["method"].as_str().unwrap_or_default().to_owned();
+        let params = &request["params"][0];
+        *self.calls.entry(method.clone()).or_default() += 1;
+
+        if let Some(fault) = self
+            .faults
+            .get_mut(&method)
+            .and_then(|faults| faults.pop_front())
+        {
+            return match fault {
+                RpcFault::HttpStatus(status) => (status, String::new()),
+                RpcFault::Error(error) => (200, error_response(&error).to_string()),
+                RpcFault::MalformedJson => (200, r#"{"result": {"status": "success""#.into()),
+            };
+        }
+
+        let result = match method.as_str() {
+            "server_info" => self.server_info(),
+            "account_info" => self.account_info(params["account"].as_str().unwrap_or_default()),
+            "ledger" => self.ledger(),
+            "tx" => self.tx(params["transaction"].as_str().unwrap_or_default()),
+            "submit" => Ok(self.submit(params)),
+            "wallet_propose" => Ok(json!({
+                "account_id": format!("r{}", params["passphrase"].as_str().unwrap_or_default()),
+                "master_seed": "sMock",
+            })),
+            _ => Err("unknownCmd"),
+        };
+
+        match result {
+            Ok(mut result) => {
+                result["status"] = "success".into();
+                (200, json!({ "result": result }).to_string())
+            }
+            Err(error) => (200, error_response(error).to_string()),
+        }
+    }
+
+    fn server_info(&mut self) -> Result<Value, &'static str> {
+        // Every call moves on to the next state, the last one stays.
+        let state = if self.server_states.len() > 1 {
+            self.server_states.pop_front()
+        } else {
+            self.server_states.front().cloned()
+        };
+
+        Ok(json!({ "info": { "server_state": state.unwrap_or_else(|| "full".into()) } }))
+    }
+
+    fn account_info(&mut self, account: &str) -> Result<Value, &'static str> {
+        let account_data = match self.accounts.get_mut(account) {
+            Some(data) if data.hidden_for > 0 => {
+                data.hidden_for -= 1;
+                return Err("actNotFound");
+            }
+            Some(data) => data,
+            None => return Err("actNotFound"),
+        };
+
+        Ok(json!({
+            "account_data": {
+                "Account": account,
+                "Balance": account_data.balance,
+                "PreviousTxnID": account_data.previous_txn,
+                "Sequence": 1,
+            },
+            "validated": true,
+        }))
+    }
+
+    fn ledger(&self) -> Result<Value, &'static str> {
+        let ledger = self.ledger.as_ref().ok_or("lgrNotFound")?;
+
+        Ok(json!({
+            "ledger": {
+                "ledger_hash": ledger.hash,
+                "ledger_index": ledger.index.to_string(),
+                "accountState": ledger.account_state,
+            },
+            "validated": true,
+        }))
+    }
+
+    // Transactions signed by the node are validated right away; their sequence is filled in
+    // unless given.
+    fn submit(&mut self, params: &Value) -> Value {
+        let mut response = json!({
+            "accepted": true,
+            "applied": true,
+            "broadcast": true,
+            "engine_result": "tesSUCCESS",
+        });
+
+        if let Some(tx_json) = params.get("tx_json") {
+            let mut tx_json = tx_json.clone();
+            let hash = format!("{:064X}", self.transactions.len() + 1);
+            if tx_json.get("Sequence").is_none() {
+                tx_json["Sequence"] = 1.into();
+            }
+            tx_json["hash"] = hash.clone().into();
+            self.transactions.insert(hash);
+            response["tx_json"] = tx_json;
+        }
+
+        response
+    }
+
+    fn tx(&self, transaction: &str) -> Result<Value, &'static str> {
+        if !self.transactions.contains(transaction) {
+            return Err("txnNotFound");
+        }
+
+        Ok(json!({ "hash": transaction, "validated": true }))
+    }
+}
+
+// A rippled-style error response.
+fn error_response(error: &str) -> Value {
+    json!({
+        "result": {
+            "error": error,
+            "status": "error",
+        }
+    })
+}
+
+/// A local JSON-RPC server standing in for rippled's admin API.
+pub struct MockRpc {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockRpc {
+    /// Starts the server on a random local port.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, state).await {
+                            debug!("mock RPC connection failed: {e}");
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(Self { addr, state, task })
+    }
+
+    /// The URL to be passed to the [rpc](crate::tools::rpc) functions.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Sets the states reported by `server_info`. Each call reports the next state and
+    /// the last one is reported from then on.
+    pub fn set_server_states<S: Into<String>>(&self, states: impl IntoIterator<Item = S>) {
+        self.state.lock().unwrap().server_states = states.into_iter().map(Into::into).collect();
+    }
+
+    /// Adds an account which appears after the given number of `account_info` calls.
+    pub fn add_account(&self, account: &str, balance: &str, previous_txn: &str, after_calls: u32) {
+        self.state.lock().unwrap().accounts.insert(
+            account.into(),
+            Account {
+                balance: balance.into(),
+                previous_txn: previous_txn.into(),
+                hidden_for: after_calls,
+            },
+        );
+    }
+
+    /// Sets the validated ledger returned by the `ledger` method.
+    pub fn set_ledger(&self, hash: &str, index: u32, account_state: Vec<String>) {
+        self.state.lock().unwrap().ledger = Some(Ledger {
+            hash: hash.into(),
+            index,
+            account_state,
+        });
+    }
+
+    /// Adds a transaction known to the `tx` method.
+    pub fn add_transaction(&self, hash: &str) {
+        self.state.lock().unwrap().transactions.insert(hash.into());
+    }
+
+    /// Makes the next call of the method fail. Multiple faults are returned in order.
+    pub fn inject_fault(&self, method: &str, fault: RpcFault) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .entry(method.into())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// The number of times the method was called.
+    pub fn calls(&self, method: &str) -> u32 {
+        self.state
+            .lock()
+            .unwrap()
+            .calls
+            .get(method)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for MockRpc {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// Serves a single request and closes the connection.
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) -> io::Result<()> {
+    let mut buf = Vec::new();
+
+    let body = loop {
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut request = httparse::Request::new(&mut headers);
+        let header_len = match request
+            .parse(&buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        {
+            httparse::Status::Complete(header_len) => header_len,
+            httparse::Status::Partial => continue,
+        };
+
+        let content_len = request
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|header| {
+                std::str::from_utf8(header.value)
+                    .ok()?
+                    .parse::<usize>()
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        if buf.len() >= header_len + content_len {
+            break buf[header_len..header_len + content_len].to_vec();
+        }
+    };
+
+    let (status, body) = match serde_json::from_slice::<Value>(&body) {
+        Ok(request) => state.lock().unwrap().respond(&request),
+        Err(_) => (400, String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        if status == 200 { "OK" } else { "Error" },
+        body.len(),
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tools::rpc::{
+        get_transaction_info, sign_and_submit, submit_transaction, wait_for_account_data,
+        wait_for_ledger_info, wait_for_ledger_info_timeout, wait_for_state, wait_for_state_timeout,
+        wait_for_validated_transaction, wallet_propose,
+    };
+
+    const ACCOUNT: &str = "rNGknFCRBZguXcPqC63k6xTZnonSe6ZuWt";
+    const SHORT_TIMEOUT: Duration = Duration::from_millis(500);
+
+    #[tokio::test]
+    async fn server_state_transitions() {
+        let rpc = MockRpc::start().await.unwrap();
+        rpc.set_server_states(["connected", "syncing", "tracking", "full"]);
+        rpc.inject_fault("server_info", RpcFault::HttpStatus(503));
+
+        wait_for_state(&rpc.url(), "full".into()).await;
+        // The failed call, followed by all the states.
+        assert_eq!(rpc.calls("server_info"), 5);
+    }
+
+    #[tokio::test]
+    async fn server_state_timeout() {
+        let rpc = MockRpc::start().await.unwrap();
+        rpc.set_server_states(["syncing"]);
+
+        assert!(
+            wait_for_state_timeout(&rpc.url(), "full".into(), SHORT_TIMEOUT)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn account_appears_after_polls() {
+        let rpc = MockRpc::start().await.unwrap();
+        rpc.add_account(ACCOUNT, "1000", "ABCD", 2);
+
+        let account = wait_for_account_data(&rpc.url(), ACCOUNT, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(account.result.account_data.balance, "1000");
+        assert_eq!(rpc.calls("account_info"), 3);
+
+        // Unknown accounts never appear.
+        assert!(wait_for_account_data(&rpc.url(), "rUnknown", SHORT_TIMEOUT)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn ledger_info_survives_faults() {
+        let rpc = MockRpc::start().await.unwrap();
+        rpc.set_ledger("AB", 7, vec!["CD".into()]);
+        rpc.inject_fault("ledger", RpcFault::MalformedJson);
+        rpc.inject_fault("ledger", RpcFault::Error("noNetwork".into()));
+
+        let info = wait_for_ledger_info(&rpc.url()).await.unwrap();
+        assert_eq!(info.result.ledger.ledger_index, "7");
+        assert_eq!(info.result.ledger.account_state, vec!["CD"]);
+        assert_eq!(rpc.calls("ledger"), 3);
+    }
+
+    #[tokio::test]
+    async fn ledger_info_timeout() {
+        let rpc = MockRpc::start().await.unwrap();
+        // Without a validated ledger, every call fails with `lgrNotFound`.
+        assert!(wait_for_ledger_info_timeout(&rpc.url(), SHORT_TIMEOUT)
+            .await
+            .is_err());
+        assert!(rpc.calls("ledger") > 0);
+    }
+
+    #[tokio::test]
+    async fn transactions_and_submit() {
+        let rpc = MockRpc::start().await.unwrap();
+        rpc.add_transaction("AB");
+
+        assert!(get_transaction_info(&rpc.url(), "AB".into()).await.is_ok());
+        assert_eq!(rpc.calls("tx"), 1);
+
+        let response = submit_transaction(&rpc.url(), "00".into(), false)
+            .await
+            .unwrap();
+        assert!(response.result.accepted);
+    }
+
+    #[tokio::test]
+    async fn sign_and_submit_until_validated() {
+        let rpc = MockRpc::start().await.unwrap();
+
+        let wallet = wallet_propose(&rpc.url(), "alice").await.unwrap();
+        assert_eq!(wallet.result.account_id, "ralice");
+
+        let tx_json = json!({
+            "TransactionType": "Payment",
+            "Account": "rGenesis",
+            "Destination": wallet.result.account_id,
+            "Amount": "1000",
+            "Sequence": 7,
+        });
+        let response = sign_and_submit(&rpc.url(), "sSecret", tx_json)
+            .await
+            .unwrap();
+        assert_eq!(response.result.engine_result, "tesSUCCESS");
+        assert_eq!(response.result.tx_json.sequence, 7);
+
+        wait_for_validated_transaction(&rpc.url(), &response.result.tx_json.hash, SHORT_TIMEOUT)
+            .await
+            .unwrap();
+        assert!(
+            wait_for_validated_transaction(&rpc.url(), "UNKNOWN", SHORT_TIMEOUT)
+                .await
+                .is_err()
+        );
+
+        rpc.inject_fault("submit", RpcFault::Error("tooBusy".into()));
+        let error = sign_and_submit(&rpc.url(), "sSecret", json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "submit failed with tooBusy");
+    }
+}
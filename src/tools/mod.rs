@@ -1,5 +1,6 @@
 //! Utilities for network testing.
 
+pub mod capture;
 pub mod config;
 pub mod constants;
 // This mod belongs to the tools/crawler and we are using a sym
@@ -7,11 +8,22 @@ pub mod constants;
 // This is a workaround solution in this repo for this case,
 // in future Ziggurat repos, we will handle this differently.
 pub mod crawl;
+pub mod events;
 pub mod inner_node;
 pub mod ips;
+pub mod keys;
+pub mod mock_rippled;
+pub mod mock_rpc;
+pub mod replay;
 pub mod rpc;
+pub mod shaping;
+pub mod sntp;
+pub mod stream;
 pub mod synth_node;
+#[cfg(test)]
+pub(crate) mod testing;
 pub mod tls_cert;
+pub mod traffic;
 
 /// Waits until an expression is true or times out.
 ///
@@ -26,6 +38,7 @@ macro_rules! wait_until {
             }
 
             // Default timeout.
+            #[allow(unused_variables)]
             let sleep_duration = std::time::Duration::from_millis(10);
             // Set if present in args.
             $(let sleep_duration = $sleep_duration;)?
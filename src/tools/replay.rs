@@ -0,0 +1,329 @@
+//! Replaying of the outbound traffic recorded in a [capture](crate::tools::capture) file.
+
+use std::{collections::HashMap, io, net::SocketAddr, path::Path, time::Duration};
+
+use tokio::time::{sleep_until, Instant};
+
+use crate::{
+    protocol::{codecs::message::Payload, writing::MessageOrBytes},
+    tools::{
+        capture::{decode_frame, read_capture, CaptureRecord, RecordKind},
+        synth_node::SyntheticNode,
+        traffic::Direction,
+    },
+};
+
+/// How the time between the replayed messages is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Pacing {
+    /// The same time as in the capture.
+    #[default]
+    Original,
+    /// The time from the capture multiplied by the factor, e.g. `0.5` replays twice as fast.
+    ///
+    /// The factor must be finite and not negative, see [Pacing::scaled].
+    Scaled(f64),
+    /// All the messages are sent right away.
+    Immediate,
+}
+
+impl Pacing {
+    /// The scaled pacing, if the factor is finite and not negative.
+    pub fn scaled(factor: f64) -> io::Result<Self> {
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid pacing factor: {factor}"),
+            ));
+        }
+
+        Ok(Self::Scaled(factor))
+    }
+}
+
+/// Ledger hashes and sequence numbers replaced in the replayed messages.
+///
+/// Useful when the target node is on a different ledger than the one in the capture.
+/// Only plain protobuf fields are replaced; serialized objects, like validations, are left intact.
+#[derive(Debug, Clone, Default)]
+pub struct LedgerSubstitutions {
+    /// Ledger hashes to replace.
+    pub hashes: HashMap<Vec<u8>, Vec<u8>>,
+    /// Ledger sequence numbers to replace.
+    pub seqs: HashMap<u32, u32>,
+    /// Added to the ledger sequence numbers not found in `seqs`.
+    pub seq_offset: i64,
+}
+
+impl LedgerSubstitutions {
+    fn is_empty(&self) -> bool {
+        self.hashes.is_empty() && self.seqs.is_empty() && self.seq_offset == 0
+    }
+
+    fn hash(&self, hash: &mut Vec<u8>) {
+        if let Some(new_hash) = self.hashes.get(hash) {
+            *hash = new_hash.clone();
+        }
+    }
+
+    fn opt_hash(&self, hash: &mut Option<Vec<u8>>) {
+        if let Some(hash) = hash {
+            self.hash(hash);
+        }
+    }
+
+    fn seq(&self, seq: &mut u32) {
+        *seq = match self.seqs.get(seq) {
+            Some(new_seq) => *new_seq,
+            None => (*seq as i64 + self.seq_offset).clamp(0, u32::MAX as i64) as u32,
+        };
+    }
+
+    fn opt_seq(&self, seq: &mut Option<u32>) {
+        if let Some(seq) = seq {
+            self.seq(seq);
+        }
+    }
+
+    /// Applies the substitutions to the payload.
+    pub fn apply(&self, payload: &mut Payload) {
+        match payload {
+            Payload::TmStatusChange(status) => {
+                self.opt_seq(&mut status.ledger_seq);
+                self.opt_hash(&mut status.ledger_hash);
+                self.opt_hash(&mut status.ledger_hash_previous);
+            }
+            Payload::TmProposeLedger(proposal) => self.hash(&mut proposal.previousledger),
+            Payload::TmGetLedger(request) => {
+                self.opt_seq(&mut request.ledger_seq);
+                self.opt_hash(&mut request.ledger_hash);
+            }
+            Payload::TmLedgerData(data) => {
+                self.seq(&mut data.ledger_seq);
+                self.hash(&mut data.ledger_hash);
+            }
+            Payload::TmGetObjectByHash(request) => {
+                self.opt_hash(&mut request.ledger_hash);
+                for object in &mut request.objects {
+                    self.opt_seq(&mut object.ledger_seq);
+                }
+            }
+            Payload::TmProofPathRequest(request) => self.hash(&mut request.ledger_hash),
+            Payload::TmProofPathResponse(response) => self.hash(&mut response.ledger_hash),
+            Payload::TmReplayDeltaRequest(request) => self.hash(&mut request.ledger_hash),
+            Payload::TmReplayDeltaResponse(response) => self.hash(&mut response.ledger_hash),
+            _ => (),
+        }
+    }
+}
+
+// A message to replay, along with its time offset from the first one.
+struct ReplayFrame {
+    offset: Duration,
+    kind: RecordKind,
+    bytes: Vec<u8>,
+}
+
+/// Re-sends the outbound traffic from a capture to a target node.
+pub struct Replayer {
+    frames: Vec<ReplayFrame>,
+    pacing: Pacing,
+    substitutions: LedgerSubstitutions,
+}
+
+impl Replayer {
+    /// Reads the outbound messages from a capture file.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_records(&read_capture(path)?))
+    }
+
+    /// Uses the outbound messages from the given records.
+    ///
+    /// The records can be filtered beforehand, e.g. to replay the messages sent to a single peer.
+    pub fn from_records(records: &[CaptureRecord]) -> Self {
+        let mut outbound = records
+            .iter()
+            .filter(|record| record.direction == Direction::Outbound)
+            .peekable();
+
+        let start = outbound.peek().map(|record| record.timestamp);
+        let frames = outbound
+            .map(|record| ReplayFrame {
+                offset: start
+                    .and_then(|start| record.timestamp.duration_since(start).ok())
+                    .unwrap_or_default(),
+                kind: record.kind,
+                bytes: record.bytes.clone(),
+            })
+            .collect();
+
+        Self {
+            frames,
+            pacing: Default::default(),
+            substitutions: Default::default(),
+        }
+    }
+
+    pub fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn with_substitutions(mut self, substitutions: LedgerSubstitutions) -> Self {
+        self.substitutions = substitutions;
+        self
+    }
+
+    /// The number of messages to replay.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The messages to replay, with the substitutions applied, along with their delay from
+    /// the start of the replay.
+    ///
+    /// Frames are re-encoded only if there are substitutions to apply, otherwise they're sent
+    /// exactly as recorded.
+    pub fn messages(&self) -> io::Result<Vec<(Duration, MessageOrBytes)>> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let delay = match self.pacing {
+                    Pacing::Original => frame.offset,
+                    Pacing::Scaled(factor) => Duration::try_from_secs_f64(
+                        frame.offset.as_secs_f64() * factor,
+                    )
+                    .map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid pacing factor {factor}: {e}"),
+                        )
+                    })?,
+                    Pacing::Immediate => Duration::ZERO,
+                };
+
+                Ok((delay, self.message(frame)?))
+            })
+            .collect()
+    }
+
+    fn message(&self, frame: &ReplayFrame) -> io::Result<MessageOrBytes> {
+        if frame.kind == RecordKind::Raw || self.substitutions.is_empty() {
+            return Ok(MessageOrBytes::Bytes(frame.bytes.clone()));
+        }
+
+        let mut payload = decode_frame(&frame.bytes)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "incomplete frame"))?
+            .payload;
+        self.substitutions.apply(&mut payload);
+
+        Ok(MessageOrBytes::Payload(payload))
+    }
+
+    /// Sends the messages to the target through the given node, which must already be connected
+    /// to it.
+    ///
+    /// Returns the number of messages sent.
+    pub async fn replay(&self, node: &SyntheticNode, target: SocketAddr) -> io::Result<usize> {
+        let messages = self.messages()?;
+        let start = Instant::now();
+
+        for (delay, message) in &messages {
+            sleep_until(start + *delay).await;
+
+            let delivery = match message.clone() {
+                MessageOrBytes::Payload(payload) => node.unicast(target, payload)?,
+                MessageOrBytes::Bytes(bytes) => node.unicast_bytes(target, bytes)?,
+            };
+            delivery
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::ConnectionAborted.into()))?;
+        }
+
+        Ok(messages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use bytes::BytesMut;
+    use tokio_util::codec::Encoder;
+    use tracing::Span;
+
+    use super::*;
+    use crate::protocol::{codecs::message::MessageCodec, proto::TmStatusChange};
+
+    fn status_record(direction: Direction, millis: u64, ledger_seq: u32) -> CaptureRecord {
+        let status = Payload::TmStatusChange(TmStatusChange {
+            ledger_seq: Some(ledger_seq),
+            ledger_hash: Some(vec![1; 32]),
+            ..Default::default()
+        });
+
+        let mut bytes = BytesMut::new();
+        MessageCodec::new(Span::none())
+            .encode(status, &mut bytes)
+            .unwrap();
+
+        CaptureRecord {
+            direction,
+            kind: RecordKind::Frame,
+            timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+            peer: "127.0.0.1:51235".parse().unwrap(),
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn pacing_and_substitutions() {
+        let records = vec![
+            status_record(Direction::Outbound, 1000, 10),
+            status_record(Direction::Inbound, 1500, 10),
+            status_record(Direction::Outbound, 3000, 11),
+        ];
+
+        for factor in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(Pacing::scaled(factor).is_err());
+            let replayer = Replayer::from_records(&records).with_pacing(Pacing::Scaled(factor));
+            assert!(replayer.messages().is_err());
+        }
+
+        let replayer = Replayer::from_records(&records).with_pacing(Pacing::scaled(0.5).unwrap());
+        assert_eq!(replayer.len(), 2);
+
+        // Without substitutions the frames are sent as recorded.
+        let messages = replayer.messages().unwrap();
+        assert_eq!(messages[0].0, Duration::ZERO);
+        assert_eq!(messages[1].0, Duration::from_secs(1));
+        assert!(
+            matches!(&messages[1].1, MessageOrBytes::Bytes(bytes) if bytes == &records[2].bytes)
+        );
+
+        let substitutions = LedgerSubstitutions {
+            hashes: [(vec![1; 32], vec![2; 32])].into(),
+            seqs: [(10, 100)].into(),
+            seq_offset: 5,
+        };
+        let messages = replayer
+            .with_substitutions(substitutions)
+            .messages()
+            .unwrap();
+
+        let statuses = messages
+            .into_iter()
+            .map(|(_, message)| match message {
+                MessageOrBytes::Payload(Payload::TmStatusChange(status)) => status,
+                _ => panic!("unexpected message"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(statuses[0].ledger_seq, Some(100));
+        assert_eq!(statuses[1].ledger_seq, Some(16));
+        assert_eq!(statuses[1].ledger_hash, Some(vec![2; 32]));
+    }
+}
@@ -1,18 +1,30 @@
 use std::time::Duration;
 
+use anyhow::bail;
 use reqwest::{
     header::{ACCEPT, CONTENT_TYPE},
     Client, RequestBuilder,
 };
 use serde::{Deserialize, Serialize};
-use tokio::time::{error::Elapsed, sleep, timeout};
+use serde_json::{json, Value};
+use tokio::time::{error::Elapsed, sleep};
 
 use crate::tools::constants::EXPECTED_RESULT_TIMEOUT;
 
 const API_VERSION: u32 = 1;
 
 pub async fn wait_for_state(rpc_url: &str, state: String) {
-    tokio::time::timeout(EXPECTED_RESULT_TIMEOUT, async move {
+    wait_for_state_timeout(rpc_url, state, EXPECTED_RESULT_TIMEOUT)
+        .await
+        .unwrap()
+}
+
+pub async fn wait_for_state_timeout(
+    rpc_url: &str,
+    state: String,
+    timeout: Duration,
+) -> Result<(), Elapsed> {
+    tokio::time::timeout(timeout, async move {
         loop {
             if let Ok(response) = get_server_info(rpc_url).await {
                 if response.result.info.server_state == state {
@@ -23,7 +35,6 @@ pub async fn wait_for_state(rpc_url: &str, state: String) {
         }
     })
     .await
-    .unwrap()
 }
 
 pub async fn wait_for_account_data(
@@ -45,7 +56,14 @@ pub async fn wait_for_account_data(
 pub async fn wait_for_ledger_info(
     rpc_url: &str,
 ) -> Result<RpcResponse<LedgerInfoResponse>, Elapsed> {
-    timeout(EXPECTED_RESULT_TIMEOUT, async {
+    wait_for_ledger_info_timeout(rpc_url, EXPECTED_RESULT_TIMEOUT).await
+}
+
+pub async fn wait_for_ledger_info_timeout(
+    rpc_url: &str,
+    timeout: Duration,
+) -> Result<RpcResponse<LedgerInfoResponse>, Elapsed> {
+    tokio::time::timeout(timeout, async {
         loop {
             if let Ok(info) = get_ledger_info(rpc_url).await {
                 return info;
@@ -56,6 +74,25 @@ pub async fn wait_for_ledger_info(
     .await
 }
 
+/// Waits until the transaction is included in a validated ledger.
+pub async fn wait_for_validated_transaction(
+    rpc_url: &str,
+    hash: &str,
+    timeout: Duration,
+) -> Result<(), Elapsed> {
+    tokio::time::timeout(timeout, async move {
+        loop {
+            if let Ok(info) = get_transaction_info(rpc_url, hash.into()).await {
+                if info.result.validated {
+                    break;
+                }
+            }
+            sleep(Duration::from_millis(250)).await;
+        }
+    })
+    .await
+}
+
 async fn execute_rpc<T: for<'a> Deserialize<'a>>(
     rpc_url: &str,
     body: &impl Serialize,
@@ -64,6 +101,24 @@ async fn execute_rpc<T: for<'a> Deserialize<'a>>(
     Ok(response.error_for_status()?.json::<T>().await?)
 }
 
+// Like `execute_rpc`, but fails with the error code of rippled's error responses.
+async fn execute_checked_rpc<T: for<'a> Deserialize<'a>>(
+    rpc_url: &str,
+    body: &RpcRequest<Vec<Value>>,
+) -> anyhow::Result<RpcResponse<T>> {
+    let response: RpcResponse<Value> = execute_rpc(rpc_url, body).await?;
+    if let Some(error) = response.result.get("error") {
+        bail!(
+            "{} failed with {}",
+            body.method,
+            error.as_str().unwrap_or_default()
+        );
+    }
+    Ok(RpcResponse {
+        result: serde_json::from_value(response.result)?,
+    })
+}
+
 async fn get_account_info(
     rpc_url: &str,
     account: &str,
@@ -71,7 +126,7 @@ async fn get_account_info(
     execute_rpc(rpc_url, &build_account_info_request(account)).await
 }
 
-async fn get_server_info(rpc_url: &str) -> anyhow::Result<RpcResponse<ResultResponse>> {
+pub async fn get_server_info(rpc_url: &str) -> anyhow::Result<RpcResponse<ResultResponse>> {
     let request: RpcRequest<Option<()>> = RpcRequest {
         id: String::from("1"),
         method: String::from("server_info"),
@@ -114,6 +169,36 @@ pub async fn submit_transaction(
     execute_rpc(rpc_url, &request).await
 }
 
+/// Generates a key pair and the account derived from the passphrase; the same passphrase always
+/// leads to the same account.
+pub async fn wallet_propose(
+    rpc_url: &str,
+    passphrase: &str,
+) -> anyhow::Result<RpcResponse<WalletProposeResponse>> {
+    let request = build_request("wallet_propose", json!({ "passphrase": passphrase }));
+    execute_checked_rpc(rpc_url, &request).await
+}
+
+/// Lets the node fill in, sign and submit the transaction. Signing with a secret is only
+/// allowed over the admin API.
+pub async fn sign_and_submit(
+    rpc_url: &str,
+    secret: &str,
+    tx_json: Value,
+) -> anyhow::Result<RpcResponse<SignAndSubmitResponse>> {
+    let request = build_request("submit", json!({ "secret": secret, "tx_json": tx_json }));
+    execute_checked_rpc(rpc_url, &request).await
+}
+
+fn build_request(method: &str, params: Value) -> RpcRequest<Vec<Value>> {
+    RpcRequest {
+        id: String::from("1"),
+        method: String::from(method),
+        api_version: API_VERSION,
+        params: vec![params],
+    }
+}
+
 #[derive(Serialize)]
 struct LedgerInfoRequest {
     ledger_index: String,
@@ -192,7 +277,29 @@ struct TransactionInfoRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct TransactionInfoResponse {
-    // Empty struct as we aren't interested in content
+    /// Whether the transaction is in a validated ledger; missing from error responses.
+    #[serde(default)]
+    pub validated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignAndSubmitResponse {
+    /// The preliminary result, e.g. `tesSUCCESS`.
+    pub engine_result: String,
+    pub tx_json: SubmittedTransaction,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmittedTransaction {
+    pub hash: String,
+    #[serde(rename(deserialize = "Sequence"))]
+    pub sequence: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletProposeResponse {
+    pub account_id: String,
+    pub master_seed: String,
 }
 
 #[derive(Serialize)]
@@ -225,6 +332,18 @@ pub struct ResultResponse {
 #[derive(Debug, Deserialize)]
 pub struct ServerInfoResponse {
     pub server_state: String,
+    /// The number of connected peers.
+    #[serde(default)]
+    pub peers: u32,
+    /// The latest validated ledger, missing until the node validates one.
+    #[serde(default)]
+    pub validated_ledger: Option<ValidatedLedgerInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct ValidatedLedgerInfo {
+    pub hash: String,
+    pub seq: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -240,6 +359,9 @@ pub struct AccountDataResponse {
     #[allow(dead_code)]
     #[serde(rename(deserialize = "PreviousTxnID"))]
     pub previous_transaction: String,
+
+    #[serde(rename(deserialize = "Sequence"), default)]
+    pub sequence: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,88 @@
+//! Sets up Ziggurat's environment: the configuration pointing at the rippled binary and the data
+//! of the stateful nodes, generated by a testnet.
+
+use std::{collections::BTreeMap, env, fs, path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use ziggurat_xrpl::setup::{
+    build_ripple_work_path,
+    config::NodeMetaData,
+    constants::{RIPPLE_SETUP_DIR, VALIDATORS_FILE_NAME},
+    snapshot::{generate_stateful_snapshots, SnapshotParams},
+};
+
+/// Drops per XRP.
+const DROPS_PER_XRP: u64 = 1_000_000;
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// The directory containing the rippled binary, defaults to $RIPPLED_BIN_PATH
+    #[clap(short, long, value_parser)]
+    rippled_path: Option<PathBuf>,
+
+    /// Another rippled binary which nodes can be started with, as NAME=DIRECTORY; can be repeated
+    #[clap(long = "binary", value_name = "NAME=DIRECTORY", value_parser = parse_binary)]
+    binaries: Vec<(String, PathBuf)>,
+
+    /// The number of funded accounts, the first one is always the test account
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    accounts: usize,
+
+    /// The balance of each funded account, in XRP
+    #[clap(short, long, value_parser, default_value_t = 5000)]
+    balance: u64,
+
+    /// The minimum number of validated ledgers in the snapshot
+    #[clap(short, long, value_parser, default_value_t = 10)]
+    ledgers: u32,
+
+    /// Timeout of each of the setup steps, in seconds
+    #[clap(short, long, value_parser, default_value_t = 300)]
+    timeout: u64,
+}
+
+fn parse_binary(arg: &str) -> Result<(String, PathBuf)> {
+    let (name, path) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected NAME=DIRECTORY, got {arg:?}"))?;
+    Ok((name.to_owned(), fs::canonicalize(path)?))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let rippled_path = match args.rippled_path {
+        Some(path) => path,
+        None => env::var_os("RIPPLED_BIN_PATH")
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("pass --rippled-path or export RIPPLED_BIN_PATH"))?,
+    };
+    let rippled_path = fs::canonicalize(rippled_path)?;
+
+    let setup_path = build_ripple_work_path()?.join(RIPPLE_SETUP_DIR);
+    let binaries = args.binaries.into_iter().collect::<BTreeMap<_, _>>();
+    NodeMetaData::write(&setup_path, rippled_path, binaries)?;
+    fs::write(
+        setup_path.join(VALIDATORS_FILE_NAME),
+        include_str!("../../../setup/validators.txt"),
+    )?;
+    println!("Wrote the configuration to {}", setup_path.display());
+
+    println!("Generating the stateful nodes' data, this takes a few minutes");
+    let params = SnapshotParams {
+        accounts: args.accounts,
+        balance: args.balance * DROPS_PER_XRP,
+        ledgers: args.ledgers,
+        step_timeout: Duration::from_secs(args.timeout),
+    };
+    let accounts = generate_stateful_snapshots(&params).await?;
+
+    println!("Funded accounts:");
+    for account in accounts {
+        println!("  {account}");
+    }
+    Ok(())
+}
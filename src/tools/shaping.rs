@@ -0,0 +1,293 @@
+//! Traffic shaping applied by the [PeerStream](crate::tools::stream::PeerStream) to
+//! individual connections.
+//!
+//! The shaping works at the transport level, below TLS, so it also affects the handshake.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    task::Waker,
+    time::Duration,
+};
+
+use rand::{thread_rng, Rng};
+use tokio::sync::Notify;
+
+/// A pause in writing after every given number of bytes.
+///
+/// Useful to stall in the middle of a frame or a handshake message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pause {
+    /// The number of bytes written between pauses.
+    pub after_bytes: usize,
+    /// The length of the pause.
+    pub duration: Duration,
+}
+
+/// Shaping configuration of a single connection. The default doesn't alter the traffic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShapingCfg {
+    /// The maximum number of bytes written per second, which has to be positive; use
+    /// [Pause]s to stall the writes instead.
+    pub write_rate: Option<u64>,
+    /// The time the written bytes are held back before they're sent, without limiting
+    /// the throughput.
+    pub latency: Duration,
+    /// The upper bound of a random delay added on top of the latency.
+    pub jitter: Duration,
+    /// Pauses in writing.
+    pub pause: Option<Pause>,
+    /// If set, nothing is read from the socket, so the peer eventually sees a full receive window.
+    pub read_stopped: bool,
+}
+
+impl ShapingCfg {
+    /// Writes one byte per second, the classic slow-loris.
+    pub fn slow_loris() -> Self {
+        Self {
+            write_rate: Some(1),
+            ..Default::default()
+        }
+    }
+
+    // Panics on a configuration which can't be applied.
+    fn check(&self) {
+        assert_ne!(
+            self.write_rate,
+            Some(0),
+            "the write rate has to be positive"
+        );
+    }
+
+    // The time the bytes written now are held back.
+    pub(crate) fn write_delay(&self) -> Duration {
+        let jitter = if self.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            thread_rng().gen_range(Duration::ZERO..=self.jitter)
+        };
+
+        self.latency + jitter
+    }
+
+    // The number of bytes which can be written at once.
+    pub(crate) fn max_write_len(&self, written_since_pause: usize, len: usize) -> usize {
+        let mut max_len = len;
+
+        // Split the writes so the rate is kept smooth, rather than bursting once per second.
+        if let Some(rate) = self.write_rate {
+            max_len = max_len.min((rate as usize / 10).max(1));
+        }
+        if let Some(pause) = self.pause {
+            max_len = max_len.min(pause.after_bytes.saturating_sub(written_since_pause).max(1));
+        }
+
+        max_len
+    }
+
+    // The delay required by the rate limit after writing the given number of bytes.
+    pub(crate) fn rate_delay(&self, written: usize) -> Duration {
+        match self.write_rate {
+            Some(rate) => Duration::from_secs_f64(written as f64 / rate as f64),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    cfg: ShapingCfg,
+    read_waker: Option<Waker>,
+}
+
+impl State {
+    // Wakes the reader so it picks up the changes.
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to the shaping of a single connection; the changes apply immediately.
+#[derive(Clone, Default)]
+pub struct Shaping {
+    state: Arc<Mutex<State>>,
+    half_close: Arc<Notify>,
+}
+
+impl Shaping {
+    pub fn new(cfg: ShapingCfg) -> Self {
+        cfg.check();
+        Self {
+            state: Arc::new(Mutex::new(State {
+                cfg,
+                ..Default::default()
+            })),
+            half_close: Default::default(),
+        }
+    }
+
+    /// The current configuration.
+    pub fn cfg(&self) -> ShapingCfg {
+        self.state.lock().unwrap().cfg
+    }
+
+    /// Replaces the configuration.
+    pub fn set_cfg(&self, cfg: ShapingCfg) {
+        cfg.check();
+        let mut state = self.state.lock().unwrap();
+        state.cfg = cfg;
+        state.wake_reader();
+    }
+
+    /// Shuts down the writing half of the connection, while the reading continues.
+    pub fn half_close(&self) {
+        // The permit is kept until the writer picks it up.
+        self.half_close.notify_one();
+    }
+
+    // Completes once the half-close is requested.
+    pub(crate) async fn half_close_requested(&self) {
+        self.half_close.notified().await
+    }
+
+    // Checks if reading is allowed; if not, the reader is woken once it is.
+    pub(crate) fn poll_read_allowed(&self, waker: &Waker) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.cfg.read_stopped {
+            return true;
+        }
+
+        state.read_waker = Some(waker.clone());
+        false
+    }
+}
+
+/// Shaping of all the connections of a node.
+#[derive(Clone, Default)]
+pub struct Shapers {
+    default: ShapingCfg,
+    connections: Arc<Mutex<HashMap<SocketAddr, Shaping>>>,
+}
+
+impl Shapers {
+    /// Applies the given configuration to connections without one set explicitly.
+    pub fn new(default: ShapingCfg) -> Self {
+        default.check();
+        Self {
+            default,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the shaping of the connection with the given address.
+    ///
+    /// If the connection doesn't exist yet, the configuration applies once it's established,
+    /// which allows shaping the handshake as well. It's forgotten once the connection is closed
+    /// or fails to be established.
+    ///
+    /// Panics if the write rate is zero.
+    pub fn set(&self, addr: SocketAddr, cfg: ShapingCfg) {
+        let mut connections = self.connections.lock().unwrap();
+        match connections.get(&addr) {
+            Some(shaping) => shaping.set_cfg(cfg),
+            None => {
+                connections.insert(addr, Shaping::new(cfg));
+            }
+        }
+    }
+
+    /// Half-closes the connection with the given address.
+    ///
+    /// Returns `false` if there is no such connection.
+    pub fn half_close(&self, addr: SocketAddr) -> bool {
+        match self.connections.lock().unwrap().get(&addr) {
+            Some(shaping) => {
+                shaping.half_close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns the shaping for a new connection.
+    pub(crate) fn attach(&self, addr: SocketAddr) -> Shaping {
+        self.connections
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_insert_with(|| Shaping::new(self.default))
+            .clone()
+    }
+
+    // Forgets the shaping of a closed or failed connection.
+    pub(crate) fn detach(&self, addr: SocketAddr) {
+        self.connections.lock().unwrap().remove(&addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_lengths_and_delays() {
+        let cfg = ShapingCfg::default();
+        assert_eq!(cfg.max_write_len(0, 1000), 1000);
+        assert_eq!(cfg.rate_delay(1000), Duration::ZERO);
+        assert_eq!(cfg.write_delay(), Duration::ZERO);
+
+        // The writes are split into tenths of the rate, but never into empty ones.
+        let cfg = ShapingCfg {
+            write_rate: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(cfg.max_write_len(0, 1000), 100);
+        assert_eq!(cfg.max_write_len(0, 50), 50);
+        assert_eq!(cfg.rate_delay(100), Duration::from_millis(100));
+        assert_eq!(ShapingCfg::slow_loris().max_write_len(0, 1000), 1);
+        assert_eq!(
+            ShapingCfg::slow_loris().rate_delay(1),
+            Duration::from_secs(1)
+        );
+
+        // The writes stop right at the pause.
+        let cfg = ShapingCfg {
+            pause: Some(Pause {
+                after_bytes: 10,
+                duration: Duration::from_secs(1),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(cfg.max_write_len(0, 1000), 10);
+        assert_eq!(cfg.max_write_len(7, 1000), 3);
+        assert_eq!(cfg.max_write_len(7, 2), 2);
+        assert_eq!(cfg.max_write_len(10, 1000), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the write rate has to be positive")]
+    fn zero_write_rate() {
+        let cfg = ShapingCfg {
+            write_rate: Some(0),
+            ..Default::default()
+        };
+        Shapers::default().set("127.0.0.1:1000".parse().unwrap(), cfg);
+    }
+
+    #[test]
+    fn latency_and_jitter() {
+        let cfg = ShapingCfg {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let delay = cfg.write_delay();
+            assert!(delay >= cfg.latency && delay <= cfg.latency + cfg.jitter);
+        }
+    }
+}
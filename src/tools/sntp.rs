@@ -0,0 +1,282 @@
+//! A minimal SNTP server serving an adjustable clock, used as the time source of test nodes.
+//!
+//! Rippled always queries its `[sntp_servers]` on the standard NTP port, so the server has to
+//! listen on port 123 of a dedicated address, which requires the `CAP_NET_BIND_SERVICE`
+//! capability (or a lowered `net.ipv4.ip_unprivileged_port_start`).
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use tokio::{net::UdpSocket, task::JoinHandle};
+use tracing::*;
+
+use crate::setup::addrs::NodeAddrs;
+
+/// The port rippled sends its SNTP queries to.
+pub const NTP_PORT: u16 = 123;
+
+/// The size of an SNTP packet without the optional fields.
+const PACKET_SIZE: usize = 48;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+// The offsets of the packet fields.
+const OFF_STRATUM: usize = 1;
+const OFF_POLL: usize = 2;
+const OFF_PRECISION: usize = 3;
+const OFF_REFERENCE_ID: usize = 12;
+const OFF_REFERENCE_TS: usize = 16;
+const OFF_ORIGINATE_TS: usize = 24;
+const OFF_RECEIVE_TS: usize = 32;
+const OFF_TRANSMIT_TS: usize = 40;
+
+/// Builds the reply to a client's request, reporting the given time.
+///
+/// Returns `None` if the packet isn't an SNTP client request.
+pub fn sntp_reply(request: &[u8], now: DateTime<Utc>) -> Option<[u8; PACKET_SIZE]> {
+    if request.len() < PACKET_SIZE || request[0] & 0x07 != MODE_CLIENT {
+        return None;
+    }
+
+    let version = (request[0] >> 3) & 0x07;
+    let timestamp = ntp_timestamp(now);
+
+    let mut reply = [0; PACKET_SIZE];
+    // No leap second warning, the client's version and the server mode.
+    reply[0] = (version << 3) | MODE_SERVER;
+    // A primary server, synchronized to its local clock.
+    reply[OFF_STRATUM] = 1;
+    reply[OFF_POLL] = request[OFF_POLL];
+    // About a microsecond, as a power of two.
+    reply[OFF_PRECISION] = -20i8 as u8;
+    reply[OFF_REFERENCE_ID..OFF_REFERENCE_ID + 4].copy_from_slice(b"LOCL");
+    reply[OFF_REFERENCE_TS..OFF_REFERENCE_TS + 8].copy_from_slice(&timestamp);
+    // The client matches the reply to its request by the echoed transmit timestamp.
+    reply[OFF_ORIGINATE_TS..OFF_ORIGINATE_TS + 8]
+        .copy_from_slice(&request[OFF_TRANSMIT_TS..OFF_TRANSMIT_TS + 8]);
+    reply[OFF_RECEIVE_TS..OFF_RECEIVE_TS + 8].copy_from_slice(&timestamp);
+    reply[OFF_TRANSMIT_TS..OFF_TRANSMIT_TS + 8].copy_from_slice(&timestamp);
+
+    Some(reply)
+}
+
+/// Encodes the time as an NTP timestamp: seconds since 1900 and their binary fraction.
+pub fn ntp_timestamp(time: DateTime<Utc>) -> [u8; 8] {
+    let secs = (time.timestamp() + NTP_UNIX_OFFSET) as u32;
+    let fraction = ((time.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000;
+
+    let mut timestamp = [0; 8];
+    timestamp[..4].copy_from_slice(&secs.to_be_bytes());
+    timestamp[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+    timestamp
+}
+
+/// Decodes an NTP timestamp, see [ntp_timestamp].
+pub fn parse_ntp_timestamp(timestamp: &[u8]) -> Option<DateTime<Utc>> {
+    let secs = u32::from_be_bytes(timestamp.get(..4)?.try_into().ok()?) as i64;
+    let fraction = u32::from_be_bytes(timestamp.get(4..8)?.try_into().ok()?) as u64;
+    let nanos = (fraction * 1_000_000_000) >> 32;
+
+    Utc.timestamp_opt(secs - NTP_UNIX_OFFSET, nanos as u32)
+        .single()
+}
+
+/// A local SNTP server. Its clock follows the system clock, shifted by an adjustable offset.
+pub struct SntpServer {
+    addr: SocketAddr,
+    offset: Arc<Mutex<Duration>>,
+    requests: Arc<AtomicU32>,
+    task: JoinHandle<()>,
+}
+
+impl SntpServer {
+    /// Starts the server on the NTP port of a loopback address of its own, so the nodes can
+    /// be pointed at it with [NodeBuilder::sntp_server](crate::setup::node::NodeBuilder::sntp_server).
+    ///
+    /// Binding the privileged port requires the `CAP_NET_BIND_SERVICE` capability or
+    /// the `net.ipv4.ip_unprivileged_port_start` sysctl set to 123 or lower.
+    pub async fn start_local() -> io::Result<Self> {
+        let ip = NodeAddrs::allocate()?.ip;
+        Self::start(SocketAddr::new(ip, NTP_PORT))
+            .await
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::PermissionDenied => io::Error::new(
+                    e.kind(),
+                    format!(
+                        "can't bind the NTP port {NTP_PORT} ({e}); grant the test binary \
+                         CAP_NET_BIND_SERVICE or lower net.ipv4.ip_unprivileged_port_start"
+                    ),
+                ),
+                _ => e,
+            })
+    }
+
+    /// Starts the server on the given address.
+    pub async fn start(addr: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+        let addr = socket.local_addr()?;
+        let offset = Arc::new(Mutex::new(Duration::zero()));
+        let requests = Arc::new(AtomicU32::new(0));
+
+        let task = tokio::spawn({
+            let offset = offset.clone();
+            let requests = requests.clone();
+            async move {
+                let mut buf = [0; 1024];
+                loop {
+                    let (len, client) = match socket.recv_from(&mut buf).await {
+                        Ok(received) => received,
+                        Err(e) => {
+                            debug!("SNTP server failed to receive: {e}");
+                            continue;
+                        }
+                    };
+
+                    let now = Utc::now() + *offset.lock().unwrap();
+                    match sntp_reply(&buf[..len], now) {
+                        Some(reply) => {
+                            requests.fetch_add(1, Ordering::Relaxed);
+                            if let Err(e) = socket.send_to(&reply, client).await {
+                                debug!("SNTP server failed to reply to {client}: {e}");
+                            }
+                        }
+                        None => debug!("SNTP server received an invalid request from {client}"),
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            offset,
+            requests,
+            task,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.addr.ip()
+    }
+
+    /// Shifts the served time by the offset from the system clock; a negative offset makes the
+    /// clock lag behind.
+    pub fn set_offset(&self, offset: Duration) {
+        *self.offset.lock().unwrap() = offset;
+    }
+
+    /// The offset of the served time from the system clock.
+    pub fn offset(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+
+    /// Makes the server report the given time from now on, ticking along with the system clock.
+    pub fn set_time(&self, time: DateTime<Utc>) {
+        self.set_offset(time - Utc::now());
+    }
+
+    /// The time currently served.
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset()
+    }
+
+    /// The number of requests answered so far.
+    pub fn requests(&self) -> u32 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for SntpServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    // A client request as sent by rippled: version 3, client mode and a nonce in the
+    // transmit timestamp.
+    fn client_request() -> [u8; PACKET_SIZE] {
+        let mut request = [0; PACKET_SIZE];
+        request[0] = (3 << 3) | MODE_CLIENT;
+        request[OFF_TRANSMIT_TS..OFF_TRANSMIT_TS + 8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        request
+    }
+
+    #[test]
+    fn ntp_timestamps_round_trip() {
+        let time = Utc.timestamp_opt(1_700_000_000, 250_000_000).unwrap();
+        let timestamp = ntp_timestamp(time);
+
+        assert_eq!(
+            u32::from_be_bytes(timestamp[..4].try_into().unwrap()),
+            3_908_988_800
+        );
+        // A quarter of a second.
+        assert_eq!(
+            u32::from_be_bytes(timestamp[4..].try_into().unwrap()),
+            1 << 30
+        );
+        assert_eq!(parse_ntp_timestamp(&timestamp), Some(time));
+    }
+
+    #[test]
+    fn reply_to_client_requests_only() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let reply = sntp_reply(&client_request(), time).unwrap();
+
+        assert_eq!(reply[0], (3 << 3) | MODE_SERVER);
+        assert_eq!(reply[OFF_STRATUM], 1);
+        assert_eq!(
+            reply[OFF_ORIGINATE_TS..OFF_ORIGINATE_TS + 8],
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        assert_eq!(parse_ntp_timestamp(&reply[OFF_TRANSMIT_TS..]), Some(time));
+
+        let mut server_packet = client_request();
+        server_packet[0] = (3 << 3) | MODE_SERVER;
+        assert!(sntp_reply(&server_packet, time).is_none());
+        assert!(sntp_reply(&[0x1b; 10], time).is_none());
+    }
+
+    #[tokio::test]
+    async fn serve_skewed_clock() {
+        let server = SntpServer::start((Ipv4Addr::LOCALHOST, 0).into())
+            .await
+            .unwrap();
+        server.set_offset(Duration::minutes(-5));
+
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        client
+            .send_to(&client_request(), server.addr())
+            .await
+            .unwrap();
+
+        let mut reply = [0; PACKET_SIZE];
+        let (len, _) = client.recv_from(&mut reply).await.unwrap();
+        assert_eq!(len, PACKET_SIZE);
+        assert_eq!(server.requests(), 1);
+
+        let served = parse_ntp_timestamp(&reply[OFF_TRANSMIT_TS..]).unwrap();
+        let skew = served - Utc::now();
+        assert!(skew < Duration::minutes(-4) && skew > Duration::minutes(-6));
+    }
+}
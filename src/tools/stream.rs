@@ -0,0 +1,498 @@
+//! A transport wrapper placed under every connection of the [InnerNode](crate::tools::inner_node::InnerNode).
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll, Waker},
+};
+
+use bytes::{Buf, Bytes};
+use tokio::{
+    io::{split, AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{sleep, sleep_until, timeout, Instant},
+};
+use tokio_util::sync::PollSender;
+
+use crate::tools::{
+    constants::{PEER_STREAM_CLOSE_TIMEOUT, PEER_STREAM_WRITE_QUEUE_DEPTH},
+    events::{ConnectionEvents, DisconnectReason},
+    shaping::Shaping,
+};
+
+/// Wraps the raw stream to shape the traffic and note the transport-level reason when
+/// the stream ends.
+///
+/// The written bytes are queued and sent by a separate task, so the latency delays the
+/// traffic rather than limiting its throughput, and a half-close is carried out even if
+/// nothing is read or written. Like with a socket's send buffer, a write only waits until
+/// the earlier bytes are sent, except for the ones still held back by the latency; those
+/// are dropped once the stream is closed. The ones already due are still sent, unless that
+/// takes longer than [PEER_STREAM_CLOSE_TIMEOUT].
+pub struct PeerStream<S> {
+    reader: ReadHalf<S>,
+    addr: SocketAddr,
+    events: ConnectionEvents,
+    shaping: Shaping,
+    // The queue of the bytes to be sent.
+    chunks: PollSender<Chunk>,
+    // The sequence numbers and release times of the chunks which may not be sent yet.
+    unsent: VecDeque<(u64, Instant)>,
+    // The number of queued chunks.
+    queued: u64,
+    progress: Arc<Mutex<WriteProgress>>,
+    // Tells the writer the stream was dropped.
+    closing: Option<oneshot::Sender<()>>,
+    // The task sending the queued bytes.
+    writer: JoinHandle<io::Result<()>>,
+    // Whether the writing half was shut down.
+    write_shut_down: bool,
+}
+
+// Written bytes, to be sent once they're released.
+struct Chunk {
+    release_at: Instant,
+    bytes: Bytes,
+}
+
+// The progress of the writer task.
+#[derive(Default)]
+struct WriteProgress {
+    // The number of chunks sent.
+    sent: u64,
+    // Whether the writer stopped, so nothing else is sent.
+    finished: bool,
+    write_waker: Option<Waker>,
+}
+
+impl WriteProgress {
+    fn update(progress: &Mutex<Self>, update: impl FnOnce(&mut Self)) {
+        let mut progress = progress.lock().unwrap();
+        update(&mut progress);
+        if let Some(waker) = progress.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Send + 'static> PeerStream<S> {
+    pub fn new(inner: S, addr: SocketAddr, events: ConnectionEvents, shaping: Shaping) -> Self {
+        let (reader, writer) = split(inner);
+        let (sender, receiver) = mpsc::channel(PEER_STREAM_WRITE_QUEUE_DEPTH);
+        let (closing, closed) = oneshot::channel();
+        let progress = Arc::new(Mutex::new(WriteProgress::default()));
+        let writer = tokio::spawn(
+            Writer {
+                writer,
+                chunks: receiver,
+                current: None,
+                shaping: shaping.clone(),
+                progress: progress.clone(),
+            }
+            .run(addr, events.clone(), closed),
+        );
+
+        Self {
+            reader,
+            addr,
+            events,
+            shaping,
+            chunks: PollSender::new(sender),
+            unsent: Default::default(),
+            queued: 0,
+            progress,
+            closing: Some(closing),
+            writer,
+            write_shut_down: false,
+        }
+    }
+}
+
+impl<S> Drop for PeerStream<S> {
+    fn drop(&mut self) {
+        if let Some(closing) = self.closing.take() {
+            // An error only means the writer is already done.
+            let _ = closing.send(());
+        }
+    }
+}
+
+// Sends the queued bytes to the stream.
+struct Writer<S> {
+    writer: WriteHalf<S>,
+    chunks: mpsc::Receiver<Chunk>,
+    // The rest of the chunk being sent.
+    current: Option<Bytes>,
+    shaping: Shaping,
+    progress: Arc<Mutex<WriteProgress>>,
+}
+
+impl<S: AsyncWrite> Writer<S> {
+    // Sends the queued bytes until the queue is closed, the half-close is requested or the
+    // stream is dropped, then shuts down the writing half.
+    async fn run(
+        mut self,
+        addr: SocketAddr,
+        events: ConnectionEvents,
+        closed: oneshot::Receiver<()>,
+    ) -> io::Result<()> {
+        let shaping = self.shaping.clone();
+        let result = tokio::select! {
+            result = self.write_chunks() => Some(result),
+            _ = shaping.half_close_requested() => None,
+            _ = closed => None,
+        };
+        let result = match result {
+            Some(result) => result,
+            // The peer might not read anymore, so the writer doesn't outlive the stream for long.
+            None => timeout(PEER_STREAM_CLOSE_TIMEOUT, self.write_due())
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+        };
+        // Nothing can be written afterwards.
+        self.chunks.close();
+        WriteProgress::update(&self.progress, |progress| progress.finished = true);
+
+        let result = match result {
+            Ok(()) => self.writer.shutdown().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            events.set_reason(addr, DisconnectReason::Io(e.kind()));
+        }
+
+        result
+    }
+
+    // Sends the chunks once they're released, keeping the rate and the pauses.
+    async fn write_chunks(&mut self) -> io::Result<()> {
+        // Bytes written since the last pause.
+        let mut written_since_pause = 0;
+
+        loop {
+            if self.current.is_none() {
+                let chunk = match self.chunks.recv().await {
+                    Some(chunk) => chunk,
+                    None => return Ok(()),
+                };
+                sleep_until(chunk.release_at).await;
+                self.current = Some(chunk.bytes);
+            }
+
+            let bytes = self.current.as_mut().unwrap();
+            let cfg = self.shaping.cfg();
+            let len = cfg.max_write_len(written_since_pause, bytes.len());
+            let written = self.writer.write(&bytes[..len]).await?;
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            bytes.advance(written);
+            if bytes.is_empty() {
+                self.current = None;
+                WriteProgress::update(&self.progress, |progress| progress.sent += 1);
+            }
+
+            let mut delay = cfg.rate_delay(written);
+            if let Some(pause) = cfg.pause {
+                written_since_pause += written;
+                if written_since_pause >= pause.after_bytes {
+                    written_since_pause = 0;
+                    delay = delay.max(pause.duration);
+                }
+            }
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+        }
+    }
+
+    // Sends the rest of the current chunk and the queued chunks which are already released,
+    // without any further shaping; the others are dropped.
+    async fn write_due(&mut self) -> io::Result<()> {
+        if let Some(bytes) = self.current.take() {
+            self.writer.write_all(&bytes).await?;
+        }
+
+        let now = Instant::now();
+        while let Ok(chunk) = self.chunks.try_recv() {
+            if chunk.release_at > now {
+                break;
+            }
+            self.writer.write_all(&chunk.bytes).await?;
+        }
+
+        Ok(())
+    }
+}
+
+// The error returned once the writing half is closed.
+fn write_closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the writing half is closed")
+}
+
+impl<S: AsyncRead> AsyncRead for PeerStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if !this.shaping.poll_read_allowed(cx.waker()) {
+            return Poll::Pending;
+        }
+
+        let filled = buf.filled().len();
+
+        let poll = Pin::new(&mut this.reader).poll_read(cx, buf);
+        match &poll {
+            Poll::Ready(Ok(())) if buf.filled().len() == filled && buf.remaining() != 0 => {
+                this.events.set_reason(this.addr, DisconnectReason::Eof)
+            }
+            Poll::Ready(Err(e)) => this
+                .events
+                .set_reason(this.addr, DisconnectReason::Io(e.kind())),
+            _ => (),
+        }
+
+        poll
+    }
+}
+
+impl<S> PeerStream<S> {
+    // Waits until the writer sends all the released chunks.
+    fn poll_released_sent(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut progress = self.progress.lock().unwrap();
+        while let Some((seq, _)) = self.unsent.front() {
+            if *seq > progress.sent {
+                break;
+            }
+            self.unsent.pop_front();
+        }
+
+        // The chunks held back by the latency aren't waited for.
+        let now = Instant::now();
+        if self.unsent.iter().all(|(_, release_at)| *release_at > now) {
+            return Poll::Ready(Ok(()));
+        }
+        if progress.finished {
+            return Poll::Ready(Err(write_closed()));
+        }
+
+        progress.write_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<S> AsyncWrite for PeerStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_released_sent(cx))?;
+        ready!(this.chunks.poll_reserve(cx)).map_err(|_| write_closed())?;
+
+        let release_at = Instant::now() + this.shaping.cfg().write_delay();
+        let chunk = Chunk {
+            release_at,
+            bytes: Bytes::copy_from_slice(buf),
+        };
+        this.chunks.send_item(chunk).map_err(|_| write_closed())?;
+        this.queued += 1;
+        this.unsent.push_back((this.queued, release_at));
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // TLS treats a pending flush as a failure, the writes wait for the stream instead.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.write_shut_down {
+            return Poll::Ready(Ok(()));
+        }
+
+        // The writer sends the remaining bytes before shutting down.
+        this.chunks.close();
+        let result = ready!(Pin::new(&mut this.writer).poll(cx))
+            .unwrap_or_else(|e| Err(io::Error::other(e)));
+        this.write_shut_down = true;
+
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        tools::{
+            mock_rippled::MockRippled,
+            shaping::{Pause, ShapingCfg},
+            synth_node::SyntheticNode,
+            testing::{is_pong, mock, ping, TIMEOUT},
+            traffic::Direction,
+        },
+        wait_until,
+    };
+
+    async fn connect() -> (MockRippled, SyntheticNode) {
+        let mock = mock().await;
+
+        let synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.connect(mock.addr()).await.unwrap();
+
+        (mock, synth_node)
+    }
+
+    // Sends the given number of pings at once and returns the time it took to receive all
+    // the pongs, which have to come in order.
+    async fn ping_pong(mock: &MockRippled, synth_node: &mut SyntheticNode, count: u32) -> Duration {
+        let start = Instant::now();
+        for seq in 0..count {
+            synth_node.unicast(mock.addr(), ping(seq)).unwrap();
+        }
+
+        for seq in 0..count {
+            let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+            assert!(is_pong(&message.payload, seq));
+        }
+
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn latency_delays_without_limiting_throughput() {
+        let (mock, mut synth_node) = connect().await;
+        let latency = Duration::from_millis(300);
+        synth_node.set_shaping(
+            mock.addr(),
+            ShapingCfg {
+                latency,
+                ..Default::default()
+            },
+        );
+
+        // Each of the messages is delayed, but they're all in flight at once.
+        let elapsed = ping_pong(&mock, &mut synth_node, 10).await;
+        assert!(elapsed >= latency, "{elapsed:?}");
+        assert!(elapsed < latency * 3, "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn jitter_keeps_the_order() {
+        let (mock, mut synth_node) = connect().await;
+        let jitter = Duration::from_millis(100);
+        synth_node.set_shaping(
+            mock.addr(),
+            ShapingCfg {
+                jitter,
+                ..Default::default()
+            },
+        );
+
+        let elapsed = ping_pong(&mock, &mut synth_node, 20).await;
+        assert!(elapsed < jitter * 3, "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn write_rate() {
+        let (mock, mut synth_node) = connect().await;
+        let rate = 500;
+        synth_node.set_shaping(
+            mock.addr(),
+            ShapingCfg {
+                write_rate: Some(rate),
+                ..Default::default()
+            },
+        );
+        synth_node.reset_traffic_stats();
+
+        let elapsed = ping_pong(&mock, &mut synth_node, 20).await;
+
+        // The frames are even smaller than what's sent over TLS.
+        let sent = synth_node
+            .traffic_stats()
+            .by_peer(Direction::Outbound)
+            .get(&mock.addr())
+            .unwrap()
+            .bytes;
+        let min_duration = Duration::from_secs_f64(sent as f64 / rate as f64);
+        assert!(elapsed >= min_duration, "{elapsed:?} < {min_duration:?}");
+    }
+
+    #[tokio::test]
+    async fn pause() {
+        let (mock, mut synth_node) = connect().await;
+        let duration = Duration::from_millis(200);
+        synth_node.set_shaping(
+            mock.addr(),
+            ShapingCfg {
+                pause: Some(Pause {
+                    after_bytes: 10,
+                    duration,
+                }),
+                ..Default::default()
+            },
+        );
+
+        // A single frame is larger than 10 bytes, so it's sent in several parts.
+        let elapsed = ping_pong(&mock, &mut synth_node, 1).await;
+        assert!(elapsed >= duration * 2, "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn read_stopped() {
+        let (mock, mut synth_node) = connect().await;
+        let cfg = ShapingCfg {
+            read_stopped: true,
+            ..Default::default()
+        };
+        synth_node.set_shaping(mock.addr(), cfg);
+
+        synth_node.unicast(mock.addr(), ping(1)).unwrap();
+        assert!(synth_node
+            .recv_message_timeout(Duration::from_millis(300))
+            .await
+            .is_err());
+
+        // The pong is read once the reading is resumed.
+        synth_node.set_shaping(mock.addr(), Default::default());
+        let (_, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+        assert!(is_pong(&message.payload, 1));
+    }
+
+    #[tokio::test]
+    async fn half_close_without_reading() {
+        let (mock, synth_node) = connect().await;
+        wait_until!(TIMEOUT, mock.peers().len() == 1);
+
+        // Nothing is read from the stream, yet the mock sees the end of it.
+        synth_node.set_shaping(
+            mock.addr(),
+            ShapingCfg {
+                read_stopped: true,
+                ..Default::default()
+            },
+        );
+        assert!(synth_node.half_close(mock.addr()));
+        wait_until!(TIMEOUT, mock.peers().is_empty());
+
+        // Nothing can be written afterwards.
+        let delivery = synth_node.unicast(mock.addr(), ping(1)).unwrap();
+        assert!(delivery.await.unwrap().is_err());
+    }
+}
@@ -5,15 +5,15 @@ use std::{
 };
 
 use pea2pea::{
-    protocols::{Handshake, Reading, Writing},
+    protocols::{Disconnect, Handshake, Reading, Writing},
     Pea2Pea,
 };
 use tokio::{
     net::TcpSocket,
-    sync::{mpsc, mpsc::Receiver, oneshot},
+    sync::{broadcast, broadcast::error::RecvError, mpsc, mpsc::Receiver, oneshot},
     time::timeout,
 };
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::{
     protocol::{
@@ -23,7 +23,11 @@ use crate::{
     tools::{
         config::SynthNodeCfg,
         constants::{EXPECTED_RESULT_TIMEOUT, SYNTH_NODE_QUEUE_DEPTH},
+        events::{ConnectionEvent, ConnectionEventKind, DisconnectReason},
         inner_node::InnerNode,
+        keys,
+        shaping::ShapingCfg,
+        traffic::TrafficSnapshot,
     },
 };
 
@@ -40,20 +44,41 @@ pub fn enable_tracing() {
 pub struct SyntheticNode {
     inner: InnerNode,
     receiver: Receiver<(SocketAddr, BinaryMessage)>,
+    events: broadcast::Receiver<ConnectionEvent>,
 }
 
 impl SyntheticNode {
+    /// Creates the node.
+    ///
+    /// # Panics
+    ///
+    /// If the node keys are invalid or the capture file can't be created, see
+    /// [try_new](Self::try_new) for a fallible alternative.
     pub async fn new(config: &SynthNodeCfg) -> Self {
+        Self::try_new(config)
+            .await
+            .expect("couldn't create the synthetic node")
+    }
+
+    /// Creates the node, returning an error if the node keys are invalid or the capture
+    /// file can't be created.
+    pub async fn try_new(config: &SynthNodeCfg) -> io::Result<Self> {
         let (sender, receiver) = mpsc::channel(SYNTH_NODE_QUEUE_DEPTH);
-        let inner = InnerNode::new(config, sender).await;
+        let inner = InnerNode::new(config, sender).await?;
+        let events = inner.events.subscribe();
 
-        if config.handshake.is_some() {
-            inner.enable_handshake().await;
-        }
+        // The handshake protocol is always enabled to track connection events and shape
+        // the traffic; the connection mode decides which parts of the Ripple handshake are done.
+        inner.enable_handshake().await;
         inner.enable_reading().await;
         inner.enable_writing().await;
+        inner.enable_disconnect().await;
 
-        Self { inner, receiver }
+        Ok(Self {
+            inner,
+            receiver,
+            events,
+        })
     }
 
     /// Starts listening for inbound connections.
@@ -68,6 +93,13 @@ impl SyntheticNode {
         self.inner.connect(target).await
     }
 
+    /// Connects to all the target addresses, so the node keeps several peers at once.
+    ///
+    /// Returns the connection result for every address.
+    pub async fn connect_all(&self, targets: &[SocketAddr]) -> Vec<(SocketAddr, io::Result<()>)> {
+        self.inner.connect_all(targets).await
+    }
+
     /// Connects to the target address using specified socket.
     pub async fn connect_from(&self, target: SocketAddr, socket: TcpSocket) -> io::Result<()> {
         self.inner.connect_from(target, socket).await
@@ -91,6 +123,48 @@ impl SyntheticNode {
         self.inner.unicast(addr, MessageOrBytes::Bytes(bytes))
     }
 
+    /// Sends the message to all the connected peers and waits until it's delivered.
+    ///
+    /// Returns the delivery result for every peer.
+    pub async fn broadcast(&self, message: Payload) -> Vec<(SocketAddr, io::Result<()>)> {
+        self.multicast(&self.connected_addrs(), message).await
+    }
+
+    /// Sends the raw bytes to all the connected peers and waits until they're delivered.
+    ///
+    /// Returns the delivery result for every peer.
+    pub async fn broadcast_bytes(&self, bytes: Vec<u8>) -> Vec<(SocketAddr, io::Result<()>)> {
+        self.multicast_bytes(&self.connected_addrs(), bytes).await
+    }
+
+    /// Sends the message to the given peers and waits until it's delivered.
+    ///
+    /// Returns the delivery result for every peer.
+    pub async fn multicast(
+        &self,
+        addrs: &[SocketAddr],
+        message: Payload,
+    ) -> Vec<(SocketAddr, io::Result<()>)> {
+        trace!(parent: self.inner.node().span(), "multicast send msg to {addrs:?}: {:?}", message);
+        self.inner
+            .multicast(addrs, MessageOrBytes::Payload(message))
+            .await
+    }
+
+    /// Sends the raw bytes to the given peers and waits until they're delivered.
+    ///
+    /// Returns the delivery result for every peer.
+    pub async fn multicast_bytes(
+        &self,
+        addrs: &[SocketAddr],
+        bytes: Vec<u8>,
+    ) -> Vec<(SocketAddr, io::Result<()>)> {
+        trace!(parent: self.inner.node().span(), "multicast send msg to {addrs:?}: {:?}", bytes);
+        self.inner
+            .multicast(addrs, MessageOrBytes::Bytes(bytes))
+            .await
+    }
+
     /// Reads a message from the inbound (internal) queue of the node.
     ///
     /// Messages are sent to the queue when unfiltered by the message filter.
@@ -119,11 +193,108 @@ impl SyntheticNode {
         }
     }
 
+    /// Disconnects from the target address.
+    ///
+    /// Returns true if the node was connected to the target.
+    pub async fn disconnect(&self, target: SocketAddr) -> bool {
+        self.inner.disconnect(target).await
+    }
+
+    /// Returns a new receiver of all the connection events emitted from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Reads the next connection event observed since the node was created.
+    pub async fn recv_event(&mut self) -> ConnectionEvent {
+        loop {
+            match self.events.recv().await {
+                Ok(event) => return event,
+                Err(RecvError::Lagged(n)) => {
+                    warn!(parent: self.inner.node().span(), "skipped {n} connection events")
+                }
+                Err(RecvError::Closed) => panic!("all event senders dropped!"),
+            }
+        }
+    }
+
+    /// Reads the next connection event. If there is no event by the given time there is an
+    /// error returned indicating if timeout occurred.
+    pub async fn recv_event_timeout(&mut self, duration: Duration) -> io::Result<ConnectionEvent> {
+        match timeout(duration, self.recv_event()).await {
+            Ok(event) => Ok(event),
+            Err(_e) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no connection event after {0:.3}s", duration.as_secs_f64()),
+            )),
+        }
+    }
+
+    /// Waits until the connection with the given address is closed and returns the reason.
+    pub async fn wait_for_disconnect(
+        &mut self,
+        addr: SocketAddr,
+        duration: Duration,
+    ) -> io::Result<DisconnectReason> {
+        timeout(duration, async {
+            loop {
+                let event = self.recv_event().await;
+                if event.addr != addr {
+                    continue;
+                }
+                if let ConnectionEventKind::Disconnected(reason) = event.kind {
+                    return reason;
+                }
+            }
+        })
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "{addr} still connected after {0:.3}s",
+                    duration.as_secs_f64()
+                ),
+            )
+        })
+    }
+
+    /// Sets the traffic shaping of the connection with the given address.
+    ///
+    /// If there's no such connection yet, the shaping applies once it's established.
+    pub fn set_shaping(&self, addr: SocketAddr, cfg: ShapingCfg) {
+        self.inner.shaping.set(addr, cfg)
+    }
+
+    /// Shuts down the writing half of the connection with the given address, while still
+    /// reading from it.
+    ///
+    /// Returns `false` if there is no such connection.
+    pub fn half_close(&self, addr: SocketAddr) -> bool {
+        self.is_connected(addr) && self.inner.shaping.half_close(addr)
+    }
+
+    /// Returns the traffic statistics collected since the node was created or the
+    /// statistics were reset.
+    pub fn traffic_stats(&self) -> TrafficSnapshot {
+        self.inner.stats.snapshot()
+    }
+
+    /// Clears the traffic statistics.
+    pub fn reset_traffic_stats(&self) {
+        self.inner.stats.reset()
+    }
+
     /// Gracefully shuts down the node.
     pub async fn shut_down(&self) {
         self.inner.shut_down().await
     }
 
+    /// Returns the base58-encoded public key the node presents in the handshake.
+    pub fn public_key(&self) -> String {
+        keys::encode_public_key(&self.inner.crypto.public_key)
+    }
+
     pub fn listening_addr(&self) -> io::Result<SocketAddr> {
         self.inner.node().listening_addr()
     }
@@ -136,6 +307,12 @@ impl SyntheticNode {
         self.inner.node().num_connected()
     }
 
+    /// Returns the addresses of all the connected peers, e.g. to pick the targets of a
+    /// [multicast](Self::multicast).
+    pub fn connected_addrs(&self) -> Vec<SocketAddr> {
+        self.inner.node().connected_addrs()
+    }
+
     pub fn is_connected_ip(&self, addr: IpAddr) -> bool {
         self.inner.is_connected_ip(addr)
     }
@@ -153,3 +330,92 @@ impl SyntheticNode {
         .is_ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::tools::testing::{is_pong, mock, ping, TIMEOUT};
+
+    // Collects the peers which answered the ping with the given sequence number.
+    async fn recv_pongs(
+        synth_node: &mut SyntheticNode,
+        seq: u32,
+        count: usize,
+    ) -> HashSet<SocketAddr> {
+        let mut peers = HashSet::new();
+        while peers.len() < count {
+            let (addr, message) = synth_node.recv_message_timeout(TIMEOUT).await.unwrap();
+            if is_pong(&message.payload, seq) {
+                assert!(peers.insert(addr), "{addr} answered twice");
+            }
+        }
+        peers
+    }
+
+    #[tokio::test]
+    async fn capture_file_errors_are_returned() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cfg = SynthNodeCfg {
+            capture_path: Some(dir.path().join("missing").join("traffic.zgcap")),
+            ..Default::default()
+        };
+
+        let error = SyntheticNode::try_new(&cfg).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn multicast_to_several_peers() {
+        let mut mocks = vec![];
+        for _ in 0..3 {
+            mocks.push(mock().await);
+        }
+        let mut addrs = mocks.iter().map(|mock| mock.addr()).collect::<Vec<_>>();
+        addrs.sort();
+        // Nobody listens on the port of a listener which is already closed.
+        let unreachable = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut synth_node = SyntheticNode::new(&Default::default()).await;
+        synth_node.set_shaping(unreachable, ShapingCfg::slow_loris());
+        let mut targets = addrs.clone();
+        targets.push(unreachable);
+        let results = synth_node.connect_all(&targets).await;
+        assert_eq!(
+            results.iter().map(|(addr, _)| *addr).collect::<Vec<_>>(),
+            targets
+        );
+        assert!(results[..3].iter().all(|(_, result)| result.is_ok()));
+        assert!(results[3].1.is_err());
+        // The shaping of the failed connection is forgotten.
+        assert!(!synth_node.inner.shaping.half_close(unreachable));
+
+        let mut connected = synth_node.connected_addrs();
+        connected.sort();
+        assert_eq!(connected, addrs);
+
+        // Every peer gets the broadcast message and answers it.
+        let results = synth_node.broadcast(ping(1)).await;
+        assert_eq!(results.len(), addrs.len());
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        let peers = recv_pongs(&mut synth_node, 1, addrs.len()).await;
+        assert_eq!(peers, addrs.iter().copied().collect());
+
+        // Each address gets its own result, including the ones which aren't connected.
+        let results = synth_node
+            .multicast(&[addrs[0], unreachable, addrs[2]], ping(2))
+            .await;
+        assert_eq!(results[0].0, addrs[0]);
+        assert!(results[0].1.is_ok());
+        assert_eq!(results[1].0, unreachable);
+        assert!(results[1].1.is_err());
+        assert_eq!(results[2].0, addrs[2]);
+        assert!(results[2].1.is_ok());
+        let peers = recv_pongs(&mut synth_node, 2, 2).await;
+        assert_eq!(peers, [addrs[0], addrs[2]].into_iter().collect());
+    }
+}
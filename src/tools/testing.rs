@@ -0,0 +1,51 @@
+//! Helpers shared by the unit tests of the tools and the protocol.
+
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio_util::codec::Encoder;
+use tracing::Span;
+
+use crate::{
+    protocol::{
+        codecs::message::{MessageCodec, Payload},
+        proto::{tm_ping::PingType, TmPing},
+    },
+    tools::mock_rippled::{MockRippled, MockRippledCfg},
+};
+
+/// How long the tests wait for a message or an event.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts a mock with the default configuration, listening on a random port of `127.0.0.1`.
+pub async fn mock() -> MockRippled {
+    MockRippled::new(MockRippledCfg::default()).await.unwrap()
+}
+
+/// A ping with the given sequence number.
+pub fn ping(seq: u32) -> Payload {
+    Payload::TmPing(TmPing {
+        r#type: PingType::PtPing as i32,
+        seq: Some(seq),
+        ping_time: None,
+        net_time: None,
+    })
+}
+
+/// A ping with the given sequence number, encoded as it's sent over the wire.
+pub fn ping_frame(seq: u32) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    MessageCodec::new(Span::none())
+        .encode(ping(seq), &mut bytes)
+        .unwrap();
+    bytes
+}
+
+/// Checks whether the payload is the pong to the ping with the given sequence number.
+pub fn is_pong(payload: &Payload, seq: u32) -> bool {
+    matches!(
+        payload,
+        Payload::TmPing(TmPing { r#type, seq: Some(pong_seq), .. })
+            if *r#type == PingType::PtPong as i32 && *pong_seq == seq
+    )
+}
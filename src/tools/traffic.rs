@@ -0,0 +1,446 @@
+//! Per-message-type traffic statistics collected by the [InnerNode](crate::tools::inner_node::InnerNode).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use histogram::Histogram;
+
+use crate::protocol::proto::MessageType;
+
+/// Metric name for the number of messages.
+pub const METRIC_MESSAGES: &str = "synth_node_messages";
+/// Metric name for the number of bytes.
+pub const METRIC_BYTES: &str = "synth_node_bytes";
+/// Metric name for the size of single messages in bytes.
+pub const METRIC_MESSAGE_SIZE: &str = "synth_node_message_bytes";
+/// Metric name for the time between two consecutive messages of the same type from the same peer.
+pub const METRIC_INTER_ARRIVAL: &str = "synth_node_inter_arrival_ms";
+
+/// The direction of the traffic, as seen by the synthetic node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Inbound => f.pad("inbound"),
+            Self::Outbound => f.pad("outbound"),
+        }
+    }
+}
+
+/// Statistics for the time elapsed between consecutive messages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InterArrival {
+    /// The number of measured intervals.
+    pub count: u64,
+    /// The sum of all intervals.
+    pub total: Duration,
+    /// The shortest interval.
+    pub min: Option<Duration>,
+    /// The longest interval.
+    pub max: Option<Duration>,
+}
+
+impl InterArrival {
+    fn record(&mut self, interval: Duration) {
+        self.count += 1;
+        self.total += interval;
+        self.min = Some(self.min.map_or(interval, |min| min.min(interval)));
+        self.max = Some(self.max.map_or(interval, |max| max.max(interval)));
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.total += other.total;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// The average interval, if any were measured.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count != 0).then(|| self.total / self.count as u32)
+    }
+}
+
+/// The distribution of the message sizes in bytes.
+///
+/// The sizes are exact up to 127 bytes, larger ones are kept within 1/64 of their value.
+#[derive(Clone)]
+pub struct MessageSizes {
+    histogram: Histogram,
+}
+
+impl MessageSizes {
+    // The largest size kept apart, larger ones are counted as this one (128 MiB).
+    const MAX: u64 = (1 << 27) - 1;
+
+    fn record(&mut self, bytes: usize) {
+        // Only a size above the maximum could fail, which is clamped beforehand.
+        let _ = self.histogram.increment((bytes as u64).min(Self::MAX), 1);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        // Only histograms with different parameters fail to merge.
+        let _ = self.histogram.merge(&other.histogram);
+    }
+
+    /// The size which the given percentage (0 to 100) of the messages don't exceed, if any
+    /// were recorded.
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        self.histogram
+            .percentile(percentile)
+            .ok()
+            .map(|bucket| bucket.high())
+    }
+
+    /// The median size.
+    pub fn median(&self) -> Option<u64> {
+        self.percentile(50.0)
+    }
+}
+
+impl Default for MessageSizes {
+    fn default() -> Self {
+        Self {
+            // The parameters are valid, so it can't fail.
+            histogram: Histogram::new(0, 7, 27).unwrap(),
+        }
+    }
+}
+
+impl fmt::Debug for MessageSizes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MessageSizes")
+            .field("p50", &self.percentile(50.0))
+            .field("p90", &self.percentile(90.0))
+            .field("p99", &self.percentile(99.0))
+            .field("max", &self.percentile(100.0))
+            .finish()
+    }
+}
+
+/// Traffic totals for a single direction, message type and peer.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficTotals {
+    /// The number of messages.
+    pub messages: u64,
+    /// The number of bytes on the wire, including headers.
+    pub bytes: u64,
+    /// The sizes of the single messages.
+    pub sizes: MessageSizes,
+    /// Time between consecutive messages.
+    pub inter_arrival: InterArrival,
+}
+
+impl TrafficTotals {
+    fn merge(&mut self, other: &Self) {
+        self.messages += other.messages;
+        self.bytes += other.bytes;
+        self.sizes.merge(&other.sizes);
+        self.inter_arrival.merge(&other.inter_arrival);
+    }
+}
+
+/// The key under which traffic is counted.
+///
+/// The message type is not set for raw bytes which don't form a known message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TrafficKey {
+    pub direction: Direction,
+    pub message_type: Option<MessageType>,
+    pub peer: SocketAddr,
+}
+
+#[derive(Default)]
+struct Entry {
+    totals: TrafficTotals,
+    last_seen: Option<Instant>,
+}
+
+struct State {
+    since: Instant,
+    entries: HashMap<TrafficKey, Entry>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            since: Instant::now(),
+            entries: Default::default(),
+        }
+    }
+}
+
+/// Collects traffic statistics and mirrors them to the `metrics` crate.
+#[derive(Clone, Default)]
+pub struct TrafficStats {
+    state: Arc<Mutex<State>>,
+}
+
+impl TrafficStats {
+    /// Records a single message.
+    pub fn record(
+        &self,
+        direction: Direction,
+        message_type: Option<MessageType>,
+        peer: SocketAddr,
+        bytes: usize,
+    ) {
+        let now = Instant::now();
+        let key = TrafficKey {
+            direction,
+            message_type,
+            peer,
+        };
+
+        let interval = {
+            let mut state = self.state.lock().unwrap();
+            let entry = state.entries.entry(key).or_default();
+
+            entry.totals.messages += 1;
+            entry.totals.bytes += bytes as u64;
+            entry.totals.sizes.record(bytes);
+            let interval = entry.last_seen.map(|last_seen| now - last_seen);
+            if let Some(interval) = interval {
+                entry.totals.inter_arrival.record(interval);
+            }
+            entry.last_seen = Some(now);
+
+            interval
+        };
+
+        let labels = [
+            ("direction", direction.to_string()),
+            ("type", type_name(message_type)),
+            ("peer", peer.to_string()),
+        ];
+        metrics::counter!(METRIC_MESSAGES, 1, &labels);
+        metrics::counter!(METRIC_BYTES, bytes as u64, &labels);
+        metrics::histogram!(METRIC_MESSAGE_SIZE, bytes as f64, &labels);
+        if let Some(interval) = interval {
+            metrics::histogram!(
+                METRIC_INTER_ARRIVAL,
+                interval.as_secs_f64() * 1000.0,
+                &labels
+            );
+        }
+    }
+
+    /// Returns a copy of the statistics collected so far.
+    pub fn snapshot(&self) -> TrafficSnapshot {
+        let state = self.state.lock().unwrap();
+
+        TrafficSnapshot {
+            elapsed: state.since.elapsed(),
+            entries: state
+                .entries
+                .iter()
+                .map(|(key, entry)| (*key, entry.totals.clone()))
+                .collect(),
+        }
+    }
+
+    /// Clears the statistics collected so far.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = Default::default();
+    }
+}
+
+/// A point-in-time copy of the [TrafficStats].
+#[derive(Debug, Clone, Default)]
+pub struct TrafficSnapshot {
+    /// Time since the statistics were started or reset.
+    pub elapsed: Duration,
+    /// Totals for every direction, message type and peer.
+    pub entries: BTreeMap<TrafficKey, TrafficTotals>,
+}
+
+impl TrafficSnapshot {
+    /// Totals for each message type in the given direction, summed over all peers.
+    pub fn by_type(&self, direction: Direction) -> BTreeMap<Option<MessageType>, TrafficTotals> {
+        self.aggregate(direction, |key| key.message_type)
+    }
+
+    /// Totals for each peer in the given direction, summed over all message types.
+    pub fn by_peer(&self, direction: Direction) -> BTreeMap<SocketAddr, TrafficTotals> {
+        self.aggregate(direction, |key| key.peer)
+    }
+
+    /// Totals for a single message type in the given direction, summed over all peers.
+    pub fn totals(&self, direction: Direction, message_type: MessageType) -> TrafficTotals {
+        self.by_type(direction)
+            .remove(&Some(message_type))
+            .unwrap_or_default()
+    }
+
+    /// The number of messages of the given type per minute.
+    pub fn per_minute(&self, direction: Direction, message_type: MessageType) -> f64 {
+        per_minute(self.totals(direction, message_type).messages, self.elapsed)
+    }
+
+    fn aggregate<K: Ord>(
+        &self,
+        direction: Direction,
+        key_fn: impl Fn(&TrafficKey) -> K,
+    ) -> BTreeMap<K, TrafficTotals> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| key.direction == direction)
+            .fold(BTreeMap::new(), |mut map, (key, totals)| {
+                map.entry(key_fn(key)).or_default().merge(totals);
+                map
+            })
+    }
+}
+
+impl fmt::Display for TrafficSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "traffic over {:.1}s:", self.elapsed.as_secs_f64())?;
+        for direction in [Direction::Inbound, Direction::Outbound] {
+            for (message_type, totals) in self.by_type(direction) {
+                let mean = totals
+                    .inter_arrival
+                    .mean()
+                    .map(|mean| format!("{:.3}s", mean.as_secs_f64()))
+                    .unwrap_or_else(|| "-".into());
+                let size = |percentile| {
+                    totals
+                        .sizes
+                        .percentile(percentile)
+                        .map(|size| size.to_string())
+                        .unwrap_or_else(|| "-".into())
+                };
+
+                writeln!(
+                    f,
+                    "{direction:8} {:28} messages: {:6} bytes: {:9} size p50: {:7} p99: {:7} per minute: {:8.2} mean interval: {mean}",
+                    type_name(message_type),
+                    totals.messages,
+                    totals.bytes,
+                    size(50.0),
+                    size(99.0),
+                    per_minute(totals.messages, self.elapsed),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The rate of the messages over the elapsed time, zero if no time has elapsed.
+fn per_minute(messages: u64, elapsed: Duration) -> f64 {
+    let minutes = elapsed.as_secs_f64() / 60.0;
+    if minutes == 0.0 {
+        return 0.0;
+    }
+
+    messages as f64 / minutes
+}
+
+// A label for the message type, raw bytes are labeled as such.
+fn type_name(message_type: Option<MessageType>) -> String {
+    message_type
+        .map(|message_type| format!("{message_type:?}"))
+        .unwrap_or_else(|| "raw".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_by_type_and_peer() {
+        let stats = TrafficStats::default();
+        let peer1: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let peer2: SocketAddr = "127.0.0.2:1000".parse().unwrap();
+
+        stats.record(Direction::Inbound, Some(MessageType::MtPing), peer1, 10);
+        stats.record(Direction::Inbound, Some(MessageType::MtPing), peer1, 10);
+        stats.record(Direction::Inbound, Some(MessageType::MtPing), peer2, 10);
+        stats.record(
+            Direction::Inbound,
+            Some(MessageType::MtValidation),
+            peer2,
+            100,
+        );
+        stats.record(Direction::Outbound, None, peer1, 5);
+
+        let snapshot = stats.snapshot();
+
+        let ping = snapshot.totals(Direction::Inbound, MessageType::MtPing);
+        assert_eq!(ping.messages, 3);
+        assert_eq!(ping.bytes, 30);
+        // Only the second message from the first peer has a predecessor.
+        assert_eq!(ping.inter_arrival.count, 1);
+
+        let by_peer = snapshot.by_peer(Direction::Inbound);
+        assert_eq!(by_peer[&peer1].messages, 2);
+        assert_eq!(by_peer[&peer2].bytes, 110);
+        assert_eq!(by_peer[&peer2].sizes.median(), Some(10));
+        assert_eq!(by_peer[&peer2].sizes.percentile(100.0), Some(100));
+
+        assert_eq!(snapshot.by_type(Direction::Outbound)[&None].messages, 1);
+
+        stats.reset();
+        assert!(stats.snapshot().entries.is_empty());
+    }
+
+    #[test]
+    fn message_size_percentiles() {
+        let stats = TrafficStats::default();
+        let peer: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        for bytes in 1..=100 {
+            stats.record(Direction::Inbound, Some(MessageType::MtPing), peer, bytes);
+        }
+        stats.record(Direction::Inbound, Some(MessageType::MtPing), peer, 10_000);
+
+        let sizes = stats
+            .snapshot()
+            .totals(Direction::Inbound, MessageType::MtPing)
+            .sizes;
+        assert_eq!(sizes.median(), Some(51));
+        assert_eq!(sizes.percentile(99.0), Some(100));
+        // Large sizes fall into buckets of 1/64 of their value.
+        let max = sizes.percentile(100.0).unwrap();
+        assert!((10_000..10_000 + 10_000 / 64).contains(&max), "{max}");
+
+        assert_eq!(MessageSizes::default().median(), None);
+    }
+
+    #[test]
+    fn rates_without_elapsed_time() {
+        let stats = TrafficStats::default();
+        let peer: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        stats.record(Direction::Inbound, Some(MessageType::MtPing), peer, 10);
+
+        let mut snapshot = stats.snapshot();
+        snapshot.elapsed = Duration::ZERO;
+        assert_eq!(
+            snapshot.per_minute(Direction::Inbound, MessageType::MtPing),
+            0.0
+        );
+
+        let report = snapshot.to_string();
+        assert!(report.contains("per minute:     0.00"), "{report}");
+        assert!(
+            !report.contains("inf") && !report.contains("NaN"),
+            "{report}"
+        );
+    }
+}
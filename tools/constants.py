@@ -1,4 +0,0 @@
-TEST_ACCOUNT = "rNGknFCRBZguXcPqC63k6xTZnonSe6ZuWt"
-GENESIS_ACCOUNT = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh"
-GENESIS_SEED = "snoPBrXtMeMyMHUVTgbuqAfg1SUTb"
-JSON_RPC_URL = "http://127.0.0.1:5005/"